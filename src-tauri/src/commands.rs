@@ -1,13 +1,478 @@
 // Tauri IPC commands for frontend communication
 
+use crate::coverage::{coverage, CoverageQuery, CoverageReport};
+use crate::jobs::{Emit, JobContext, JobId, Jobs};
+use crate::links::{GraphExport, Reached, TraceDirection, TraceGraph, TracePath};
+use crate::reqif::document::{Document, DocumentFormat};
+use crate::reqif::store::DocumentStore;
+use crate::reqif::table::{TableCache, TablePage, TableQuery};
+use crate::reqif::undo::{Edit, History};
+use crate::reqif::validate::{validate_until, Diagnostic};
+use crate::reqif::views::{SavedView, ViewStorage};
+use crate::search::filter::{filter_objects, parse_filter};
+use crate::search::index::{SearchHit, SearchIndex};
+use crate::search::query::parse_query;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Application state managed by Tauri
+#[derive(Default)]
+pub struct AppState {
+    /// Document currently open, if any
+    pub document: Mutex<Option<Document>>,
+    /// Search index of the open document, once built in the background
+    pub search: Mutex<Option<SearchIndex>>,
+    /// Trace graph of the open document, once built in the background
+    pub graph: Mutex<Option<TraceGraph>>,
+    /// Row order of the requirements table last shown
+    pub table: Mutex<TableCache>,
+}
+
+impl AppState {
+    /// Make `document` the open document
+    fn replace_document(&self, document: Document) -> Result<(), String> {
+        let mut current = self.document.lock().map_err(|e| e.to_string())?;
+        *self.search.lock().map_err(|e| e.to_string())? = None;
+        *self.graph.lock().map_err(|e| e.to_string())? = None;
+        *current = Some(document);
+        Ok(())
+    }
+
+    /// Bring the search index and trace graph, where built, up to date with
+    /// the edits made to `document` since the last call
+    fn catch_up(&self, document: &mut Document) -> Result<(), String> {
+        let changed = document.take_changed();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        if let Some(index) = self.search.lock().map_err(|e| e.to_string())?.as_mut() {
+            index
+                .apply_changes(document.store(), &changed)
+                .map_err(|e| e.to_string())?;
+        }
+        if let Some(graph) = self.graph.lock().map_err(|e| e.to_string())?.as_mut() {
+            graph.apply_changes(document.store(), &changed);
+        }
+        Ok(())
+    }
+
+    /// Window `query` of a requirements table of `document`, reusing the
+    /// row order while neither the document nor the rest of the query change
+    fn table_page(&self, document: &Document, query: &TableQuery) -> Result<TablePage, String> {
+        self.table
+            .lock()
+            .map_err(|e| e.to_string())?
+            .page(document.store(), document.id(), document.revision(), query)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Run `f` on the trace graph and store of `document`; fails until the
+    /// indexing job has built the graph
+    fn with_graph<T>(
+        &self,
+        document: &mut Document,
+        f: impl FnOnce(&TraceGraph, &DocumentStore) -> T,
+    ) -> Result<T, String> {
+        self.catch_up(document)?;
+        let graph = self.graph.lock().map_err(|e| e.to_string())?;
+        let graph = graph
+            .as_ref()
+            .ok_or_else(|| "the trace graph is not ready yet".to_string())?;
+        Ok(f(graph, document.store()))
+    }
+}
+
+/// Summary of the open document sent to the frontend
+#[derive(Debug, Serialize)]
+pub struct DocumentInfo {
+    pub path: String,
+    pub format: DocumentFormat,
+    pub title: Option<String>,
+    pub dirty: bool,
+    pub spec_objects: usize,
+    pub specifications: usize,
+    /// Validation results; only filled in when the document is opened
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl DocumentInfo {
+    fn of(document: &Document) -> Self {
+        let reqif = document.store().reqif();
+        Self {
+            path: document.path().display().to_string(),
+            format: document.format(),
+            title: reqif.header.title.clone(),
+            dirty: document.is_dirty(),
+            spec_objects: reqif.core_content.spec_objects.len(),
+            specifications: reqif.core_content.specifications.len(),
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+/// Run `f` on the open document
+fn with_document<T>(
+    state: &AppState,
+    f: impl FnOnce(&mut Document) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut document = state.document.lock().map_err(|e| e.to_string())?;
+    match document.as_mut() {
+        Some(document) => f(document),
+        None => Err("no document is open".to_string()),
+    }
+}
+
 #[tauri::command]
 pub fn greet(name: &str) -> String {
     format!("Hello, {}! Welcome to ReqSmith.", name)
 }
 
-// Future commands will be added here:
-// - open_reqif
-// - save_reqif
-// - get_requirements
-// - search
-// etc.
+/// Summary of the open document, including whether it has unsaved changes,
+/// or `None` when no document is open
+#[tauri::command]
+pub fn document_status(state: State<'_, AppState>) -> Result<Option<DocumentInfo>, String> {
+    let document = state.document.lock().map_err(|e| e.to_string())?;
+    Ok(document.as_ref().map(DocumentInfo::of))
+}
+
+/// One window of a specification's requirements table. Async so that
+/// ordering a large table stays off the main thread.
+#[tauri::command]
+pub async fn get_requirements(
+    query: TableQuery,
+    state: State<'_, AppState>,
+) -> Result<TablePage, String> {
+    with_document(&state, |document| state.table_page(document, &query))
+}
+
+/// Saved views of the open document
+#[tauri::command]
+pub fn list_views(state: State<'_, AppState>) -> Result<Vec<SavedView>, String> {
+    with_document(&state, |document| Ok(document.views().to_vec()))
+}
+
+/// Create a view, or replace the view with the same name
+#[tauri::command]
+pub fn save_view(view: SavedView, state: State<'_, AppState>) -> Result<Vec<SavedView>, String> {
+    with_document(&state, |document| {
+        document.put_view(view);
+        Ok(document.views().to_vec())
+    })
+}
+
+#[tauri::command]
+pub fn delete_view(name: String, state: State<'_, AppState>) -> Result<Vec<SavedView>, String> {
+    with_document(&state, |document| {
+        if !document.delete_view(&name) {
+            return Err(format!("unknown view '{}'", name));
+        }
+        Ok(document.views().to_vec())
+    })
+}
+
+/// One window of the requirements table laid out by view `name`
+#[tauri::command]
+pub async fn apply_view(
+    name: String,
+    offset: usize,
+    limit: usize,
+    state: State<'_, AppState>,
+) -> Result<TablePage, String> {
+    with_document(&state, |document| {
+        let view = document
+            .view(&name)
+            .ok_or_else(|| format!("unknown view '{}'", name))?;
+        state.table_page(document, &view.query(offset, limit))
+    })
+}
+
+/// Keep the open document's views in the document itself or in a sidecar
+/// file, from the next save on
+#[tauri::command]
+pub fn set_view_storage(storage: ViewStorage, state: State<'_, AppState>) -> Result<(), String> {
+    with_document(&state, |document| {
+        document.set_view_storage(storage);
+        Ok(())
+    })
+}
+
+/// Up to `limit` requirements matching `query`, best first, with
+/// highlighted snippets. The index catches up with edits before searching;
+/// until the indexing job has built it, searching fails.
+#[tauri::command]
+pub fn search_requirements(
+    query: String,
+    limit: usize,
+    state: State<'_, AppState>,
+) -> Result<Vec<SearchHit>, String> {
+    let query = parse_query(&query).map_err(|e| e.to_string())?;
+    with_document(&state, |document| {
+        state.catch_up(document)?;
+        let search = state.search.lock().map_err(|e| e.to_string())?;
+        let index = search
+            .as_ref()
+            .ok_or_else(|| "the search index is not ready yet".to_string())?;
+        index.search(&query, limit).map_err(|e| e.to_string())
+    })
+}
+
+/// Identifiers of the requirements matching a structured filter such as
+/// `Priority > 5 AND Status in (Draft, Review)`, in document order
+#[tauri::command]
+pub fn filter_requirements(
+    filter: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    let filter = parse_filter(&filter).map_err(|e| e.to_string())?;
+    with_document(&state, |document| {
+        let objects = filter_objects(document.store(), &filter).map_err(|e| e.to_string())?;
+        Ok(objects
+            .into_iter()
+            .map(|o| o.ident.identifier.clone())
+            .collect())
+    })
+}
+
+/// Requirements reached from `object` by following relations of the given
+/// types (all when empty) up to `depth` levels (unlimited when `None`)
+#[tauri::command]
+pub fn trace_requirements(
+    object: String,
+    direction: TraceDirection,
+    depth: Option<usize>,
+    relation_types: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Reached>, String> {
+    with_document(&state, |document| {
+        state.with_graph(document, |graph, _| {
+            graph.traverse(&object, direction, depth, &relation_types)
+        })
+    })
+}
+
+/// Shortest chain of relations between two requirements, if linked
+#[tauri::command]
+pub fn trace_path(
+    from: String,
+    to: String,
+    state: State<'_, AppState>,
+) -> Result<Option<TracePath>, String> {
+    with_document(&state, |document| {
+        state.with_graph(document, |graph, _| graph.shortest_path(&from, &to))
+    })
+}
+
+/// Groups of requirements whose relations form cycles
+#[tauri::command]
+pub fn trace_cycles(state: State<'_, AppState>) -> Result<Vec<Vec<String>>, String> {
+    with_document(&state, |document| {
+        state.with_graph(document, |graph, _| graph.cycles())
+    })
+}
+
+/// Nodes and edges around `objects` for the graph view
+#[tauri::command]
+pub fn trace_graph(
+    objects: Vec<String>,
+    direction: TraceDirection,
+    depth: Option<usize>,
+    relation_types: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<GraphExport, String> {
+    with_document(&state, |document| {
+        state.with_graph(document, |graph, store| {
+            graph.subgraph(store, &objects, direction, depth, &relation_types)
+        })
+    })
+}
+
+/// How far the requirements of one specification are covered by another
+/// through a relation type, and which have no links at all
+#[tauri::command]
+pub fn coverage_report(
+    query: CoverageQuery,
+    state: State<'_, AppState>,
+) -> Result<CoverageReport, String> {
+    with_document(&state, |document| {
+        coverage(document.store(), &query).map_err(|e| e.to_string())
+    })
+}
+
+/// Apply an edit to the open document as one undo step, or as part of the
+/// open edit group
+#[tauri::command]
+pub fn apply_edit(edit: Edit, state: State<'_, AppState>) -> Result<History, String> {
+    with_document(&state, |document| {
+        document.apply(edit).map_err(|e| e.to_string())?;
+        Ok(document.history())
+    })
+}
+
+/// Collect the following edits into one undo step labelled `label`
+#[tauri::command]
+pub fn begin_edit_group(label: String, state: State<'_, AppState>) -> Result<History, String> {
+    with_document(&state, |document| {
+        document.journal_mut().begin_group(&label);
+        Ok(document.history())
+    })
+}
+
+#[tauri::command]
+pub fn end_edit_group(state: State<'_, AppState>) -> Result<History, String> {
+    with_document(&state, |document| {
+        document.journal_mut().end_group();
+        Ok(document.history())
+    })
+}
+
+#[tauri::command]
+pub fn undo_edit(state: State<'_, AppState>) -> Result<History, String> {
+    with_document(&state, |document| {
+        document.undo().map_err(|e| e.to_string())?;
+        Ok(document.history())
+    })
+}
+
+#[tauri::command]
+pub fn redo_edit(state: State<'_, AppState>) -> Result<History, String> {
+    with_document(&state, |document| {
+        document.redo().map_err(|e| e.to_string())?;
+        Ok(document.history())
+    })
+}
+
+/// Labels of the steps undo and redo would act on
+#[tauri::command]
+pub fn edit_history(state: State<'_, AppState>) -> Result<History, String> {
+    with_document(&state, |document| Ok(document.history()))
+}
+
+/// Events of background jobs go to every window
+fn emitter(app: &AppHandle) -> Emit {
+    let app = app.clone();
+    Arc::new(move |event, payload| {
+        let _ = app.emit(event, payload);
+    })
+}
+
+/// Open a `.reqif` or `.reqifz` file in the background, reporting progress
+/// while it is read; the job's value is the opened document's info
+#[tauri::command]
+pub fn start_open_reqif(path: String, app: AppHandle, jobs: State<'_, Jobs>) -> JobId {
+    let emit = emitter(&app);
+    jobs.start("open", emit, move |context| {
+        let file = File::open(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let reader = BufReader::new(context.reader(file, total, "reading"));
+        let document = Document::from_reader(&path, reader).map_err(|e| e.to_string())?;
+        context.check()?;
+        context.progress(0, None, "validating");
+        let mut info = DocumentInfo::of(&document);
+        info.diagnostics =
+            validate_until(document.store(), context.cancel_flag()).ok_or("cancelled")?;
+        context.check()?;
+        app.state::<AppState>().replace_document(document)?;
+        start_index_job(&app, &app.state::<Jobs>());
+        Ok(info)
+    })
+}
+
+/// Build the search index and trace graph of the open document in the
+/// background; opening a document starts this by itself
+#[tauri::command]
+pub fn start_indexing(app: AppHandle, jobs: State<'_, Jobs>) -> JobId {
+    start_index_job(&app, &jobs)
+}
+
+fn start_index_job(app: &AppHandle, jobs: &Jobs) -> JobId {
+    let emit = emitter(app);
+    let app = app.clone();
+    jobs.start("index", emit, move |context| {
+        let state = app.state::<AppState>();
+        // Edits made from here on are replayed onto the new index
+        let (id, store) = with_document(&state, |document| {
+            state.catch_up(document)?;
+            Ok((document.id(), document.store().clone()))
+        })?;
+        let total = store.content().spec_objects.len() as u64;
+        let index = SearchIndex::build_until(&store, |done| {
+            context.progress(done as u64, Some(total), "indexing");
+            !context.is_cancelled()
+        })
+        .map_err(|e| e.to_string())?
+        .ok_or("cancelled")?;
+        context.progress(total, Some(total), "tracing");
+        let graph = TraceGraph::build(&store);
+        context.check()?;
+        with_document(&state, |document| {
+            if document.id() != id {
+                return Err("the document was closed while indexing".to_string());
+            }
+            *state.search.lock().map_err(|e| e.to_string())? = Some(index);
+            *state.graph.lock().map_err(|e| e.to_string())? = Some(graph);
+            state.catch_up(document)
+        })
+    })
+}
+
+/// Validate the open document in the background. The document is only
+/// locked while it is copied, so editing can go on during validation.
+#[tauri::command]
+pub fn start_validation(app: AppHandle, jobs: State<'_, Jobs>) -> JobId {
+    let emit = emitter(&app);
+    jobs.start("validate", emit, move |context| {
+        let store = with_document(&app.state::<AppState>(), |document| {
+            Ok(document.store().clone())
+        })?;
+        context.progress(0, None, "validating");
+        validate_until(&store, context.cancel_flag()).ok_or_else(|| "cancelled".to_string())
+    })
+}
+
+/// Save the open document to the file it was opened from, in the
+/// background; the job's value is the document's info
+#[tauri::command]
+pub fn start_save_reqif(app: AppHandle, jobs: State<'_, Jobs>) -> JobId {
+    let emit = emitter(&app);
+    jobs.start("save", emit, move |context| save(&app, None, context))
+}
+
+/// Export the open document to `path` in the background and continue
+/// editing it there
+#[tauri::command]
+pub fn start_save_reqif_as(path: String, app: AppHandle, jobs: State<'_, Jobs>) -> JobId {
+    let emit = emitter(&app);
+    jobs.start("export", emit, move |context| {
+        save(&app, Some(path), context)
+    })
+}
+
+/// Write the open document to `path`, or to its own file. The document is
+/// only locked while it is copied, so editing can go on during the write.
+fn save(
+    app: &AppHandle,
+    path: Option<String>,
+    context: &JobContext,
+) -> Result<DocumentInfo, String> {
+    let state = app.state::<AppState>();
+    let copy = with_document(&state, |document| {
+        let path = path.map_or_else(|| document.path().to_path_buf(), Into::into);
+        document.save_copy(path).map_err(|e| e.to_string())
+    })?;
+    context.progress(0, None, "writing");
+    copy.write(context.cancel_flag())
+        .map_err(|e| e.to_string())?;
+    with_document(&state, |document| {
+        document.saved(copy);
+        Ok(DocumentInfo::of(document))
+    })
+}
+
+/// Ask a background job to stop. Returns whether it was still running.
+#[tauri::command]
+pub fn cancel_job(id: JobId, jobs: State<'_, Jobs>) -> bool {
+    jobs.cancel(id)
+}
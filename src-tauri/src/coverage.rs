@@ -0,0 +1,223 @@
+// Coverage analysis - Which requirements of one specification are linked to
+// another specification by a given relation type, and which have no links
+
+use crate::links::label;
+use crate::reqif::model::*;
+use crate::reqif::store::DocumentStore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CoverageError {
+    #[error("unknown specification '{0}'")]
+    UnknownSpecification(String),
+    #[error("unknown relation type '{0}'")]
+    UnknownRelationType(String),
+}
+
+/// Coverage of `source` by `target` through relations of `relation_type`
+#[derive(Debug, Clone, Deserialize)]
+pub struct CoverageQuery {
+    pub source: String,
+    pub target: String,
+    /// Identifier or LONG-NAME of a SpecRelation type
+    pub relation_type: String,
+}
+
+/// Source object and the target objects linked to it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoveredObject {
+    pub object: String,
+    pub label: String,
+    pub linked: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UncoveredObject {
+    pub object: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoverageReport {
+    pub source: String,
+    pub target: String,
+    /// Identifier of the relation type
+    pub relation_type: String,
+    /// Number of objects in the source specification
+    pub total: usize,
+    pub covered: Vec<CoveredObject>,
+    pub uncovered: Vec<UncoveredObject>,
+    /// Share of source objects covered, 0 to 100; 100 for an empty source
+    pub percentage: f64,
+    /// Objects of either specification without any relation
+    pub orphans: Vec<String>,
+}
+
+/// Coverage report for `query`. A source object is covered when a relation
+/// of the type links it, in either direction, to an object placed in the
+/// target specification. Objects are listed in hierarchy order.
+pub fn coverage(
+    store: &DocumentStore,
+    query: &CoverageQuery,
+) -> Result<CoverageReport, CoverageError> {
+    let source = spec_objects(store, &query.source)?;
+    let target = spec_objects(store, &query.target)?;
+    let relation_type = store
+        .content()
+        .spec_types
+        .iter()
+        .find(|t| {
+            t.kind == SpecTypeKind::SpecRelationType
+                && (t.ident.identifier == query.relation_type
+                    || t.ident.long_name.as_deref() == Some(query.relation_type.as_str()))
+        })
+        .map(|t| t.ident.identifier.clone())
+        .ok_or_else(|| CoverageError::UnknownRelationType(query.relation_type.clone()))?;
+
+    let in_target: HashSet<&str> = target.iter().map(|o| o.ident.identifier.as_str()).collect();
+    let mut covered = Vec::new();
+    let mut uncovered = Vec::new();
+    for object in &source {
+        let id = &object.ident.identifier;
+        let outgoing = store.outgoing_relations(id).map(|r| (r, &r.target));
+        let incoming = store.incoming_relations(id).map(|r| (r, &r.source));
+        let mut linked: Vec<String> = Vec::new();
+        for (_, other) in outgoing
+            .chain(incoming)
+            .filter(|(r, other)| r.spec_type == relation_type && in_target.contains(other.as_str()))
+        {
+            if !linked.contains(other) {
+                linked.push(other.clone());
+            }
+        }
+        if linked.is_empty() {
+            uncovered.push(UncoveredObject {
+                object: id.clone(),
+                label: label(object),
+            });
+        } else {
+            covered.push(CoveredObject {
+                object: id.clone(),
+                label: label(object),
+                linked,
+            });
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let orphans = source
+        .iter()
+        .chain(&target)
+        .map(|o| o.ident.identifier.as_str())
+        .filter(|id| seen.insert(*id))
+        .filter(|id| {
+            store.outgoing_relations(id).next().is_none()
+                && store.incoming_relations(id).next().is_none()
+        })
+        .map(str::to_string)
+        .collect();
+    let total = source.len();
+    let percentage = if total == 0 {
+        100.0
+    } else {
+        covered.len() as f64 * 100.0 / total as f64
+    };
+    Ok(CoverageReport {
+        source: query.source.clone(),
+        target: query.target.clone(),
+        relation_type,
+        total,
+        covered,
+        uncovered,
+        percentage,
+        orphans,
+    })
+}
+
+/// Distinct objects placed in specification `id`, depth-first
+fn spec_objects<'a>(
+    store: &'a DocumentStore,
+    id: &str,
+) -> Result<Vec<&'a SpecObject>, CoverageError> {
+    let specification = store
+        .specification(id)
+        .ok_or_else(|| CoverageError::UnknownSpecification(id.to_string()))?;
+    let mut objects = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<&SpecHierarchy> = specification.children.iter().rev().collect();
+    while let Some(hierarchy) = stack.pop() {
+        if let Some(object) = store.hierarchy_object(hierarchy) {
+            if seen.insert(object.ident.identifier.as_str()) {
+                objects.push(object);
+            }
+        }
+        stack.extend(hierarchy.children.iter().rev());
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+
+    const TRACED: &str = include_str!("../../tests/fixtures/traced.reqif");
+
+    fn query(source: &str, target: &str, relation_type: &str) -> CoverageQuery {
+        CoverageQuery {
+            source: source.into(),
+            target: target.into(),
+            relation_type: relation_type.into(),
+        }
+    }
+
+    #[test]
+    fn test_coverage_and_orphans() {
+        let store = DocumentStore::new(parser::parse_str(TRACED).unwrap());
+        let report = coverage(&store, &query("spec-sys", "spec-sw", "satisfies")).unwrap();
+        assert_eq!(report.relation_type, "srt-satisfies");
+        assert_eq!(report.total, 4);
+        let covered: Vec<(&str, Vec<&str>)> = report
+            .covered
+            .iter()
+            .map(|c| {
+                let linked = c.linked.iter().map(String::as_str).collect();
+                (c.object.as_str(), linked)
+            })
+            .collect();
+        assert_eq!(
+            covered,
+            [
+                ("sys-001", vec!["sw-001", "sw-003"]),
+                ("sys-002", vec!["sw-002"])
+            ]
+        );
+        let uncovered: Vec<&str> = report.uncovered.iter().map(|u| u.object.as_str()).collect();
+        assert_eq!(uncovered, ["sys-003", "sys-004"]);
+        assert_eq!(report.uncovered[0].label, "Log user actions");
+        assert_eq!(report.percentage, 50.0);
+        assert_eq!(report.orphans, ["sys-003", "sys-004", "sw-004"]);
+
+        // Tests verify system requirements directly or through software
+        let report = coverage(&store, &query("spec-sys", "spec-test", "srt-verifies")).unwrap();
+        assert_eq!(report.covered.len(), 1);
+        assert_eq!(report.percentage, 25.0);
+        let report = coverage(&store, &query("spec-sw", "spec-test", "verifies")).unwrap();
+        assert_eq!(report.covered[0].object, "sw-002");
+    }
+
+    #[test]
+    fn test_unknown_names_are_errors() {
+        let store = DocumentStore::new(parser::parse_str(TRACED).unwrap());
+        assert_eq!(
+            coverage(&store, &query("spec-sys", "spec-404", "satisfies")).unwrap_err(),
+            CoverageError::UnknownSpecification("spec-404".into())
+        );
+        assert_eq!(
+            coverage(&store, &query("spec-sys", "spec-sw", "Requirement")).unwrap_err(),
+            CoverageError::UnknownRelationType("Requirement".into())
+        );
+    }
+}
@@ -0,0 +1,309 @@
+// Background jobs - Long-running operations on the async runtime that report
+// progress to the frontend and can be cancelled
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Event carrying a [`JobProgress`] payload
+pub const PROGRESS_EVENT: &str = "job-progress";
+/// Event carrying a [`JobFinished`] payload
+pub const FINISHED_EVENT: &str = "job-finished";
+
+pub type JobId = u64;
+
+/// Sends an event with a JSON payload to the frontend
+pub type Emit = Arc<dyn Fn(&str, serde_json::Value) + Send + Sync>;
+
+/// How far a job has got
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgress {
+    pub job: JobId,
+    pub name: String,
+    pub done: u64,
+    /// Amount of work in the same unit as `done`, when known
+    pub total: Option<u64>,
+    pub message: String,
+}
+
+/// Result envelope shared by every job
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobOutcome<T> {
+    Completed { value: T },
+    Failed { error: String },
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobFinished<T> {
+    pub job: JobId,
+    pub name: String,
+    #[serde(flatten)]
+    pub outcome: JobOutcome<T>,
+}
+
+/// Handle given to a running job to report progress and notice cancellation
+#[derive(Clone)]
+pub struct JobContext {
+    id: JobId,
+    name: String,
+    cancelled: Arc<AtomicBool>,
+    emit: Emit,
+}
+
+impl JobContext {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Flag set once the job is cancelled, for work that polls it itself
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+
+    /// Fail with a "cancelled" error once the job has been cancelled; call
+    /// between steps of the work
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err("cancelled".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn progress(&self, done: u64, total: Option<u64>, message: &str) {
+        let progress = JobProgress {
+            job: self.id,
+            name: self.name.clone(),
+            done,
+            total,
+            message: message.to_string(),
+        };
+        (self.emit)(PROGRESS_EVENT, to_json(&progress));
+    }
+
+    /// Wrap `reader` so reading it reports progress in bytes out of `total`
+    /// and fails once the job is cancelled
+    pub fn reader<R>(&self, reader: R, total: u64, message: &str) -> ProgressReader<R> {
+        ProgressReader {
+            inner: reader,
+            context: self.clone(),
+            message: message.to_string(),
+            read: 0,
+            total,
+            reported: 0,
+        }
+    }
+}
+
+/// Running jobs, managed as Tauri state
+#[derive(Default)]
+pub struct Jobs {
+    last_id: AtomicU64,
+    running: Arc<Mutex<HashMap<JobId, Arc<AtomicBool>>>>,
+}
+
+impl Jobs {
+    /// Run `work` on the async runtime's blocking pool. Its result is
+    /// emitted as a [`FINISHED_EVENT`]; a job that fails after being
+    /// cancelled is reported as cancelled.
+    pub fn start<T, F>(&self, name: &str, emit: Emit, work: F) -> JobId
+    where
+        T: Serialize + Send + 'static,
+        F: FnOnce(&JobContext) -> Result<T, String> + Send + 'static,
+    {
+        let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        lock(&self.running).insert(id, cancelled.clone());
+        let running = self.running.clone();
+        let context = JobContext {
+            id,
+            name: name.to_string(),
+            cancelled,
+            emit,
+        };
+        tauri::async_runtime::spawn_blocking(move || {
+            let outcome = match work(&context) {
+                Ok(value) => JobOutcome::Completed { value },
+                Err(_) if context.is_cancelled() => JobOutcome::Cancelled,
+                Err(error) => JobOutcome::Failed { error },
+            };
+            lock(&running).remove(&id);
+            let finished = JobFinished {
+                job: id,
+                name: context.name.clone(),
+                outcome,
+            };
+            (context.emit)(FINISHED_EVENT, to_json(&finished));
+        });
+        id
+    }
+
+    /// Ask job `id` to stop. Returns whether it was still running.
+    pub fn cancel(&self, id: JobId) -> bool {
+        match lock(&self.running).get(&id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Identifiers of the jobs still running
+    pub fn running(&self) -> Vec<JobId> {
+        let mut ids: Vec<JobId> = lock(&self.running).keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Reader that reports its progress through a job and stops once the job is
+/// cancelled
+pub struct ProgressReader<R> {
+    inner: R,
+    context: JobContext,
+    message: String,
+    read: u64,
+    total: u64,
+    /// Percentage last reported
+    reported: u64,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.context.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, "cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        let percent = (self.read * 100).checked_div(self.total).unwrap_or(100);
+        if percent > self.reported {
+            self.reported = percent;
+            self.context
+                .progress(self.read, Some(self.total), &self.message);
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for ProgressReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // Progress is the position reached, e.g. when a zip reader jumps to
+        // the central directory and back
+        self.read = self.inner.seek(pos)?;
+        Ok(self.read)
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).expect("job payloads serialize to JSON")
+}
+
+/// Lock `mutex`, ignoring poisoning: the maps stay consistent even if a
+/// holder panicked
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Emitter that forwards events to a channel
+    fn channel() -> (Emit, mpsc::Receiver<(String, serde_json::Value)>) {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let emit: Emit = Arc::new(move |event, payload| {
+            let _ = lock(&tx).send((event.to_string(), payload));
+        });
+        (emit, rx)
+    }
+
+    fn finished(rx: &mpsc::Receiver<(String, serde_json::Value)>) -> serde_json::Value {
+        loop {
+            let (event, payload) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            if event == FINISHED_EVENT {
+                return payload;
+            }
+        }
+    }
+
+    #[test]
+    fn test_job_reports_progress_and_result() {
+        let jobs = Jobs::default();
+        let (emit, rx) = channel();
+        let id = jobs.start("count", emit, |context| {
+            let mut data = Vec::new();
+            context
+                .reader(&[7u8; 1000][..], 1000, "reading")
+                .read_to_end(&mut data)
+                .map_err(|e| e.to_string())?;
+            Ok(data.len())
+        });
+        let (event, progress) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, PROGRESS_EVENT);
+        assert_eq!(progress["job"], id);
+        assert_eq!(progress["total"], 1000);
+
+        let result = finished(&rx);
+        assert_eq!(result["status"], "completed");
+        assert_eq!(result["value"], 1000);
+        assert_eq!(result["name"], "count");
+        assert!(jobs.running().is_empty());
+    }
+
+    #[test]
+    fn test_seek_moves_progress() {
+        let (emit, rx) = channel();
+        let context = JobContext {
+            id: 1,
+            name: "seek".into(),
+            cancelled: Arc::default(),
+            emit,
+        };
+        let mut reader = context.reader(io::Cursor::new([0u8; 100]), 100, "reading");
+        reader.seek(SeekFrom::Start(90)).unwrap();
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        let (_, progress) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(progress["done"], 100);
+    }
+
+    #[test]
+    fn test_cancelled_job_stops_reading() {
+        let jobs = Jobs::default();
+        let (emit, rx) = channel();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (go_tx, go_rx) = mpsc::channel::<()>();
+        let id = jobs.start("read", emit, move |context| {
+            started_tx.send(()).unwrap();
+            go_rx.recv().unwrap();
+            let mut data = Vec::new();
+            context
+                .reader(io::repeat(0), u64::MAX, "reading")
+                .read_to_end(&mut data)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        });
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(jobs.cancel(id));
+        go_tx.send(()).unwrap();
+        assert_eq!(finished(&rx)["status"], "cancelled");
+        assert!(!jobs.cancel(id));
+
+        let (emit, rx) = channel();
+        jobs.start::<(), _>("fail", emit, |_| Err("broken".to_string()));
+        let result = finished(&rx);
+        assert_eq!(result["status"], "failed");
+        assert_eq!(result["error"], "broken");
+    }
+}
@@ -1,13 +1,48 @@
 // ReqSmith - Modern ReqIF requirements management tool
 
 mod commands;
-mod reqif;
+pub mod coverage;
+pub mod jobs;
+pub mod links;
+pub mod reqif;
+pub mod search;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![commands::greet])
+        .plugin(tauri_plugin_dialog::init())
+        .manage(commands::AppState::default())
+        .manage(jobs::Jobs::default())
+        .invoke_handler(tauri::generate_handler![
+            commands::greet,
+            commands::document_status,
+            commands::get_requirements,
+            commands::start_open_reqif,
+            commands::start_validation,
+            commands::start_indexing,
+            commands::start_save_reqif,
+            commands::start_save_reqif_as,
+            commands::cancel_job,
+            commands::apply_edit,
+            commands::begin_edit_group,
+            commands::end_edit_group,
+            commands::undo_edit,
+            commands::redo_edit,
+            commands::edit_history,
+            commands::search_requirements,
+            commands::filter_requirements,
+            commands::list_views,
+            commands::save_view,
+            commands::delete_view,
+            commands::apply_view,
+            commands::set_view_storage,
+            commands::trace_requirements,
+            commands::trace_path,
+            commands::trace_cycles,
+            commands::trace_graph,
+            commands::coverage_report,
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
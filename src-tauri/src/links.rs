@@ -0,0 +1,499 @@
+// Traceability graph - SpecObjects linked by typed SpecRelations, for
+// impact analysis, trace paths, cycle checks and the graph view
+
+use crate::reqif::model::*;
+use crate::reqif::store::DocumentStore;
+use crate::reqif::undo::Changed;
+use petgraph::algo::tarjan_scc;
+use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableDiGraph};
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Which way to follow relations. Relations point upstream, from the
+/// object that satisfies, refines or verifies to the one it traces to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceDirection {
+    /// From source to target
+    Upstream,
+    /// From target to source
+    Downstream,
+    Both,
+}
+
+/// Object found by a traversal
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reached {
+    pub object: String,
+    /// Number of relations followed to get there
+    pub depth: usize,
+    /// Relation it was first reached through
+    pub relation: String,
+}
+
+/// Objects from one end of a path to the other, and the relations between
+/// consecutive objects
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TracePath {
+    pub objects: Vec<String>,
+    pub relations: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    /// LONG-NAME, first string value or identifier of the object
+    pub label: String,
+    pub spec_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphEdge {
+    pub relation: String,
+    pub source: String,
+    pub target: String,
+    pub relation_type: String,
+}
+
+/// Part of the graph for the graph view
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphExport {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Edge weight: a SpecRelation and its type
+struct Link {
+    relation: String,
+    relation_type: String,
+    /// LONG-NAME of the relation type
+    type_name: Option<String>,
+}
+
+/// SpecObjects as nodes and SpecRelations as directed, typed edges.
+/// Relations with an end that does not resolve are left out.
+pub struct TraceGraph {
+    graph: StableDiGraph<String, Link>,
+    nodes: HashMap<String, NodeIndex>,
+    edges: HashMap<String, EdgeIndex>,
+}
+
+impl TraceGraph {
+    pub fn build(store: &DocumentStore) -> Self {
+        let content = store.content();
+        let mut graph = Self {
+            graph: StableDiGraph::with_capacity(
+                content.spec_objects.len(),
+                content.spec_relations.len(),
+            ),
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+        };
+        for object in &content.spec_objects {
+            graph.add_object(&object.ident.identifier);
+        }
+        for relation in &content.spec_relations {
+            graph.add_relation(store, relation);
+        }
+        graph
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    /// Bring the graph up to date with what `changed` touched
+    pub fn apply_changes(&mut self, store: &DocumentStore, changed: &Changed) {
+        for object in &changed.objects {
+            match (self.nodes.get(object), store.spec_object(object)) {
+                (Some(&node), None) => {
+                    let incident: Vec<EdgeIndex> = self
+                        .graph
+                        .edges_directed(node, Direction::Outgoing)
+                        .chain(self.graph.edges_directed(node, Direction::Incoming))
+                        .map(|e| e.id())
+                        .collect();
+                    for edge in incident {
+                        if let Some(link) = self.graph.remove_edge(edge) {
+                            self.edges.remove(&link.relation);
+                        }
+                    }
+                    self.graph.remove_node(node);
+                    self.nodes.remove(object);
+                }
+                (None, Some(_)) => {
+                    self.add_object(object);
+                    // Relations left out while this end was missing
+                    let relations = store
+                        .outgoing_relations(object)
+                        .chain(store.incoming_relations(object));
+                    for relation in relations {
+                        if !self.edges.contains_key(&relation.ident.identifier) {
+                            self.add_relation(store, relation);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        for relation in &changed.relations {
+            if let Some(edge) = self.edges.remove(relation) {
+                self.graph.remove_edge(edge);
+            }
+            if let Some(relation) = store.spec_relation(relation) {
+                self.add_relation(store, relation);
+            }
+        }
+        if !changed.spec_types.is_empty() {
+            for link in self.graph.edge_weights_mut() {
+                if changed.spec_types.contains(&link.relation_type) {
+                    link.type_name = type_name(store, &link.relation_type);
+                }
+            }
+        }
+    }
+
+    /// Objects reachable from `object` within `depth` relations (any
+    /// number when `None`) of the given types (all when empty), nearest
+    /// first
+    pub fn traverse(
+        &self,
+        object: &str,
+        direction: TraceDirection,
+        depth: Option<usize>,
+        relation_types: &[String],
+    ) -> Vec<Reached> {
+        let Some(&start) = self.nodes.get(object) else {
+            return Vec::new();
+        };
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut reached = Vec::new();
+        while let Some((node, distance)) = queue.pop_front() {
+            if depth.is_some_and(|d| distance >= d) {
+                continue;
+            }
+            for (edge, next) in self.neighbours(node, direction, relation_types) {
+                if seen.insert(next) {
+                    reached.push(Reached {
+                        object: self.graph[next].clone(),
+                        depth: distance + 1,
+                        relation: self.graph[edge].relation.clone(),
+                    });
+                    queue.push_back((next, distance + 1));
+                }
+            }
+        }
+        reached
+    }
+
+    /// Shortest chain of relations between two objects, followed either way
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<TracePath> {
+        let start = *self.nodes.get(from)?;
+        let goal = *self.nodes.get(to)?;
+        // Edge and node each node was reached through
+        let mut previous: HashMap<NodeIndex, (EdgeIndex, NodeIndex)> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        let mut seen = HashSet::from([start]);
+        while let Some(node) = queue.pop_front() {
+            if node == goal {
+                break;
+            }
+            for (edge, next) in self.neighbours(node, TraceDirection::Both, &[]) {
+                if seen.insert(next) {
+                    previous.insert(next, (edge, node));
+                    queue.push_back(next);
+                }
+            }
+        }
+        if !seen.contains(&goal) {
+            return None;
+        }
+        let mut objects = vec![self.graph[goal].clone()];
+        let mut relations = Vec::new();
+        let mut node = goal;
+        while let Some(&(edge, before)) = previous.get(&node) {
+            relations.push(self.graph[edge].relation.clone());
+            objects.push(self.graph[before].clone());
+            node = before;
+        }
+        objects.reverse();
+        relations.reverse();
+        Some(TracePath { objects, relations })
+    }
+
+    /// Strongly connected components, each in graph order, in reverse
+    /// topological order of the condensed graph
+    pub fn strongly_connected_components(&self) -> Vec<Vec<String>> {
+        tarjan_scc(&self.graph)
+            .into_iter()
+            .map(|mut component| {
+                component.sort_unstable();
+                component
+                    .into_iter()
+                    .map(|n| self.graph[n].clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Groups of objects that trace to each other in a circle, including
+    /// objects related to themselves
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        self.strongly_connected_components()
+            .into_iter()
+            .filter(|component| {
+                let node = self.nodes[&component[0]];
+                component.len() > 1 || self.graph.find_edge(node, node).is_some()
+            })
+            .collect()
+    }
+
+    /// `objects` with what they reach as for [`Self::traverse`], and the
+    /// relations of the given types among them
+    pub fn subgraph(
+        &self,
+        store: &DocumentStore,
+        objects: &[String],
+        direction: TraceDirection,
+        depth: Option<usize>,
+        relation_types: &[String],
+    ) -> GraphExport {
+        let mut included: HashSet<NodeIndex> = HashSet::new();
+        for object in objects {
+            if let Some(&node) = self.nodes.get(object) {
+                included.insert(node);
+                for reached in self.traverse(object, direction, depth, relation_types) {
+                    included.insert(self.nodes[&reached.object]);
+                }
+            }
+        }
+        let mut nodes: Vec<NodeIndex> = included.iter().copied().collect();
+        nodes.sort_unstable();
+        let nodes = nodes
+            .into_iter()
+            .filter_map(|n| store.spec_object(&self.graph[n]))
+            .map(|object| GraphNode {
+                id: object.ident.identifier.clone(),
+                label: label(object),
+                spec_type: object.spec_type.clone(),
+            })
+            .collect();
+        let mut edges: Vec<_> = (&self.graph)
+            .edge_references()
+            .filter(|e| included.contains(&e.source()) && included.contains(&e.target()))
+            .filter(|e| has_type(e.weight(), relation_types))
+            .collect();
+        edges.sort_unstable_by_key(|e| e.id());
+        let edges = edges
+            .into_iter()
+            .map(|e| GraphEdge {
+                relation: e.weight().relation.clone(),
+                source: self.graph[e.source()].clone(),
+                target: self.graph[e.target()].clone(),
+                relation_type: e.weight().relation_type.clone(),
+            })
+            .collect();
+        GraphExport { nodes, edges }
+    }
+
+    fn add_object(&mut self, object: &str) {
+        let node = self.graph.add_node(object.to_string());
+        self.nodes.insert(object.to_string(), node);
+    }
+
+    fn add_relation(&mut self, store: &DocumentStore, relation: &SpecRelation) {
+        let (Some(&source), Some(&target)) = (
+            self.nodes.get(&relation.source),
+            self.nodes.get(&relation.target),
+        ) else {
+            return;
+        };
+        let edge = self.graph.add_edge(
+            source,
+            target,
+            Link {
+                relation: relation.ident.identifier.clone(),
+                relation_type: relation.spec_type.clone(),
+                type_name: type_name(store, &relation.spec_type),
+            },
+        );
+        self.edges.insert(relation.ident.identifier.clone(), edge);
+    }
+
+    /// Edges of the given types at `node` and the nodes at their other end,
+    /// outgoing before incoming, each in graph order
+    fn neighbours(
+        &self,
+        node: NodeIndex,
+        direction: TraceDirection,
+        relation_types: &[String],
+    ) -> Vec<(EdgeIndex, NodeIndex)> {
+        let mut found = Vec::new();
+        for (followed, wanted) in [
+            (Direction::Outgoing, direction != TraceDirection::Downstream),
+            (Direction::Incoming, direction != TraceDirection::Upstream),
+        ] {
+            if !wanted {
+                continue;
+            }
+            let start = found.len();
+            found.extend(
+                self.graph
+                    .edges_directed(node, followed)
+                    .filter(|e| has_type(e.weight(), relation_types))
+                    .map(|e| {
+                        let next = match followed {
+                            Direction::Outgoing => e.target(),
+                            Direction::Incoming => e.source(),
+                        };
+                        (e.id(), next)
+                    }),
+            );
+            found[start..].sort_unstable();
+        }
+        found
+    }
+}
+
+/// Whether `link` has one of `relation_types`, by identifier or LONG-NAME;
+/// any type when empty
+fn has_type(link: &Link, relation_types: &[String]) -> bool {
+    relation_types.is_empty()
+        || relation_types
+            .iter()
+            .any(|t| *t == link.relation_type || link.type_name.as_ref() == Some(t))
+}
+
+fn type_name(store: &DocumentStore, spec_type: &str) -> Option<String> {
+    store
+        .spec_type(spec_type)
+        .and_then(|t| t.ident.long_name.clone())
+}
+
+/// LONG-NAME, first string value or identifier of `object`
+pub(crate) fn label(object: &SpecObject) -> String {
+    object
+        .ident
+        .long_name
+        .clone()
+        .or_else(|| {
+            object.values.iter().find_map(|v| match v {
+                AttributeValue::String { value, .. } => Some(value.clone()),
+                _ => None,
+            })
+        })
+        .unwrap_or_else(|| object.ident.identifier.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+    use crate::reqif::undo::{Edit, Journal};
+
+    const TRACED: &str = include_str!("../../tests/fixtures/traced.reqif");
+
+    fn graph() -> (DocumentStore, TraceGraph) {
+        let store = DocumentStore::new(parser::parse_str(TRACED).unwrap());
+        let graph = TraceGraph::build(&store);
+        (store, graph)
+    }
+
+    fn objects(reached: &[Reached]) -> Vec<(&str, usize)> {
+        reached
+            .iter()
+            .map(|r| (r.object.as_str(), r.depth))
+            .collect()
+    }
+
+    #[test]
+    fn test_traversal_and_shortest_path() {
+        let (_, graph) = graph();
+        assert_eq!(graph.node_count(), 10);
+        assert_eq!(graph.edge_count(), 7);
+
+        let down = graph.traverse("sys-001", TraceDirection::Downstream, None, &[]);
+        assert_eq!(
+            objects(&down),
+            [("sw-001", 1), ("sw-003", 1), ("tc-001", 1)]
+        );
+        let satisfied = ["satisfies".to_string()];
+        let down = graph.traverse("sys-002", TraceDirection::Downstream, None, &satisfied);
+        assert_eq!(objects(&down), [("sw-002", 1)]);
+        let up = graph.traverse("tc-002", TraceDirection::Upstream, Some(1), &[]);
+        assert_eq!(objects(&up), [("sw-002", 1)]);
+        let up = graph.traverse("tc-002", TraceDirection::Upstream, None, &[]);
+        assert_eq!(objects(&up), [("sw-002", 1), ("sys-002", 2)]);
+
+        let path = graph.shortest_path("tc-002", "sys-002").unwrap();
+        assert_eq!(path.objects, ["tc-002", "sw-002", "sys-002"]);
+        assert_eq!(path.relations, ["rel-005", "rel-002"]);
+        let path = graph.shortest_path("tc-001", "sw-003").unwrap();
+        assert_eq!(path.objects, ["tc-001", "sys-001", "sw-003"]);
+        assert!(graph.shortest_path("sys-001", "sys-004").is_none());
+    }
+
+    #[test]
+    fn test_components_cycles_and_subgraph() {
+        let (store, graph) = graph();
+        let components = graph.strongly_connected_components();
+        assert_eq!(components.len(), 9);
+        assert_eq!(graph.cycles(), [vec!["sw-001", "sw-003"]]);
+
+        let export = graph.subgraph(
+            &store,
+            &["sys-002".to_string()],
+            TraceDirection::Downstream,
+            None,
+            &[],
+        );
+        let ids: Vec<&str> = export.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["sys-002", "sw-002", "tc-002"]);
+        assert_eq!(export.nodes[0].label, "Authenticate users");
+        let relations: Vec<&str> = export.edges.iter().map(|e| e.relation.as_str()).collect();
+        assert_eq!(relations, ["rel-002", "rel-005"]);
+    }
+
+    #[test]
+    fn test_graph_follows_edits() {
+        let (mut store, mut graph) = graph();
+        let mut journal = Journal::default();
+        journal
+            .apply(
+                &mut store,
+                Edit::DeleteObject {
+                    object: "sw-002".into(),
+                },
+            )
+            .unwrap();
+        let relation = SpecRelation {
+            ident: Identifiable::new("rel-008"),
+            spec_type: "srt-satisfies".into(),
+            source: "sw-004".into(),
+            target: "sys-003".into(),
+            values: Vec::new(),
+            extras: Extras::default(),
+        };
+        journal
+            .apply(&mut store, Edit::AddRelation { relation })
+            .unwrap();
+        graph.apply_changes(&store, &journal.take_changed());
+        assert_eq!((graph.node_count(), graph.edge_count()), (9, 6));
+        assert!(graph.shortest_path("tc-002", "sys-002").is_none());
+        let up = graph.traverse("sw-004", TraceDirection::Upstream, None, &[]);
+        assert_eq!(objects(&up), [("sys-003", 1)]);
+
+        while journal.undo(&mut store).unwrap().is_some() {}
+        graph.apply_changes(&store, &journal.take_changed());
+        assert_eq!((graph.node_count(), graph.edge_count()), (10, 7));
+        let path = graph.shortest_path("tc-002", "sys-002").unwrap();
+        assert_eq!(path.relations, ["rel-005", "rel-002"]);
+    }
+}
@@ -0,0 +1,376 @@
+// ReqIF archive support - Reads and writes .reqifz files (zip with ReqIF
+// documents plus embedded images and OLE objects)
+
+use super::error::ReqIfError;
+use super::model::{AttributeValue, ReqIF};
+use super::parser;
+use super::serializer::{self, SerializeError};
+use super::xml::local_name;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Errors raised while reading or writing a `.reqifz` archive
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    /// The error's location names the archive entry
+    #[error("failed to parse {0}")]
+    Parse(ReqIfError),
+    #[error("failed to write '{path}': {source}")]
+    Serialize {
+        path: String,
+        source: SerializeError,
+    },
+    #[error("archive contains no .reqif document")]
+    NoDocument,
+}
+
+/// ReqIF document stored inside an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveDocument {
+    /// Path of the `.reqif` entry inside the archive
+    pub path: String,
+    pub reqif: ReqIF,
+}
+
+/// Contents of a `.reqifz` archive
+#[derive(Debug, Clone, Default)]
+pub struct ReqIfArchive {
+    /// ReqIF documents in archive order
+    pub documents: Vec<ArchiveDocument>,
+    /// All other entries (images, OLE objects, ...) keyed by archive path
+    pub resources: BTreeMap<String, Vec<u8>>,
+}
+
+/// An `<xhtml:object data="...">` reference found in an XHTML attribute value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceRef {
+    /// Archive path of the document containing the reference
+    pub document: String,
+    /// SpecObject, SpecRelation or Specification holding the XHTML value, or
+    /// the attribute definition whose default value it is
+    pub element: String,
+    /// Attribute definition of the XHTML value
+    pub definition: String,
+    /// `data` attribute as written in the XHTML
+    pub data: String,
+    /// `data` resolved to an archive path; `None` for absolute URIs, which
+    /// point outside the archive
+    pub path: Option<String>,
+    /// MIME type from the `type` attribute, if any
+    pub mime_type: Option<String>,
+    /// Whether the archive contains the referenced file
+    pub present: bool,
+}
+
+/// Whether an archive entry is a ReqIF document
+fn is_reqif_entry(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".reqif")
+}
+
+/// Read a `.reqifz` archive from disk
+pub fn read_archive(path: impl AsRef<Path>) -> Result<ReqIfArchive, ArchiveError> {
+    read_archive_from(BufReader::new(File::open(path)?))
+}
+
+/// Read a `.reqifz` archive from any seekable reader
+pub fn read_archive_from<R: Read + Seek>(reader: R) -> Result<ReqIfArchive, ArchiveError> {
+    let mut zip = ZipArchive::new(reader)?;
+    let mut archive = ReqIfArchive::default();
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        if is_reqif_entry(&name) {
+            let reqif = parser::parse_reader(BufReader::new(&mut entry))
+                .map_err(|e| ArchiveError::Parse(e.with_file(name.clone())))?;
+            archive
+                .documents
+                .push(ArchiveDocument { path: name, reqif });
+        } else {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            archive.resources.insert(name, data);
+        }
+    }
+    if archive.documents.is_empty() {
+        return Err(ArchiveError::NoDocument);
+    }
+    Ok(archive)
+}
+
+/// Write a `.reqifz` archive to disk
+pub fn write_archive(archive: &ReqIfArchive, path: impl AsRef<Path>) -> Result<(), ArchiveError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_archive_to(archive, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a `.reqifz` archive to any seekable writer
+pub fn write_archive_to<W: Write + Seek>(
+    archive: &ReqIfArchive,
+    writer: W,
+) -> Result<(), ArchiveError> {
+    if archive.documents.is_empty() {
+        return Err(ArchiveError::NoDocument);
+    }
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(writer);
+    for document in &archive.documents {
+        zip.start_file(document.path.as_str(), options)?;
+        // Written straight into the entry, so a failing writer stops it early
+        let mut out = BufWriter::new(&mut zip);
+        serializer::write(&document.reqif, &mut out).map_err(|source| ArchiveError::Serialize {
+            path: document.path.clone(),
+            source,
+        })?;
+        out.flush()?;
+    }
+    for (path, data) in &archive.resources {
+        zip.start_file(path.as_str(), options)?;
+        zip.write_all(data)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+impl ReqIfArchive {
+    /// Wrap a single document, e.g. to save a `.reqif` as `.reqifz`
+    pub fn from_document(path: impl Into<String>, reqif: ReqIF) -> Self {
+        Self {
+            documents: vec![ArchiveDocument {
+                path: path.into(),
+                reqif,
+            }],
+            resources: BTreeMap::new(),
+        }
+    }
+
+    /// Bytes of the resource stored at `path`
+    pub fn resource(&self, path: &str) -> Option<&[u8]> {
+        self.resources.get(path).map(Vec::as_slice)
+    }
+
+    /// All `<xhtml:object>` references in the XHTML values of every document
+    pub fn resource_refs(&self) -> Vec<ResourceRef> {
+        let mut refs = Vec::new();
+        for document in &self.documents {
+            let content = &document.reqif.core_content;
+            let defaults = content
+                .spec_types
+                .iter()
+                .flat_map(|t| &t.spec_attributes)
+                .filter_map(|d| Some((&d.ident, std::slice::from_ref(d.default_value.as_ref()?))));
+            let owners = content
+                .spec_objects
+                .iter()
+                .map(|o| (&o.ident, o.values.as_slice()))
+                .chain(
+                    content
+                        .spec_relations
+                        .iter()
+                        .map(|r| (&r.ident, r.values.as_slice())),
+                )
+                .chain(
+                    content
+                        .specifications
+                        .iter()
+                        .map(|s| (&s.ident, s.values.as_slice())),
+                )
+                .chain(defaults);
+            for (ident, values) in owners {
+                for value in values {
+                    let AttributeValue::XHTML {
+                        definition, value, ..
+                    } = value
+                    else {
+                        continue;
+                    };
+                    for (data, mime_type) in xhtml_objects(value) {
+                        let path = resolve_path(&document.path, &data);
+                        refs.push(ResourceRef {
+                            document: document.path.clone(),
+                            element: ident.identifier.clone(),
+                            definition: definition.clone(),
+                            present: path
+                                .as_ref()
+                                .is_some_and(|p| self.resources.contains_key(p)),
+                            data,
+                            path,
+                            mime_type,
+                        });
+                    }
+                }
+            }
+        }
+        refs
+    }
+}
+
+/// `data` and `type` attributes of every `<object>` element in an XHTML
+/// fragment
+pub fn xhtml_objects(xhtml: &str) -> Vec<(String, Option<String>)> {
+    let mut objects = Vec::new();
+    let mut reader = Reader::from_str(xhtml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                if local_name(&String::from_utf8_lossy(e.name().as_ref())) == "object" =>
+            {
+                let attr = |name: &[u8]| {
+                    e.try_get_attribute(name)
+                        .ok()
+                        .flatten()
+                        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+                };
+                if let Some(data) = attr(b"data") {
+                    objects.push((data, attr(b"type")));
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    objects
+}
+
+/// Resolve an object `data` path relative to the document that contains it.
+/// Absolute URIs, with a scheme such as `http:` or `file:` or a leading `/`,
+/// are left unresolved.
+fn resolve_path(document: &str, data: &str) -> Option<String> {
+    let scheme = data.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if scheme || data.starts_with(['/', '\\']) {
+        return None;
+    }
+    let mut parts: Vec<&str> = match document.rfind('/') {
+        Some(index) => document[..index].split('/').collect(),
+        None => Vec::new(),
+    };
+    for part in data.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::model::{Identifiable, SpecRelation};
+    use std::io::Cursor;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    fn archive_with_image() -> ReqIfArchive {
+        let mut reqif = parser::parse_str(SMALL).unwrap();
+        if let AttributeValue::XHTML { value, .. } =
+            &mut reqif.core_content.spec_objects[0].values[1]
+        {
+            *value = r#"<xhtml:div>See <xhtml:object data="files/boot.png" type="image/png"/></xhtml:div>"#
+                .to_string();
+        }
+        let mut archive = ReqIfArchive::from_document("docs/small.reqif", reqif);
+        archive
+            .resources
+            .insert("docs/files/boot.png".into(), vec![0x89, b'P', b'N', b'G']);
+        archive
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let archive = archive_with_image();
+        let mut bytes = Cursor::new(Vec::new());
+        write_archive_to(&archive, &mut bytes).unwrap();
+
+        bytes.set_position(0);
+        let loaded = read_archive_from(bytes).unwrap();
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.documents[0].path, "docs/small.reqif");
+        assert_eq!(loaded.documents[0].reqif.core_content.spec_objects.len(), 3);
+        assert_eq!(
+            loaded.resource("docs/files/boot.png"),
+            Some(&[0x89, b'P', b'N', b'G'][..])
+        );
+    }
+
+    #[test]
+    fn test_resource_refs_resolve_against_document() {
+        let mut archive = archive_with_image();
+        let content = &mut archive.documents[0].reqif.core_content;
+        let xhtml = |data: &str| AttributeValue::XHTML {
+            definition: "ad-description".into(),
+            value: format!(r#"<xhtml:object data="{}"/>"#, data),
+            extras: Default::default(),
+        };
+        content.specifications[0].values.push(xhtml("../logo.png"));
+        content.spec_relations.push(SpecRelation {
+            ident: Identifiable::new("rel-001"),
+            spec_type: "srt".into(),
+            source: "req-001".into(),
+            target: "req-002".into(),
+            values: vec![xhtml("http://example.com/a.png"), xhtml("/srv/b.png")],
+            extras: Default::default(),
+        });
+
+        let refs = archive.resource_refs();
+        assert_eq!(refs.len(), 4);
+        assert_eq!(refs[0].element, "req-001");
+        assert_eq!(refs[0].data, "files/boot.png");
+        assert_eq!(refs[0].path.as_deref(), Some("docs/files/boot.png"));
+        assert_eq!(refs[0].mime_type.as_deref(), Some("image/png"));
+        assert!(refs[0].present);
+        let paths: Vec<_> = refs[1..]
+            .iter()
+            .map(|r| (r.element.as_str(), r.path.as_deref()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("rel-001", None),
+                ("rel-001", None),
+                ("spec-001", Some("logo.png"))
+            ]
+        );
+        assert!(!refs[1].present);
+    }
+
+    #[test]
+    fn test_archive_without_document_is_rejected() {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut zip = ZipWriter::new(&mut bytes);
+        zip.start_file("readme.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"hello").unwrap();
+        zip.finish().unwrap();
+
+        bytes.set_position(0);
+        assert!(matches!(
+            read_archive_from(bytes),
+            Err(ArchiveError::NoDocument)
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+// ReqIF constraints - Checks attribute values against the limits of their
+// datatype definition
+
+use super::model::{datatype_kind, value_kind, AttributeValue, DatatypeDefinition};
+use serde::Serialize;
+use thiserror::Error;
+
+/// A value breaking a limit of its datatype
+#[derive(Debug, Clone, PartialEq, Error, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ConstraintViolation {
+    #[error("expected a {expected} value, got {found}")]
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("{value} is below the minimum of {min}")]
+    BelowMin { value: String, min: String },
+    #[error("{value} is above the maximum of {max}")]
+    AboveMax { value: String, max: String },
+    #[error("{length} characters exceed the maximum length of {max_length}")]
+    TooLong { length: usize, max_length: u32 },
+    #[error("{value} has more than {accuracy} decimal places")]
+    TooPrecise { value: f64, accuracy: u32 },
+    #[error("'{value}' is not a value of the enumeration")]
+    UnknownEnumValue { value: String },
+}
+
+/// Every limit of `datatype` that `value` breaks
+pub fn check_value(
+    value: &AttributeValue,
+    datatype: &DatatypeDefinition,
+) -> Vec<ConstraintViolation> {
+    let mut violations = Vec::new();
+    match (value, datatype) {
+        (AttributeValue::Integer { value, .. }, DatatypeDefinition::Integer { min, max, .. }) => {
+            check_range(*value, *min, *max, &mut violations);
+        }
+        (
+            AttributeValue::Real { value, .. },
+            DatatypeDefinition::Real {
+                min, max, accuracy, ..
+            },
+        ) => {
+            check_range(*value, *min, *max, &mut violations);
+            if let Some(accuracy) = *accuracy {
+                if !has_accuracy(*value, accuracy) {
+                    violations.push(ConstraintViolation::TooPrecise {
+                        value: *value,
+                        accuracy,
+                    });
+                }
+            }
+        }
+        (AttributeValue::String { value, .. }, DatatypeDefinition::String { max_length, .. }) => {
+            let length = value.chars().count();
+            if let Some(max_length) = *max_length {
+                if length > max_length as usize {
+                    violations.push(ConstraintViolation::TooLong { length, max_length });
+                }
+            }
+        }
+        (
+            AttributeValue::Enumeration { values, .. },
+            DatatypeDefinition::Enumeration {
+                values: specified, ..
+            },
+        ) => {
+            for value in values {
+                if !specified.iter().any(|v| &v.ident.identifier == value) {
+                    violations.push(ConstraintViolation::UnknownEnumValue {
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        _ if value_kind(value) != datatype_kind(datatype) => {
+            violations.push(ConstraintViolation::WrongType {
+                expected: datatype_kind(datatype),
+                found: value_kind(value),
+            });
+        }
+        _ => {}
+    }
+    violations
+}
+
+fn check_range<T: PartialOrd + ToString>(
+    value: T,
+    min: Option<T>,
+    max: Option<T>,
+    violations: &mut Vec<ConstraintViolation>,
+) {
+    if let Some(min) = min.filter(|min| value < *min) {
+        violations.push(ConstraintViolation::BelowMin {
+            value: value.to_string(),
+            min: min.to_string(),
+        });
+    }
+    if let Some(max) = max.filter(|max| value > *max) {
+        violations.push(ConstraintViolation::AboveMax {
+            value: value.to_string(),
+            max: max.to_string(),
+        });
+    }
+}
+
+/// Whether `value` has at most `accuracy` decimal places
+fn has_accuracy(value: f64, accuracy: u32) -> bool {
+    let scaled = value * 10f64.powi(accuracy.min(15) as i32);
+    (scaled - scaled.round()).abs() <= scaled.abs().max(1.0) * f64::EPSILON * 4.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::model::{Extras, Identifiable};
+
+    fn integer(value: i64) -> AttributeValue {
+        AttributeValue::Integer {
+            definition: "ad".into(),
+            value,
+            extras: Extras::default(),
+        }
+    }
+
+    #[test]
+    fn test_ranges_length_and_accuracy() {
+        let percent = DatatypeDefinition::Integer {
+            ident: Identifiable::new("dt-int"),
+            min: Some(0),
+            max: Some(100),
+            extras: Extras::default(),
+        };
+        assert!(check_value(&integer(50), &percent).is_empty());
+        assert!(matches!(
+            check_value(&integer(101), &percent)[..],
+            [ConstraintViolation::AboveMax { .. }]
+        ));
+        assert!(matches!(
+            check_value(&integer(-1), &percent)[..],
+            [ConstraintViolation::BelowMin { .. }]
+        ));
+
+        let short = DatatypeDefinition::String {
+            ident: Identifiable::new("dt-str"),
+            max_length: Some(3),
+            extras: Extras::default(),
+        };
+        let text = |value: &str| AttributeValue::String {
+            definition: "ad".into(),
+            value: value.into(),
+            extras: Extras::default(),
+        };
+        assert!(check_value(&text("äöü"), &short).is_empty());
+        assert_eq!(
+            check_value(&text("abcd"), &short),
+            [ConstraintViolation::TooLong {
+                length: 4,
+                max_length: 3
+            }]
+        );
+
+        let money = DatatypeDefinition::Real {
+            ident: Identifiable::new("dt-real"),
+            min: None,
+            max: None,
+            accuracy: Some(2),
+            extras: Extras::default(),
+        };
+        let real = |value: f64| AttributeValue::Real {
+            definition: "ad".into(),
+            value,
+            extras: Extras::default(),
+        };
+        assert!(check_value(&real(19.99), &money).is_empty());
+        assert!(matches!(
+            check_value(&real(19.999), &money)[..],
+            [ConstraintViolation::TooPrecise { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let xhtml = DatatypeDefinition::XHTML {
+            ident: Identifiable::new("dt-xhtml"),
+            extras: Extras::default(),
+        };
+        assert_eq!(
+            check_value(&integer(1), &xhtml),
+            [ConstraintViolation::WrongType {
+                expected: "XHTML",
+                found: "INTEGER"
+            }]
+        );
+    }
+}
@@ -0,0 +1,577 @@
+// ReqIF documents - A .reqif or .reqifz file opened for editing, with what
+// is needed to write it back
+
+use super::archive::{self, ArchiveDocument, ArchiveError, ReqIfArchive};
+use super::edit::EditError;
+use super::error::{ReqIfError, SourceLocation};
+use super::model::ReqIF;
+use super::parser;
+use super::serializer::{self, SerializeError};
+use super::store::DocumentStore;
+use super::undo::{Changed, Edit, History, Journal};
+use super::views::{self, SavedView, ViewError, ViewStorage};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use thiserror::Error;
+
+/// Errors raised while opening or saving a document
+#[derive(Debug, Error)]
+pub enum DocumentError {
+    #[error(transparent)]
+    Parse(#[from] ReqIfError),
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+    #[error(transparent)]
+    Serialize(#[from] SerializeError),
+    #[error(transparent)]
+    Views(#[from] ViewError),
+    #[error("'{0}' is neither a .reqif nor a .reqifz file")]
+    UnsupportedFile(String),
+    #[error(
+        "the archive holds images, objects or further documents that a .reqif file cannot keep"
+    )]
+    ArchiveContent,
+    #[error("cannot write {path}: {source}")]
+    Io { path: String, source: io::Error },
+}
+
+/// On-disk format of a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Reqif,
+    Reqifz,
+}
+
+impl DocumentFormat {
+    /// Format for `path`, from its extension
+    pub fn of(path: &Path) -> Result<Self, DocumentError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("reqif") => Ok(DocumentFormat::Reqif),
+            Some("reqifz") => Ok(DocumentFormat::Reqifz),
+            _ => Err(DocumentError::UnsupportedFile(path.display().to_string())),
+        }
+    }
+}
+
+/// The ReqIF document of a `.reqifz` being edited, and the rest of the
+/// archive it is written back into
+#[derive(Debug)]
+struct ArchiveSlot {
+    /// Entry path of the edited document
+    entry: String,
+    /// Position of the edited document among the archive's documents
+    position: usize,
+    /// The archive without the edited document
+    rest: ReqIfArchive,
+}
+
+/// Source of [`Document`] ids
+static DOCUMENT_IDS: AtomicU64 = AtomicU64::new(0);
+
+/// A document opened from disk. Archives are edited through their first
+/// ReqIF document; other documents and resources are written back unchanged.
+#[derive(Debug)]
+pub struct Document {
+    /// Tells documents apart, so a [`SaveCopy`] is only adopted by its own
+    id: u64,
+    path: PathBuf,
+    format: DocumentFormat,
+    store: DocumentStore,
+    archive: Option<ArchiveSlot>,
+    journal: Journal,
+    views: Vec<SavedView>,
+    view_storage: ViewStorage,
+    /// Number of changes made since the document was opened
+    revision: u64,
+    /// `revision` when the document was last saved
+    saved_revision: u64,
+}
+
+/// Everything saving a document writes, copied out of it so that it can be
+/// written while the document stays open for editing
+#[derive(Debug)]
+pub struct SaveCopy {
+    document: u64,
+    revision: u64,
+    path: PathBuf,
+    format: DocumentFormat,
+    content: SaveContent,
+    /// Views for the sidecar file; `None` to remove it
+    sidecar: Option<Vec<SavedView>>,
+}
+
+#[derive(Debug)]
+enum SaveContent {
+    Reqif(Box<ReqIF>),
+    Reqifz(ReqIfArchive),
+}
+
+impl Document {
+    /// Open the `.reqif` or `.reqifz` file at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DocumentError> {
+        let path = path.as_ref();
+        DocumentFormat::of(path)?;
+        let file = File::open(path).map_err(|e| {
+            ReqIfError::new(e.into(), SourceLocation::default())
+                .with_file(path.display().to_string())
+        })?;
+        Self::from_reader(path, file)
+    }
+
+    /// Read the document stored at `path` from `reader`, e.g. to watch the
+    /// progress of a large file. `path` gives the format and is where the
+    /// document is saved.
+    pub fn from_reader<R: Read + Seek>(
+        path: impl AsRef<Path>,
+        reader: R,
+    ) -> Result<Self, DocumentError> {
+        let path = path.as_ref();
+        let format = DocumentFormat::of(path)?;
+        let (mut reqif, archive) = match format {
+            DocumentFormat::Reqif => {
+                let reqif = parser::parse_reader(BufReader::new(reader))
+                    .map_err(|e| e.with_file(path.display().to_string()))?;
+                (reqif, None)
+            }
+            DocumentFormat::Reqifz => {
+                let mut rest = archive::read_archive_from(reader)?;
+                let document = rest.documents.remove(0);
+                let slot = ArchiveSlot {
+                    entry: document.path,
+                    position: 0,
+                    rest,
+                };
+                (document.reqif, Some(slot))
+            }
+        };
+        // Views live outside the store and are written back on save
+        let (views, view_storage) = match views::take_views(&mut reqif.tool_extensions)? {
+            Some(views) => (views, ViewStorage::Document),
+            None => match views::read_sidecar(&views::sidecar_path(path))? {
+                Some(views) => (views, ViewStorage::Sidecar),
+                None => (Vec::new(), ViewStorage::default()),
+            },
+        };
+        Ok(Self {
+            id: DOCUMENT_IDS.fetch_add(1, Ordering::Relaxed),
+            path: path.to_path_buf(),
+            format,
+            store: DocumentStore::new(reqif),
+            archive,
+            journal: Journal::default(),
+            views,
+            view_storage,
+            revision: 0,
+            saved_revision: 0,
+        })
+    }
+
+    /// Identifier telling this document apart from others opened in the
+    /// same run
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Counter raised by every change, so results computed from the
+    /// document can tell when they are stale
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> DocumentFormat {
+        self.format
+    }
+
+    pub fn store(&self) -> &DocumentStore {
+        &self.store
+    }
+
+    /// Mutable access to the document; marks it as having unsaved changes
+    pub fn store_mut(&mut self) -> &mut DocumentStore {
+        self.revision += 1;
+        &mut self.store
+    }
+
+    /// Apply `edit` so that it can be undone
+    pub fn apply(&mut self, edit: Edit) -> Result<(), EditError> {
+        self.journal.apply(&mut self.store, edit)?;
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Revert the last edit, or group of edits; returns its label
+    pub fn undo(&mut self) -> Result<Option<String>, EditError> {
+        let label = self.journal.undo(&mut self.store)?;
+        self.revision += u64::from(label.is_some());
+        Ok(label)
+    }
+
+    /// Repeat the last undone edit; returns its label
+    pub fn redo(&mut self) -> Result<Option<String>, EditError> {
+        let label = self.journal.redo(&mut self.store)?;
+        self.revision += u64::from(label.is_some());
+        Ok(label)
+    }
+
+    /// Undo history, e.g. to group edits
+    pub fn journal_mut(&mut self) -> &mut Journal {
+        &mut self.journal
+    }
+
+    pub fn history(&self) -> History {
+        self.journal.history()
+    }
+
+    /// What edits, undos and redos have touched since the last call;
+    /// changes made through `store_mut` are not tracked
+    pub fn take_changed(&mut self) -> Changed {
+        self.journal.take_changed()
+    }
+
+    /// Saved views, in creation order
+    pub fn views(&self) -> &[SavedView] {
+        &self.views
+    }
+
+    pub fn view(&self, name: &str) -> Option<&SavedView> {
+        self.views.iter().find(|v| v.name == name)
+    }
+
+    /// Add `view`, or replace the view with the same name
+    pub fn put_view(&mut self, view: SavedView) {
+        match self.views.iter_mut().find(|v| v.name == view.name) {
+            Some(existing) => *existing = view,
+            None => self.views.push(view),
+        }
+        self.revision += 1;
+    }
+
+    /// Delete view `name`. Returns whether it existed.
+    pub fn delete_view(&mut self, name: &str) -> bool {
+        let count = self.views.len();
+        self.views.retain(|v| v.name != name);
+        let deleted = self.views.len() < count;
+        self.revision += u64::from(deleted);
+        deleted
+    }
+
+    pub fn view_storage(&self) -> ViewStorage {
+        self.view_storage
+    }
+
+    /// Keep views in the document or in a sidecar file from the next save on
+    pub fn set_view_storage(&mut self, storage: ViewStorage) {
+        self.revision += u64::from(storage != self.view_storage);
+        self.view_storage = storage;
+    }
+
+    /// Whether there are changes not yet saved
+    pub fn is_dirty(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    /// Write the document back to the file it was opened from
+    pub fn save(&mut self) -> Result<(), DocumentError> {
+        let path = self.path.clone();
+        self.save_as(path)
+    }
+
+    /// Write the document to `path`, in the format its extension names, and
+    /// keep editing it there
+    pub fn save_as(&mut self, path: impl AsRef<Path>) -> Result<(), DocumentError> {
+        let copy = self.save_copy(path)?;
+        copy.write(&AtomicBool::new(false))?;
+        self.saved(copy);
+        Ok(())
+    }
+
+    /// Copy of what saving the document with its views to `path` writes
+    pub fn save_copy(&self, path: impl AsRef<Path>) -> Result<SaveCopy, DocumentError> {
+        let path = path.as_ref();
+        let format = DocumentFormat::of(path)?;
+        let mut reqif = self.store.reqif().clone();
+        if self.view_storage == ViewStorage::Document && !self.views.is_empty() {
+            reqif
+                .tool_extensions
+                .push(views::views_extension(&self.views)?);
+        }
+        let content = match (format, &self.archive) {
+            (DocumentFormat::Reqif, Some(slot))
+                if !slot.rest.documents.is_empty() || !slot.rest.resources.is_empty() =>
+            {
+                return Err(DocumentError::ArchiveContent)
+            }
+            (DocumentFormat::Reqif, _) => SaveContent::Reqif(Box::new(reqif)),
+            (DocumentFormat::Reqifz, Some(slot)) => {
+                let mut archive = slot.rest.clone();
+                let document = ArchiveDocument {
+                    path: slot.entry.clone(),
+                    reqif,
+                };
+                archive.documents.insert(slot.position, document);
+                SaveContent::Reqifz(archive)
+            }
+            (DocumentFormat::Reqifz, None) => {
+                SaveContent::Reqifz(ReqIfArchive::from_document(entry_name(path), reqif))
+            }
+        };
+        Ok(SaveCopy {
+            document: self.id,
+            revision: self.revision,
+            path: path.to_path_buf(),
+            format,
+            content,
+            sidecar: match self.view_storage {
+                ViewStorage::Sidecar => Some(self.views.clone()),
+                ViewStorage::Document => None,
+            },
+        })
+    }
+
+    /// Keep editing the document at the path `copy` was written to. The
+    /// document stays unsaved if it changed after the copy was taken. Returns
+    /// false, changing nothing, for a copy of another document.
+    pub fn saved(&mut self, copy: SaveCopy) -> bool {
+        if copy.document != self.id {
+            return false;
+        }
+        if copy.format == DocumentFormat::Reqifz && self.archive.is_none() {
+            self.archive = Some(ArchiveSlot {
+                entry: entry_name(&copy.path),
+                position: 0,
+                rest: ReqIfArchive::default(),
+            });
+        }
+        self.path = copy.path;
+        self.format = copy.format;
+        self.saved_revision = copy.revision;
+        true
+    }
+}
+
+impl SaveCopy {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the copy to its path, giving up once `cancelled` is set. The
+    /// file is only replaced once the copy is written in full.
+    pub fn write(&self, cancelled: &AtomicBool) -> Result<(), DocumentError> {
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let written = self.write_to(&partial, cancelled).and_then(|()| {
+            std::fs::rename(&partial, &self.path).map_err(|e| io_error(&self.path, e))
+        });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        written?;
+        // A sidecar left next to the document would be read back on open
+        // when the document holds no views
+        let sidecar = views::sidecar_path(&self.path);
+        match &self.sidecar {
+            Some(views) => views::write_sidecar(&sidecar, views)?,
+            None => views::remove_sidecar(&sidecar)?,
+        }
+        Ok(())
+    }
+
+    fn write_to(&self, path: &Path, cancelled: &AtomicBool) -> Result<(), DocumentError> {
+        let file = File::create(path).map_err(|e| io_error(path, e))?;
+        let mut out = BufWriter::new(Cancellable {
+            inner: file,
+            cancelled,
+        });
+        match &self.content {
+            SaveContent::Reqif(reqif) => serializer::write(reqif, &mut out)?,
+            SaveContent::Reqifz(archive) => archive::write_archive_to(archive, &mut out)?,
+        }
+        out.flush().map_err(|e| io_error(path, e))
+    }
+}
+
+/// Writer that fails once `cancelled` is set
+struct Cancellable<'a, W> {
+    inner: W,
+    cancelled: &'a AtomicBool,
+}
+
+impl<W: Write> Write for Cancellable<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Other, "cancelled"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for Cancellable<'_, W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+fn io_error(path: &Path, source: io::Error) -> DocumentError {
+    DocumentError::Io {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+/// Entry path of the document in a new archive at `path`
+fn entry_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("document");
+    format!("{}.reqif", stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    #[test]
+    fn test_open_edit_and_save_across_formats() {
+        let dir =
+            std::env::temp_dir().join(format!("reqsmith-document-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let reqif_path = dir.join("small.reqif");
+        std::fs::write(&reqif_path, SMALL).unwrap();
+
+        let mut document = Document::open(&reqif_path).unwrap();
+        assert_eq!(document.format(), DocumentFormat::Reqif);
+        assert!(!document.is_dirty());
+        document.store_mut().remove_spec_object("req-003");
+        assert!(document.is_dirty());
+
+        let archive_path = dir.join("small.reqifz");
+        document.save_as(&archive_path).unwrap();
+        assert!(!document.is_dirty());
+        assert_eq!(document.format(), DocumentFormat::Reqifz);
+
+        let mut reopened = Document::open(&archive_path).unwrap();
+        assert!(reopened.store().spec_object("req-003").is_none());
+        reopened.store_mut().remove_spec_object("req-002");
+        reopened.save().unwrap();
+        let archive = archive::read_archive(&archive_path).unwrap();
+        assert_eq!(archive.documents[0].path, "small.reqif");
+        assert_eq!(
+            archive.documents[0].reqif.core_content.spec_objects.len(),
+            1
+        );
+
+        assert!(matches!(
+            Document::open(dir.join("small.txt")),
+            Err(DocumentError::UnsupportedFile(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_copy_while_editing() {
+        let dir =
+            std::env::temp_dir().join(format!("reqsmith-save-copy-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("small.reqif");
+        std::fs::write(&path, SMALL).unwrap();
+        let mut document = Document::open(&path).unwrap();
+
+        // A cancelled save leaves the file as it was
+        document.store_mut().remove_spec_object("req-003");
+        let copy = document.save_copy(&path).unwrap();
+        assert!(copy.write(&AtomicBool::new(true)).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), SMALL);
+        assert!(!dir.join("small.reqif.part").exists());
+
+        // Edits made while the copy is written keep the document unsaved
+        let archive_path = dir.join("copy.reqifz");
+        let copy = document.save_copy(&archive_path).unwrap();
+        document.store_mut().remove_spec_object("req-002");
+        copy.write(&AtomicBool::new(false)).unwrap();
+        let other = Document::open(&path).unwrap().save_copy(&path).unwrap();
+        assert!(!document.saved(other));
+        assert!(document.saved(copy));
+        assert_eq!(document.path(), archive_path);
+        assert!(document.is_dirty());
+        let saved = Document::open(&archive_path).unwrap();
+        assert!(saved.store().spec_object("req-003").is_none());
+        assert!(saved.store().spec_object("req-002").is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_views_saved_in_document_or_sidecar() {
+        let dir = std::env::temp_dir().join(format!("reqsmith-views-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("small.reqif");
+        std::fs::write(&path, SMALL).unwrap();
+        let view = SavedView {
+            name: "Titles".into(),
+            specification: "spec-001".into(),
+            columns: vec!["ad-title".into()],
+            filter: None,
+            sort: Vec::new(),
+            group_by: None,
+            collapsed: Vec::new(),
+        };
+
+        let mut document = Document::open(&path).unwrap();
+        document.put_view(view.clone());
+        assert!(document.is_dirty());
+        document.save().unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains(views::VIEWS_ELEMENT));
+        let mut reopened = Document::open(&path).unwrap();
+        assert_eq!(reopened.views(), std::slice::from_ref(&view));
+        assert_eq!(reopened.view_storage(), ViewStorage::Document);
+        assert!(reopened.store().reqif().tool_extensions.is_empty());
+
+        reopened.set_view_storage(ViewStorage::Sidecar);
+        reopened.save().unwrap();
+        assert!(!std::fs::read_to_string(&path)
+            .unwrap()
+            .contains(views::VIEWS_ELEMENT));
+        let mut reopened = Document::open(&path).unwrap();
+        assert_eq!(reopened.view_storage(), ViewStorage::Sidecar);
+        assert!(reopened.delete_view("Titles"));
+        assert!(!reopened.delete_view("Titles"));
+        reopened.save().unwrap();
+        assert!(Document::open(&path).unwrap().views().is_empty());
+
+        // Moving the views back into the document drops the sidecar, so
+        // deleting them there does not bring the sidecar's back
+        let mut document = Document::open(&path).unwrap();
+        document.put_view(view);
+        document.save().unwrap();
+        let mut reopened = Document::open(&path).unwrap();
+        reopened.set_view_storage(ViewStorage::Document);
+        assert!(reopened.delete_view("Titles"));
+        reopened.save().unwrap();
+        assert!(!views::sidecar_path(&path).exists());
+        let reopened = Document::open(&path).unwrap();
+        assert!(reopened.views().is_empty());
+        assert_eq!(reopened.view_storage(), ViewStorage::Document);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
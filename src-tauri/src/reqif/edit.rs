@@ -0,0 +1,242 @@
+// ReqIF editing - Checked changes to the attribute values of SpecObjects
+
+use super::constraints::{check_value, ConstraintViolation};
+use super::model::{AttributeDefinition, AttributeValue, SpecTypeKind};
+use super::store::DocumentStore;
+use thiserror::Error;
+
+/// Reasons an edit is refused
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error("unknown spec object '{0}'")]
+    UnknownObject(String),
+    #[error("attribute '{definition}' is not defined for the type of '{object}'")]
+    UnknownAttribute { object: String, definition: String },
+    #[error("attribute '{0}' is not editable")]
+    NotEditable(String),
+    #[error("attribute '{definition}' accepts a single enum value, got {count}")]
+    TooManyEnumValues { definition: String, count: usize },
+    #[error("invalid value for attribute '{definition}': {violation}")]
+    Constraint {
+        definition: String,
+        violation: ConstraintViolation,
+    },
+    #[error("identifier '{0}' is already in use")]
+    DuplicateIdentifier(String),
+    #[error("unknown spec hierarchy '{0}'")]
+    UnknownHierarchy(String),
+    #[error("unknown specification '{0}'")]
+    UnknownSpecification(String),
+    #[error("unknown spec relation '{0}'")]
+    UnknownRelation(String),
+    #[error("unknown relation group '{0}'")]
+    UnknownRelationGroup(String),
+    #[error("unknown spec type '{0}'")]
+    UnknownSpecType(String),
+    #[error("spec hierarchy '{0}' cannot be moved below itself")]
+    InvalidMove(String),
+    #[error("spec type '{spec_type}' is not a {expected}")]
+    WrongSpecTypeKind {
+        spec_type: String,
+        expected: SpecTypeKind,
+    },
+    #[error("spec type '{0}' is still used")]
+    SpecTypeInUse(String),
+    #[error("attribute definition '{0}' still has values")]
+    DefinitionInUse(String),
+    #[error("attribute '{definition}' refers to unknown datatype '{datatype}'")]
+    UnknownDatatype {
+        definition: String,
+        datatype: String,
+    },
+}
+
+/// Set `value` on SpecObject `object`, replacing its current value for the
+/// same attribute definition. Returns the replaced value, if any.
+pub fn set_value(
+    store: &mut DocumentStore,
+    object: &str,
+    value: AttributeValue,
+) -> Result<Option<AttributeValue>, EditError> {
+    let spec_object = store
+        .spec_object(object)
+        .ok_or_else(|| EditError::UnknownObject(object.to_string()))?;
+    check_new_value(store, object, &spec_object.spec_type, &value)?;
+    let values = store.values_mut(object).expect("object checked above");
+    match values
+        .iter_mut()
+        .find(|v| v.definition() == value.definition())
+    {
+        Some(current) => Ok(Some(std::mem::replace(current, value))),
+        None => {
+            values.push(value);
+            Ok(None)
+        }
+    }
+}
+
+/// Check that `value` may be given to `owner`, an element of spec type
+/// `spec_type`: its attribute belongs to the type and is editable, and the
+/// value fits the attribute's datatype
+pub fn check_new_value(
+    store: &DocumentStore,
+    owner: &str,
+    spec_type: &str,
+    value: &AttributeValue,
+) -> Result<(), EditError> {
+    let definition = editable_attribute(store, owner, spec_type, value.definition())?;
+    if let AttributeValue::Enumeration { values, .. } = value {
+        if !definition.accepts_enum_value_count(values.len()) {
+            return Err(EditError::TooManyEnumValues {
+                definition: value.definition().to_string(),
+                count: values.len(),
+            });
+        }
+    }
+    if let Some(datatype) = store.definition_datatype(definition) {
+        if let Some(violation) = check_value(value, datatype).into_iter().next() {
+            return Err(EditError::Constraint {
+                definition: value.definition().to_string(),
+                violation,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Remove the value of `definition` from SpecObject `object`, so that the
+/// definition's default applies again. Returns the removed value, if any.
+pub fn clear_value(
+    store: &mut DocumentStore,
+    object: &str,
+    definition: &str,
+) -> Result<Option<AttributeValue>, EditError> {
+    check_editable(store, object, definition)?;
+    let values = store.values_mut(object).expect("object checked above");
+    Ok(values
+        .iter()
+        .position(|v| v.definition() == definition)
+        .map(|position| values.remove(position)))
+}
+
+/// Definition `definition` in the type of SpecObject `object`, provided that
+/// attribute is editable
+fn check_editable<'a>(
+    store: &'a DocumentStore,
+    object: &str,
+    definition: &str,
+) -> Result<&'a AttributeDefinition, EditError> {
+    let spec_object = store
+        .spec_object(object)
+        .ok_or_else(|| EditError::UnknownObject(object.to_string()))?;
+    editable_attribute(store, object, &spec_object.spec_type, definition)
+}
+
+/// Definition `definition` in spec type `spec_type` of `owner`, provided
+/// that attribute is editable
+fn editable_attribute<'a>(
+    store: &'a DocumentStore,
+    owner: &str,
+    spec_type: &str,
+    definition: &str,
+) -> Result<&'a AttributeDefinition, EditError> {
+    let attribute =
+        store
+            .type_attribute(spec_type, definition)
+            .ok_or_else(|| EditError::UnknownAttribute {
+                object: owner.to_string(),
+                definition: definition.to_string(),
+            })?;
+    if !attribute.editable() {
+        return Err(EditError::NotEditable(definition.to_string()));
+    }
+    Ok(attribute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::model::Extras;
+    use crate::reqif::parser;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    fn title(value: &str) -> AttributeValue {
+        AttributeValue::String {
+            definition: "ad-title".into(),
+            value: value.into(),
+            extras: Extras::default(),
+        }
+    }
+
+    #[test]
+    fn test_set_and_clear_value() {
+        let mut store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let previous = set_value(&mut store, "req-001", title("Boot fast")).unwrap();
+        match previous {
+            Some(AttributeValue::String { value, .. }) => {
+                assert_eq!(value, "System shall start within 5 seconds")
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+        assert!(matches!(
+            &store.spec_object("req-001").unwrap().values[0],
+            AttributeValue::String { value, .. } if value == "Boot fast"
+        ));
+
+        // Once cleared, the definition's default shows through
+        store.modify(|reqif| {
+            reqif.core_content.spec_types[0].spec_attributes[0].default_value =
+                Some(title("Untitled"))
+        });
+        clear_value(&mut store, "req-001", "ad-title").unwrap();
+        let object = store.spec_object("req-001").unwrap();
+        assert_eq!(object.values.len(), 2);
+        assert!(matches!(
+            store.value_or_default(object, "ad-title"),
+            Some(AttributeValue::String { value, .. }) if value == "Untitled"
+        ));
+        assert_eq!(store.content().effective_values(object).len(), 3);
+    }
+
+    #[test]
+    fn test_non_editable_attribute_is_refused() {
+        let mut reqif = parser::parse_str(SMALL).unwrap();
+        reqif.core_content.spec_types[0].spec_attributes[0].is_editable = Some(false);
+        let mut store = DocumentStore::new(reqif);
+        assert!(matches!(
+            set_value(&mut store, "req-001", title("Boot fast")),
+            Err(EditError::NotEditable(_))
+        ));
+        assert!(matches!(
+            clear_value(&mut store, "req-001", "ad-title"),
+            Err(EditError::NotEditable(_))
+        ));
+        assert!(matches!(
+            set_value(&mut store, "req-404", title("Boot fast")),
+            Err(EditError::UnknownObject(_))
+        ));
+    }
+
+    #[test]
+    fn test_value_breaking_datatype_limits_is_refused() {
+        let mut store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let priority = |value: i64| AttributeValue::Integer {
+            definition: "ad-priority".into(),
+            value,
+            extras: Extras::default(),
+        };
+        assert!(matches!(
+            set_value(&mut store, "req-001", priority(101)),
+            Err(EditError::Constraint {
+                violation: ConstraintViolation::AboveMax { .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            set_value(&mut store, "req-001", title("x".repeat(1001).as_str())),
+            Err(EditError::Constraint { .. })
+        ));
+        set_value(&mut store, "req-001", priority(100)).unwrap();
+    }
+}
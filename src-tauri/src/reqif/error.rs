@@ -0,0 +1,109 @@
+// ReqIF errors - Parse failures located by file, line and column
+
+use super::parser::ParseError;
+use serde::Serialize;
+use std::fmt;
+use thiserror::Error;
+
+/// Position in a source document. Lines and columns start at 1; columns
+/// count characters, not bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    /// Path of the document, when it was read from a file or archive entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl SourceLocation {
+    pub fn new(line: u64, column: u64) -> Self {
+        Self {
+            file: None,
+            line,
+            column,
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}:{}", file, self.line, self.column),
+            None => write!(f, "{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// A failure to read a ReqIF document, with the place it occurred
+#[derive(Debug, Error)]
+pub enum ReqIfError {
+    #[error("{location}: I/O error: {source}")]
+    Io {
+        location: SourceLocation,
+        source: std::io::Error,
+    },
+    /// The input is not well-formed XML
+    #[error("{location}: malformed XML: {source}")]
+    MalformedXml {
+        location: SourceLocation,
+        source: quick_xml::Error,
+    },
+    /// Well-formed XML that breaks the ReqIF schema: missing elements or
+    /// attributes, invalid values, too many enum values
+    #[error("{location}: {source}")]
+    Schema {
+        location: SourceLocation,
+        source: ParseError,
+    },
+    /// A reference that does not resolve to an element of the required kind
+    #[error("{location}: {source}")]
+    UnresolvedReference {
+        location: SourceLocation,
+        source: ParseError,
+    },
+    /// Valid ReqIF using a construct the parser cannot read
+    #[error("{location}: unsupported: {source}")]
+    Unsupported {
+        location: SourceLocation,
+        source: ParseError,
+    },
+}
+
+impl ReqIfError {
+    /// Classify `source` as raised at `location`
+    pub(crate) fn new(source: ParseError, location: SourceLocation) -> Self {
+        match source {
+            ParseError::Io(source) => ReqIfError::Io { location, source },
+            ParseError::Xml(source) => ReqIfError::MalformedXml { location, source },
+            ParseError::WrongSpecTypeKind { .. } => {
+                ReqIfError::UnresolvedReference { location, source }
+            }
+            ParseError::UnsupportedEncoding(_) => ReqIfError::Unsupported { location, source },
+            source => ReqIfError::Schema { location, source },
+        }
+    }
+
+    pub fn location(&self) -> &SourceLocation {
+        match self {
+            ReqIfError::Io { location, .. }
+            | ReqIfError::MalformedXml { location, .. }
+            | ReqIfError::Schema { location, .. }
+            | ReqIfError::UnresolvedReference { location, .. }
+            | ReqIfError::Unsupported { location, .. } => location,
+        }
+    }
+
+    /// Attach the path of the document the error was found in
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        let location = match &mut self {
+            ReqIfError::Io { location, .. }
+            | ReqIfError::MalformedXml { location, .. }
+            | ReqIfError::Schema { location, .. }
+            | ReqIfError::UnresolvedReference { location, .. }
+            | ReqIfError::Unsupported { location, .. } => location,
+        };
+        location.file = Some(file.into());
+        self
+    }
+}
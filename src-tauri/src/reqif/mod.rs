@@ -1,7 +1,16 @@
 // ReqIF module - Handles parsing, serialization, and data model for ReqIF files
 
+pub mod archive;
+pub mod constraints;
+pub mod document;
+pub mod edit;
+pub mod error;
 pub mod model;
-
-// Future submodules:
-// pub mod parser;
-// pub mod serializer;
+pub mod parser;
+pub mod serializer;
+pub mod store;
+pub mod table;
+pub mod undo;
+pub mod validate;
+pub mod views;
+pub mod xml;
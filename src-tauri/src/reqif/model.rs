@@ -1,7 +1,11 @@
 // ReqIF data model - Core data structures mirroring ReqIF 1.2 schema
 
+use super::xml::XmlElement;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
 
 /// Root ReqIF document structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +14,28 @@ pub struct ReqIF {
     pub core_content: CoreContent,
     #[serde(default)]
     pub tool_extensions: Vec<ToolExtension>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+    #[serde(default, skip_serializing_if = "Wrappers::is_empty")]
+    pub wrappers: Wrappers,
+}
+
+/// Unknown content of the THE-HEADER, CORE-CONTENT and TOOL-EXTENSIONS
+/// wrapper elements
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Wrappers {
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub header: Extras,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub core_content: Extras,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub tool_extensions: Extras,
+}
+
+impl Wrappers {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.core_content.is_empty() && self.tool_extensions.is_empty()
+    }
 }
 
 /// ReqIF header with metadata
@@ -18,12 +44,38 @@ pub struct ReqIFHeader {
     pub identifier: String,
     pub creation_time: String,
     pub source_tool_id: String,
+    #[serde(default)]
+    pub reqif_tool_id: String,
+    #[serde(default)]
+    pub reqif_version: String,
+    pub repository_id: Option<String>,
     pub title: Option<String>,
     pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+}
+
+/// IDENTIFIER, LONG-NAME, DESC and LAST-CHANGE shared by every ReqIF
+/// Identifiable element
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Identifiable {
+    pub identifier: String,
+    pub long_name: Option<String>,
+    pub desc: Option<String>,
+    pub last_change: Option<String>,
+}
+
+impl Identifiable {
+    pub fn new(identifier: impl Into<String>) -> Self {
+        Self {
+            identifier: identifier.into(),
+            ..Self::default()
+        }
+    }
 }
 
 /// Core content containing all specifications and requirements
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CoreContent {
     #[serde(default)]
     pub spec_objects: Vec<SpecObject>,
@@ -32,158 +84,703 @@ pub struct CoreContent {
     #[serde(default)]
     pub specifications: Vec<Specification>,
     #[serde(default)]
+    pub spec_relation_groups: Vec<RelationGroup>,
+    #[serde(default)]
     pub spec_types: Vec<SpecType>,
     #[serde(default)]
     pub datatype_definitions: Vec<DatatypeDefinition>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+}
+
+impl CoreContent {
+    pub fn spec_object(&self, id: &str) -> Option<&SpecObject> {
+        self.spec_objects.iter().find(|o| o.ident.identifier == id)
+    }
+
+    pub fn spec_type(&self, id: &str) -> Option<&SpecType> {
+        self.spec_types.iter().find(|t| t.ident.identifier == id)
+    }
+
+    /// Attribute definition `definition` of spec type `spec_type`
+    pub fn attribute_definition(
+        &self,
+        spec_type: &str,
+        definition: &str,
+    ) -> Option<&AttributeDefinition> {
+        self.spec_type(spec_type)?
+            .spec_attributes
+            .iter()
+            .find(|d| d.ident.identifier == definition)
+    }
+
+    /// Value of `definition` on `object`, falling back to the definition's
+    /// DEFAULT-VALUE when the object has none
+    pub fn value_or_default<'a>(
+        &'a self,
+        object: &'a SpecObject,
+        definition: &str,
+    ) -> Option<&'a AttributeValue> {
+        object
+            .values
+            .iter()
+            .find(|v| v.definition() == definition)
+            .or_else(|| {
+                self.attribute_definition(&object.spec_type, definition)?
+                    .default_value
+                    .as_ref()
+            })
+    }
+
+    /// Values of `object` followed by the defaults of every definition of its
+    /// type that the object has no value for
+    pub fn effective_values<'a>(&'a self, object: &'a SpecObject) -> Vec<&'a AttributeValue> {
+        let mut values: Vec<&AttributeValue> = object.values.iter().collect();
+        if let Some(spec_type) = self.spec_type(&object.spec_type) {
+            for definition in &spec_type.spec_attributes {
+                let id = definition.ident.identifier.as_str();
+                if object.values.iter().any(|v| v.definition() == id) {
+                    continue;
+                }
+                values.extend(&definition.default_value);
+            }
+        }
+        values
+    }
+
+    /// RelationGroups linking Specifications `a` and `b`, in either direction
+    pub fn relation_groups_between<'a>(
+        &'a self,
+        a: &'a str,
+        b: &'a str,
+    ) -> impl Iterator<Item = &'a RelationGroup> {
+        self.spec_relation_groups
+            .iter()
+            .filter(move |g| g.connects(a, b))
+    }
+
+    /// SpecRelations grouped under any RelationGroup linking Specifications
+    /// `a` and `b`, in document order
+    pub fn links_between(&self, a: &str, b: &str) -> Vec<&SpecRelation> {
+        let members: HashSet<&str> = self
+            .relation_groups_between(a, b)
+            .flat_map(|g| g.spec_relations.iter().map(String::as_str))
+            .collect();
+        self.spec_relations
+            .iter()
+            .filter(|r| members.contains(r.ident.identifier.as_str()))
+            .collect()
+    }
 }
 
 /// Individual requirement or specification object
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpecObject {
-    pub identifier: String,
+    #[serde(flatten)]
+    pub ident: Identifiable,
     pub spec_type: String, // Reference to SpecType
-    pub last_change: Option<String>,
     #[serde(default)]
     pub values: Vec<AttributeValue>,
-    /// Preserve unknown XML elements for round-trip
-    #[serde(default)]
-    pub extra_attrs: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
 }
 
 /// Link between requirements
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpecRelation {
-    pub identifier: String,
+    #[serde(flatten)]
+    pub ident: Identifiable,
     pub spec_type: String,
     pub source: String, // SpecObject ID
     pub target: String, // SpecObject ID
-    pub last_change: Option<String>,
     #[serde(default)]
     pub values: Vec<AttributeValue>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+}
+
+/// Bundle of SpecRelations linking two Specifications, e.g. all trace links
+/// from "System" to "Software"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationGroup {
+    #[serde(flatten)]
+    pub ident: Identifiable,
+    pub spec_type: String,            // RelationGroupType reference
+    pub source_specification: String, // Specification ID
+    pub target_specification: String, // Specification ID
+    #[serde(default)]
+    pub spec_relations: Vec<String>, // SpecRelation ID references
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+}
+
+impl RelationGroup {
+    /// Whether the group links `a` and `b`, in either direction
+    pub fn connects(&self, a: &str, b: &str) -> bool {
+        (self.source_specification == a && self.target_specification == b)
+            || (self.source_specification == b && self.target_specification == a)
+    }
 }
 
 /// Hierarchical specification structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Specification {
-    pub identifier: String,
+    #[serde(flatten)]
+    pub ident: Identifiable,
     pub spec_type: String,
-    pub last_change: Option<String>,
     #[serde(default)]
     pub values: Vec<AttributeValue>,
     #[serde(default)]
     pub children: Vec<SpecHierarchy>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
 }
 
 /// Hierarchy node referencing a SpecObject
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpecHierarchy {
-    pub identifier: String,
+    #[serde(flatten)]
+    pub ident: Identifiable,
     pub object: String, // SpecObject ID reference
-    pub last_change: Option<String>,
+    pub is_editable: Option<bool>,
+    pub is_table_internal: Option<bool>,
     #[serde(default)]
     pub children: Vec<SpecHierarchy>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
 }
 
-/// Type definition for SpecObjects
+/// Type definition for SpecObjects, SpecRelations, Specifications or
+/// RelationGroups
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpecType {
-    pub identifier: String,
-    pub long_name: Option<String>,
-    pub description: Option<String>,
-    pub last_change: Option<String>,
+    #[serde(flatten)]
+    pub ident: Identifiable,
+    #[serde(default)]
+    pub kind: SpecTypeKind,
     #[serde(default)]
     pub spec_attributes: Vec<AttributeDefinition>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+}
+
+/// Which elements a SpecType may type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpecTypeKind {
+    #[default]
+    SpecObjectType,
+    SpecRelationType,
+    SpecificationType,
+    RelationGroupType,
+}
+
+impl SpecTypeKind {
+    pub const ALL: [SpecTypeKind; 4] = [
+        SpecTypeKind::SpecObjectType,
+        SpecTypeKind::SpecRelationType,
+        SpecTypeKind::SpecificationType,
+        SpecTypeKind::RelationGroupType,
+    ];
+
+    /// ReqIF element name, e.g. `SPEC-OBJECT-TYPE`
+    pub fn element_name(self) -> &'static str {
+        match self {
+            SpecTypeKind::SpecObjectType => "SPEC-OBJECT-TYPE",
+            SpecTypeKind::SpecRelationType => "SPEC-RELATION-TYPE",
+            SpecTypeKind::SpecificationType => "SPECIFICATION-TYPE",
+            SpecTypeKind::RelationGroupType => "RELATION-GROUP-TYPE",
+        }
+    }
+
+    /// Kind for a ReqIF element name, `None` for anything else
+    pub fn from_element_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.element_name() == name)
+    }
+}
+
+impl fmt::Display for SpecTypeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.element_name())
+    }
 }
 
 /// Attribute definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttributeDefinition {
-    pub identifier: String,
-    pub long_name: Option<String>,
+    #[serde(flatten)]
+    pub ident: Identifiable,
     pub datatype_ref: String,
-    pub last_change: Option<String>,
+    /// Enumeration attributes only: whether several enum values may be selected
+    #[serde(default)]
+    pub multi_valued: bool,
+    /// IS-EDITABLE as written in the document; absent means editable
+    pub is_editable: Option<bool>,
+    /// Value assumed for SpecObjects that do not carry one
+    pub default_value: Option<AttributeValue>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
+}
+
+impl AttributeDefinition {
+    /// Whether values of this attribute may be changed
+    pub fn editable(&self) -> bool {
+        self.is_editable != Some(false)
+    }
+
+    /// Whether a value holding `count` enum value references is allowed
+    pub fn accepts_enum_value_count(&self, count: usize) -> bool {
+        self.multi_valued || count <= 1
+    }
 }
 
-/// Datatype definition (Boolean, Integer, Real, String, Enumeration, XHTML)
+/// Datatype definition (Boolean, Date, Integer, Real, String, Enumeration, XHTML)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
+#[allow(clippy::upper_case_acronyms)]
 pub enum DatatypeDefinition {
     Boolean {
-        identifier: String,
-        long_name: Option<String>,
+        #[serde(flatten)]
+        ident: Identifiable,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
+    },
+    Date {
+        #[serde(flatten)]
+        ident: Identifiable,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     Integer {
-        identifier: String,
-        long_name: Option<String>,
+        #[serde(flatten)]
+        ident: Identifiable,
         min: Option<i64>,
         max: Option<i64>,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     Real {
-        identifier: String,
-        long_name: Option<String>,
+        #[serde(flatten)]
+        ident: Identifiable,
         min: Option<f64>,
         max: Option<f64>,
         accuracy: Option<u32>,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     String {
-        identifier: String,
-        long_name: Option<String>,
+        #[serde(flatten)]
+        ident: Identifiable,
         max_length: Option<u32>,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     Enumeration {
-        identifier: String,
-        long_name: Option<String>,
+        #[serde(flatten)]
+        ident: Identifiable,
         values: Vec<EnumValue>,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     XHTML {
-        identifier: String,
-        long_name: Option<String>,
+        #[serde(flatten)]
+        ident: Identifiable,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
 }
 
+impl DatatypeDefinition {
+    pub fn ident(&self) -> &Identifiable {
+        match self {
+            DatatypeDefinition::Boolean { ident, .. }
+            | DatatypeDefinition::Date { ident, .. }
+            | DatatypeDefinition::Integer { ident, .. }
+            | DatatypeDefinition::Real { ident, .. }
+            | DatatypeDefinition::String { ident, .. }
+            | DatatypeDefinition::Enumeration { ident, .. }
+            | DatatypeDefinition::XHTML { ident, .. } => ident,
+        }
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.ident().identifier
+    }
+
+    pub fn extras(&self) -> &Extras {
+        match self {
+            DatatypeDefinition::Boolean { extras, .. }
+            | DatatypeDefinition::Date { extras, .. }
+            | DatatypeDefinition::Integer { extras, .. }
+            | DatatypeDefinition::Real { extras, .. }
+            | DatatypeDefinition::String { extras, .. }
+            | DatatypeDefinition::Enumeration { extras, .. }
+            | DatatypeDefinition::XHTML { extras, .. } => extras,
+        }
+    }
+}
+
 /// Enumeration value
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnumValue {
-    pub identifier: String,
-    pub long_name: Option<String>,
+    #[serde(flatten)]
+    pub ident: Identifiable,
     pub properties: Option<String>,
+    pub other_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
 }
 
 /// Attribute value (typed)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
+#[allow(clippy::upper_case_acronyms)]
 pub enum AttributeValue {
     Boolean {
         definition: String,
         value: bool,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
+    },
+    Date {
+        definition: String,
+        value: DateValue,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     Integer {
         definition: String,
         value: i64,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     Real {
         definition: String,
         value: f64,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     String {
         definition: String,
         value: String,
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     Enumeration {
         definition: String,
-        value: String, // EnumValue ID reference
+        #[serde(default)]
+        values: Vec<String>, // EnumValue ID references
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
     XHTML {
         definition: String,
         value: String, // XHTML content as string
+        #[serde(default, skip_serializing_if = "Extras::is_empty")]
+        extras: Extras,
     },
 }
 
+impl AttributeValue {
+    /// Identifier of the attribute definition this value belongs to
+    pub fn definition(&self) -> &str {
+        match self {
+            AttributeValue::Boolean { definition, .. }
+            | AttributeValue::Date { definition, .. }
+            | AttributeValue::Integer { definition, .. }
+            | AttributeValue::Real { definition, .. }
+            | AttributeValue::String { definition, .. }
+            | AttributeValue::Enumeration { definition, .. }
+            | AttributeValue::XHTML { definition, .. } => definition,
+        }
+    }
+
+    pub fn extras(&self) -> &Extras {
+        match self {
+            AttributeValue::Boolean { extras, .. }
+            | AttributeValue::Date { extras, .. }
+            | AttributeValue::Integer { extras, .. }
+            | AttributeValue::Real { extras, .. }
+            | AttributeValue::String { extras, .. }
+            | AttributeValue::Enumeration { extras, .. }
+            | AttributeValue::XHTML { extras, .. } => extras,
+        }
+    }
+}
+
+/// Type suffix used in typed element names, e.g. `STRING` in
+/// `ATTRIBUTE-DEFINITION-STRING`
+pub fn datatype_kind(datatype: &DatatypeDefinition) -> &'static str {
+    match datatype {
+        DatatypeDefinition::Boolean { .. } => "BOOLEAN",
+        DatatypeDefinition::Date { .. } => "DATE",
+        DatatypeDefinition::Integer { .. } => "INTEGER",
+        DatatypeDefinition::Real { .. } => "REAL",
+        DatatypeDefinition::String { .. } => "STRING",
+        DatatypeDefinition::Enumeration { .. } => "ENUMERATION",
+        DatatypeDefinition::XHTML { .. } => "XHTML",
+    }
+}
+
+/// Type suffix of the datatype `value` is written for
+pub fn value_kind(value: &AttributeValue) -> &'static str {
+    match value {
+        AttributeValue::Boolean { .. } => "BOOLEAN",
+        AttributeValue::Date { .. } => "DATE",
+        AttributeValue::Integer { .. } => "INTEGER",
+        AttributeValue::Real { .. } => "REAL",
+        AttributeValue::String { .. } => "STRING",
+        AttributeValue::Enumeration { .. } => "ENUMERATION",
+        AttributeValue::XHTML { .. } => "XHTML",
+    }
+}
+
+/// Timestamp held by an ATTRIBUTE-VALUE-DATE (xsd:dateTime).
+///
+/// The original time zone offset, and how it was written, is kept for
+/// writing the value back, while ordering and equality compare the instant
+/// in time, so values from different time zones sort correctly.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DateValue {
+    datetime: DateTime<FixedOffset>,
+    zone: ZoneForm,
+}
+
+/// How the time zone of a [`DateValue`] is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ZoneForm {
+    /// Not at all; the value is read as UTC
+    Absent,
+    /// As `Z`
+    Utc,
+    /// As an offset such as `+01:00`, including `+00:00`
+    Offset,
+}
+
+impl DateValue {
+    /// Value for `datetime`, written with `Z` when it is in UTC
+    pub fn new(datetime: DateTime<FixedOffset>) -> Self {
+        let zone = if datetime.offset().local_minus_utc() == 0 {
+            ZoneForm::Utc
+        } else {
+            ZoneForm::Offset
+        };
+        Self { datetime, zone }
+    }
+
+    /// Parse an xsd:dateTime. Values without a time zone are taken as UTC.
+    pub fn parse(value: &str) -> Result<Self, chrono::ParseError> {
+        let value = value.trim();
+        match DateTime::parse_from_rfc3339(value) {
+            Ok(datetime) => Ok(Self {
+                datetime,
+                zone: if value.ends_with(['Z', 'z']) {
+                    ZoneForm::Utc
+                } else {
+                    ZoneForm::Offset
+                },
+            }),
+            Err(err) => NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .map(|naive| Self {
+                    datetime: naive.and_utc().fixed_offset(),
+                    zone: ZoneForm::Absent,
+                })
+                .map_err(|_| err),
+        }
+    }
+
+    pub fn datetime(&self) -> DateTime<FixedOffset> {
+        self.datetime
+    }
+
+    pub fn to_utc(&self) -> DateTime<Utc> {
+        self.datetime.with_timezone(&Utc)
+    }
+
+    /// Format with a `chrono` format string, e.g. `"%Y-%m-%d"`
+    pub fn format(&self, fmt: &str) -> String {
+        self.datetime.format(fmt).to_string()
+    }
+}
+
+impl PartialEq for DateValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.datetime == other.datetime
+    }
+}
+
+impl Eq for DateValue {}
+
+impl PartialOrd for DateValue {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DateValue {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.datetime.cmp(&other.datetime)
+    }
+}
+
+impl std::hash::Hash for DateValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.datetime.hash(state)
+    }
+}
+
+impl fmt::Display for DateValue {
+    /// Writes the xsd:dateTime form used in ReqIF files, with the time zone
+    /// as it was read
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.zone {
+            ZoneForm::Absent => write!(f, "{}", self.datetime.format("%Y-%m-%dT%H:%M:%S%.f")),
+            ZoneForm::Utc => {
+                f.write_str(&self.datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            ZoneForm::Offset => {
+                f.write_str(&self.datetime.to_rfc3339_opts(SecondsFormat::AutoSi, false))
+            }
+        }
+    }
+}
+
+impl FromStr for DateValue {
+    type Err = chrono::ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+impl TryFrom<String> for DateValue {
+    type Error = chrono::ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<DateValue> for String {
+    fn from(value: DateValue) -> Self {
+        value.to_string()
+    }
+}
+
+/// XML content the model does not understand, kept for lossless round-trip
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extras {
+    /// Unknown XML attributes in document order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<(String, String)>,
+    /// Unknown child elements in document order. Unknown items found inside a
+    /// known container (e.g. an unsupported ATTRIBUTE-VALUE-* within VALUES)
+    /// are grouped under an element named after that container.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub elements: Vec<XmlElement>,
+    /// Order the element was read in; `None` for elements created in the app
+    #[serde(skip)]
+    pub layout: Option<Layout>,
+}
+
+/// Original order of an element's attributes and children, so that unknown
+/// content is written back where it was found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+    /// Names of all attributes in document order
+    pub attributes: Vec<String>,
+    /// Child elements in document order: the name of a known child, or
+    /// `None` for the next unknown element
+    pub children: Vec<Option<String>>,
+    /// Per known container, the number of known items before each of its
+    /// unknown items
+    pub items: Vec<(String, Vec<usize>)>,
+}
+
+impl Extras {
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.elements.is_empty()
+    }
+
+    /// Whether the element was read with the attribute `name`
+    pub fn had_attribute(&self, name: &str) -> bool {
+        self.layout
+            .as_ref()
+            .is_some_and(|l| l.attributes.iter().any(|a| a == name))
+    }
+
+    /// Unknown child elements, excluding container groups
+    pub fn unknown_elements<'a>(
+        &'a self,
+        containers: &'a [&'a str],
+    ) -> impl Iterator<Item = &'a XmlElement> + 'a {
+        self.elements
+            .iter()
+            .filter(move |e| !containers.contains(&e.name.as_str()))
+    }
+
+    /// Unknown items kept for the known container `name`
+    pub fn container_items<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a XmlElement> + 'a {
+        self.elements
+            .iter()
+            .filter(move |e| e.name == name)
+            .flat_map(|e| e.elements())
+    }
+
+    /// Unknown items kept for the known container `name`, each with the
+    /// number of known items that preceded it; items without a recorded
+    /// position follow the known ones
+    pub fn positioned_items<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a XmlElement)> + 'a {
+        let positions = self
+            .layout
+            .iter()
+            .flat_map(|l| &l.items)
+            .find(|(container, _)| container == name)
+            .map(|(_, positions)| positions.as_slice())
+            .unwrap_or_default();
+        self.container_items(name)
+            .enumerate()
+            .map(move |(i, e)| (positions.get(i).copied().unwrap_or(usize::MAX), e))
+    }
+
+    /// Record unknown items found inside the known container `name`, each
+    /// with the number of known items before it
+    pub fn push_container_items(&mut self, name: &str, items: Vec<(usize, XmlElement)>) {
+        if items.is_empty() {
+            return;
+        }
+        let (positions, items): (Vec<usize>, Vec<XmlElement>) = items.into_iter().unzip();
+        if let Some(layout) = &mut self.layout {
+            match layout.items.iter_mut().find(|(c, _)| c == name) {
+                Some((_, existing)) => existing.extend(positions),
+                None => layout.items.push((name.to_string(), positions)),
+            }
+        }
+        match self.elements.iter_mut().find(|e| e.name == name) {
+            Some(group) => group.children.extend(items.into_iter().map(Into::into)),
+            None => self.elements.push(XmlElement {
+                name: name.to_string(),
+                attributes: Vec::new(),
+                children: items.into_iter().map(Into::into).collect(),
+            }),
+        }
+    }
+}
+
 /// Tool-specific extensions
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolExtension {
-    pub identifier: String,
+    /// Markup inside the REQ-IF-TOOL-EXTENSION element
     pub content: String,
+    /// Attributes of the REQ-IF-TOOL-EXTENSION element
+    #[serde(default, skip_serializing_if = "Extras::is_empty")]
+    pub extras: Extras,
 }
 
 #[cfg(test)]
@@ -193,13 +790,14 @@ mod tests {
     #[test]
     fn test_spec_object_creation() {
         let spec_obj = SpecObject {
-            identifier: "REQ-001".to_string(),
+            ident: Identifiable::new("REQ-001"),
             spec_type: "requirement-type".to_string(),
-            last_change: None,
             values: vec![],
-            extra_attrs: HashMap::new(),
+            extras: Extras::default(),
         };
-        assert_eq!(spec_obj.identifier, "REQ-001");
+        assert_eq!(spec_obj.ident.identifier, "REQ-001");
+        let json = serde_json::to_string(&spec_obj).unwrap();
+        assert!(json.contains(r#""identifier":"REQ-001""#));
     }
 
     #[test]
@@ -207,8 +805,37 @@ mod tests {
         let attr = AttributeValue::String {
             definition: "attr-def-1".to_string(),
             value: "Test requirement".to_string(),
+            extras: Extras::default(),
         };
         let json = serde_json::to_string(&attr).unwrap();
         assert!(json.contains("Test requirement"));
     }
+
+    #[test]
+    fn test_date_value_parse_and_format() {
+        let date = DateValue::parse("2025-03-01T12:00:00+01:00").unwrap();
+        assert_eq!(date.to_string(), "2025-03-01T12:00:00+01:00");
+        assert_eq!(date.format("%Y-%m-%d"), "2025-03-01");
+        assert_eq!(date.to_utc().to_rfc3339(), "2025-03-01T11:00:00+00:00");
+
+        let utc = DateValue::parse("2025-03-01T11:00:00.250Z").unwrap();
+        assert_eq!(utc.to_string(), "2025-03-01T11:00:00.250Z");
+        let naive = DateValue::parse("2025-03-01T11:00:00").unwrap();
+        assert_eq!(naive.to_string(), "2025-03-01T11:00:00");
+        let zero = DateValue::parse("2025-03-01T11:00:00+00:00").unwrap();
+        assert_eq!(zero.to_string(), "2025-03-01T11:00:00+00:00");
+        assert_eq!(zero, DateValue::parse("2025-03-01T11:00:00Z").unwrap());
+        assert!(DateValue::parse("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_date_value_ordering_uses_instant() {
+        let berlin = DateValue::parse("2025-03-01T12:00:00+01:00").unwrap();
+        let london = DateValue::parse("2025-03-01T11:00:00Z").unwrap();
+        let later = DateValue::parse("2025-03-01T11:30:00Z").unwrap();
+        assert_eq!(berlin, london);
+        let mut dates = vec![later, berlin];
+        dates.sort();
+        assert_eq!(dates, vec![london, later]);
+    }
 }
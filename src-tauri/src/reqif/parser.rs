@@ -0,0 +1,1820 @@
+// ReqIF parser - Reads ReqIF 1.2 XML into the data model
+
+use super::error::{ReqIfError, SourceLocation};
+use super::model::*;
+use super::xml::{local_name, PositionReader, XmlElement, XmlNode};
+use quick_xml::errors::IllFormedError;
+use quick_xml::events::{BytesDecl, BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+
+/// What went wrong while reading a ReqIF document. The public functions
+/// report these as a [`ReqIfError`] carrying the source position.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("document has no REQ-IF root element")]
+    MissingRoot,
+    #[error("{element} is missing {what}")]
+    Missing { element: String, what: String },
+    #[error("invalid {what} '{value}' in {element}")]
+    InvalidValue {
+        element: String,
+        what: String,
+        value: String,
+    },
+    #[error("{owner} has {count} values for single-valued enumeration attribute '{definition}'")]
+    TooManyEnumValues {
+        owner: String,
+        definition: String,
+        count: usize,
+    },
+    #[error("{owner} references {found} '{spec_type}' where a {expected} is required")]
+    WrongSpecTypeKind {
+        owner: String,
+        spec_type: String,
+        expected: SpecTypeKind,
+        found: SpecTypeKind,
+    },
+    #[error("document encoding '{0}' is not supported, only UTF-8")]
+    UnsupportedEncoding(String),
+}
+
+/// Parse a ReqIF document from a string
+pub fn parse_str(xml: &str) -> Result<ReqIF, ReqIfError> {
+    parse_reader(xml.as_bytes())
+}
+
+/// Parse a `.reqif` file from disk; errors name the file
+pub fn parse_file(path: impl AsRef<Path>) -> Result<ReqIF, ReqIfError> {
+    let path = path.as_ref();
+    File::open(path)
+        .map_err(|e| ReqIfError::new(e.into(), SourceLocation::default()))
+        .and_then(|file| parse_reader(BufReader::new(file)))
+        .map_err(|e| e.with_file(path.display().to_string()))
+}
+
+/// Parse a ReqIF document from any buffered reader
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<ReqIF, ReqIfError> {
+    Parser::new(reader, false)
+        .parse()
+        .map(|recovered| recovered.reqif)
+}
+
+/// Document read in lenient mode, with the problems that were skipped over
+#[derive(Debug)]
+pub struct Recovered {
+    pub reqif: ReqIF,
+    pub problems: Vec<ReqIfError>,
+}
+
+/// Parse a ReqIF document from a string, recovering from schema errors
+pub fn parse_str_lenient(xml: &str) -> Result<Recovered, ReqIfError> {
+    parse_reader_lenient(xml.as_bytes())
+}
+
+/// Parse a `.reqif` file from disk, recovering from schema errors
+pub fn parse_file_lenient(path: impl AsRef<Path>) -> Result<Recovered, ReqIfError> {
+    let path = path.as_ref();
+    let file = path.display().to_string();
+    let mut recovered = File::open(path)
+        .map_err(|e| ReqIfError::new(e.into(), SourceLocation::default()))
+        .and_then(|f| parse_reader_lenient(BufReader::new(f)))
+        .map_err(|e| e.with_file(file.clone()))?;
+    recovered.problems = recovered
+        .problems
+        .into_iter()
+        .map(|e| e.with_file(file.clone()))
+        .collect();
+    Ok(recovered)
+}
+
+/// Parse a ReqIF document from any buffered reader, recovering from schema
+/// errors. Items that cannot be read (e.g. an attribute value with an
+/// invalid THE-VALUE) are kept verbatim like unknown elements, a missing
+/// TYPE reference is left empty, and spec type kind and enum value count
+/// checks only report. Malformed XML, a missing header and unsupported
+/// encodings still fail the load.
+pub fn parse_reader_lenient<R: BufRead>(reader: R) -> Result<Recovered, ReqIfError> {
+    Parser::new(reader, true).parse()
+}
+
+struct Parser<R: BufRead> {
+    reader: Reader<PositionReader<R>>,
+    buf: Vec<u8>,
+    /// Start of the element whose children are being visited; schema errors
+    /// are reported here
+    element: (u64, u64),
+    /// Start of every parsed content item by identifier, for errors found
+    /// once the whole document is read
+    items: HashMap<String, (u64, u64)>,
+    /// Recover from schema errors instead of failing
+    lenient: bool,
+    /// Problems recovered from in lenient mode
+    problems: Vec<ReqIfError>,
+}
+
+impl<R: BufRead> Parser<R> {
+    fn new(reader: R, lenient: bool) -> Self {
+        Self {
+            reader: Reader::from_reader(PositionReader::new(reader)),
+            buf: Vec::new(),
+            element: (1, 1),
+            items: HashMap::new(),
+            lenient,
+            problems: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> Result<Recovered, ReqIfError> {
+        match self.parse_document() {
+            Ok(reqif) => Ok(Recovered {
+                reqif,
+                problems: self.problems,
+            }),
+            Err(e) => Err(self.locate(e)),
+        }
+    }
+
+    /// Attach the source position to `e`: I/O and XML errors are reported at
+    /// the current read position, schema errors at the element being parsed
+    fn locate(&self, e: ParseError) -> ReqIfError {
+        let (line, column) = match &e {
+            ParseError::Io(_) | ParseError::Xml(_) | ParseError::MissingRoot => {
+                self.reader.get_ref().position()
+            }
+            ParseError::TooManyEnumValues { owner, .. }
+            | ParseError::WrongSpecTypeKind { owner, .. } => {
+                self.items.get(owner).copied().unwrap_or(self.element)
+            }
+            _ => self.element,
+        };
+        ReqIfError::new(e, SourceLocation::new(line, column))
+    }
+
+    /// In lenient mode note `e` as a recovered problem; otherwise fail with it
+    fn recover(&mut self, e: ParseError) -> Result<(), ParseError> {
+        if !self.lenient {
+            return Err(e);
+        }
+        let problem = self.locate(e);
+        self.problems.push(problem);
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Result<Event<'static>, ParseError> {
+        self.buf.clear();
+        Ok(self.reader.read_event_into(&mut self.buf)?.into_owned())
+    }
+
+    /// Materialize the element that starts with `start`
+    fn read_element(&mut self, start: &BytesStart) -> Result<XmlElement, ParseError> {
+        Ok(XmlElement::read(&mut self.reader, start, &mut self.buf)?)
+    }
+
+    /// Materialize the element that starts with `start`, which has no
+    /// content when `empty`
+    fn element(&mut self, start: &BytesStart, empty: bool) -> Result<XmlElement, ParseError> {
+        if empty {
+            Ok(XmlElement::from_start(start)?)
+        } else {
+            self.read_element(start)
+        }
+    }
+
+    /// Skip the remainder of an element we do not handle
+    fn skip(&mut self, start: &BytesStart) -> Result<(), ParseError> {
+        self.reader.read_to_end_into(start.name(), &mut self.buf)?;
+        Ok(())
+    }
+
+    /// Call `f` for every direct child element of the element currently open.
+    /// `f` receives the start tag and whether it was self-closing.
+    fn for_each_child(
+        &mut self,
+        mut f: impl FnMut(&mut Self, &BytesStart, bool) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        loop {
+            match self.next_event()? {
+                Event::Start(e) => {
+                    self.element = self.reader.get_ref().tag_start();
+                    f(self, &e, false)?
+                }
+                Event::Empty(e) => {
+                    self.element = self.reader.get_ref().tag_start();
+                    f(self, &e, true)?
+                }
+                Event::End(_) | Event::Eof => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Read every child of the current container as a full element subtree
+    fn for_each_item(
+        &mut self,
+        mut f: impl FnMut(&mut Self, XmlElement) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        self.for_each_child(|p, start, empty| {
+            let element = p.element(start, empty)?;
+            if let Some(id) = element.attr("IDENTIFIER") {
+                p.items.insert(id.to_string(), p.element);
+            }
+            f(p, element)
+        })
+    }
+
+    fn parse_document(&mut self) -> Result<ReqIF, ParseError> {
+        loop {
+            match self.next_event()? {
+                Event::Decl(decl) => check_encoding(&decl)?,
+                Event::Start(e) if local_name_of(&e) == "REQ-IF" => return self.parse_root(&e),
+                Event::Empty(e) if local_name_of(&e) == "REQ-IF" => {
+                    self.element = self.reader.get_ref().tag_start();
+                    return Err(ParseError::Missing {
+                        element: "REQ-IF".into(),
+                        what: "THE-HEADER".into(),
+                    });
+                }
+                Event::Eof => return Err(ParseError::MissingRoot),
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_root(&mut self, root: &BytesStart) -> Result<ReqIF, ParseError> {
+        let root_start = self.reader.get_ref().tag_start();
+        let root = XmlElement::from_start(root)?;
+        let mut header = None;
+        let mut core_content = CoreContent::default();
+        let mut tool_extensions = Vec::new();
+        let mut wrappers = Wrappers::default();
+        // Namespace declarations for ReqIF and XHTML are always written by
+        // the serializer; anything else on the root is kept verbatim
+        let mut root_extras = extras(&root, &["xmlns", "xmlns:xhtml"], &[]);
+
+        self.for_each_child(|p, start, empty| {
+            let name = local_name_of(start);
+            match name.as_str() {
+                "THE-HEADER" => {
+                    let element = p.element(start, empty)?;
+                    if let Some(h) = element.child("REQ-IF-HEADER") {
+                        header = Some(parse_header(h)?);
+                    }
+                    wrappers.header = extras(&element, &[], &["REQ-IF-HEADER"]);
+                }
+                "CORE-CONTENT" => {
+                    let wrapper = &mut wrappers.core_content;
+                    *wrapper = extras(&XmlElement::from_start(start)?, &[], &[]);
+                    if !empty {
+                        p.for_each_child(|p, start, empty| {
+                            if local_name_of(start) == "REQ-IF-CONTENT" {
+                                note_child(wrapper, Some("REQ-IF-CONTENT"));
+                                p.parse_content(start, empty, &mut core_content)
+                            } else {
+                                note_child(wrapper, None);
+                                wrapper.elements.push(p.element(start, empty)?);
+                                Ok(())
+                            }
+                        })?;
+                    }
+                }
+                "TOOL-EXTENSIONS" => {
+                    let element = p.element(start, empty)?;
+                    let wrapper = &mut wrappers.tool_extensions;
+                    *wrapper = extras(&XmlElement::from_start(start)?, &[], &[]);
+                    let mut unknown = Vec::new();
+                    for e in element.elements() {
+                        if e.local_name() == "REQ-IF-TOOL-EXTENSION" {
+                            tool_extensions.push(parse_tool_extension(e));
+                        } else {
+                            unknown.push((tool_extensions.len(), e.clone()));
+                        }
+                    }
+                    wrapper.push_container_items("TOOL-EXTENSIONS", unknown);
+                }
+                _ => {
+                    note_child(&mut root_extras, None);
+                    root_extras.elements.push(p.element(start, empty)?);
+                    return Ok(());
+                }
+            }
+            note_child(&mut root_extras, Some(&name));
+            Ok(())
+        })?;
+
+        self.element = root_start;
+        let header = header.ok_or_else(|| ParseError::Missing {
+            element: "REQ-IF".into(),
+            what: "REQ-IF-HEADER".into(),
+        })?;
+        let problems = check_spec_type_kinds(&core_content)
+            .into_iter()
+            .chain(check_enum_value_counts(&core_content));
+        for problem in problems {
+            self.recover(problem)?;
+        }
+        Ok(ReqIF {
+            header,
+            core_content,
+            tool_extensions,
+            extras: root_extras,
+            wrappers,
+        })
+    }
+
+    /// Parse REQ-IF-CONTENT, which starts with `start`
+    fn parse_content(
+        &mut self,
+        start: &BytesStart,
+        empty: bool,
+        content: &mut CoreContent,
+    ) -> Result<(), ParseError> {
+        let mut extras = extras(&XmlElement::from_start(start)?, &[], &[]);
+        if empty {
+            content.extras = extras;
+            return Ok(());
+        }
+        self.for_each_child(|p, start, empty| {
+            let name = local_name_of(start);
+            if !is_content_section(&name) {
+                note_child(&mut extras, None);
+                extras.elements.push(p.element(start, empty)?);
+                return Ok(());
+            }
+            note_child(&mut extras, Some(&name));
+            if empty {
+                return Ok(());
+            }
+            match name.as_str() {
+                "DATATYPES" => p.parse_items(
+                    &name,
+                    &mut content.datatype_definitions,
+                    &mut extras,
+                    |e, _| parse_datatype(e),
+                ),
+                "SPEC-TYPES" => {
+                    p.parse_items(&name, &mut content.spec_types, &mut extras, |e, _| {
+                        parse_spec_type(e)
+                    })
+                }
+                "SPEC-OBJECTS" => p.parse_items(
+                    &name,
+                    &mut content.spec_objects,
+                    &mut extras,
+                    parse_spec_object,
+                ),
+                "SPEC-RELATIONS" => p.parse_items(
+                    &name,
+                    &mut content.spec_relations,
+                    &mut extras,
+                    parse_spec_relation,
+                ),
+                "SPECIFICATIONS" => p.parse_items(
+                    &name,
+                    &mut content.specifications,
+                    &mut extras,
+                    parse_specification,
+                ),
+                _ => p.parse_items(
+                    &name,
+                    &mut content.spec_relation_groups,
+                    &mut extras,
+                    parse_relation_group,
+                ),
+            }
+        })?;
+        content.extras = extras;
+        Ok(())
+    }
+
+    /// Parse the items of a content section; items `parse` does not
+    /// recognize, or in lenient mode cannot read, are kept in `extras` under
+    /// the section name
+    fn parse_items<T>(
+        &mut self,
+        container: &str,
+        items: &mut Vec<T>,
+        extras: &mut Extras,
+        parse: impl Fn(&XmlElement, &mut Recovery) -> Result<Option<T>, ParseError>,
+    ) -> Result<(), ParseError> {
+        let mut unknown = Vec::new();
+        self.for_each_item(|p, e| {
+            let mut recovery = Recovery {
+                lenient: p.lenient,
+                problems: Vec::new(),
+            };
+            let parsed = parse(&e, &mut recovery);
+            for problem in recovery.problems {
+                p.recover(problem)?;
+            }
+            match parsed {
+                Ok(Some(item)) => items.push(item),
+                Ok(None) => unknown.push((items.len(), e)),
+                Err(error) => {
+                    p.recover(error)?;
+                    unknown.push((items.len(), e));
+                }
+            }
+            Ok(())
+        })?;
+        extras.push_container_items(container, unknown);
+        Ok(())
+    }
+}
+
+/// Content item yielded by [`ItemReader`]
+#[derive(Debug, Clone)]
+pub enum ReqIfItem {
+    Header(ReqIFHeader),
+    Datatype(DatatypeDefinition),
+    SpecType(SpecType),
+    SpecObject(SpecObject),
+    SpecRelation(SpecRelation),
+    /// Specification without its hierarchy, which comes as separate items
+    Specification(Specification),
+    /// SpecHierarchy entry without sub-entries, placed under `parent` or,
+    /// without one, directly in `specification`
+    Hierarchy {
+        specification: String,
+        parent: Option<String>,
+        hierarchy: SpecHierarchy,
+    },
+    RelationGroup(RelationGroup),
+}
+
+/// Read the items of a ReqIF document one at a time
+pub fn items_from_reader<R: BufRead>(reader: R) -> ItemReader<R> {
+    ItemReader {
+        parser: Parser::new(reader, false),
+        section: None,
+        open: Vec::new(),
+        file: None,
+        seen_root: false,
+        done: false,
+    }
+}
+
+/// Read the items of a `.reqif` file one at a time; errors name the file
+pub fn items_from_file(path: impl AsRef<Path>) -> Result<ItemReader<BufReader<File>>, ReqIfError> {
+    let path = path.as_ref().display().to_string();
+    let file = File::open(&path)
+        .map_err(|e| ReqIfError::new(e.into(), SourceLocation::default()).with_file(&path))?;
+    let mut items = items_from_reader(BufReader::new(file));
+    items.file = Some(path);
+    Ok(items)
+}
+
+/// Iterator over the header and content items of a ReqIF document that only
+/// holds the item being read in memory, for documents too large to load as a
+/// whole. Specifications are split into their SpecHierarchy entries, each
+/// yielded once its element ends, so sub-entries come before the entry
+/// holding them and the specification comes last. Unknown elements are
+/// skipped, and the document-wide spec type kind and enum value count checks
+/// of [`parse_reader`] are not applied. Iteration stops after the first error.
+pub struct ItemReader<R: BufRead> {
+    parser: Parser<R>,
+    /// Content section being read, e.g. SPEC-OBJECTS
+    section: Option<String>,
+    /// Specification and SpecHierarchy elements being read, outermost first
+    open: Vec<OpenElement>,
+    file: Option<String>,
+    seen_root: bool,
+    done: bool,
+}
+
+impl<R: BufRead> ItemReader<R> {
+    fn next_item(&mut self) -> Result<Option<ReqIfItem>, ParseError> {
+        loop {
+            if !self.open.is_empty() {
+                match self.next_in_specification()? {
+                    Some(item) => return Ok(Some(item)),
+                    None => continue,
+                }
+            }
+            let (start, empty) = match self.parser.next_event()? {
+                Event::Start(e) => (e, false),
+                Event::Empty(e) => (e, true),
+                Event::End(e) => {
+                    let name = local_name(&String::from_utf8_lossy(e.name().as_ref())).to_string();
+                    if self.section.as_ref() == Some(&name) {
+                        self.section = None;
+                    }
+                    continue;
+                }
+                Event::Decl(decl) => {
+                    check_encoding(&decl)?;
+                    continue;
+                }
+                Event::Eof if !self.seen_root => return Err(ParseError::MissingRoot),
+                Event::Eof => return Ok(None),
+                _ => continue,
+            };
+            self.parser.element = self.parser.reader.get_ref().tag_start();
+            let name = local_name_of(&start);
+            let Some(section) = &self.section else {
+                match name.as_str() {
+                    "REQ-IF" => self.seen_root = true,
+                    "CORE-CONTENT" | "REQ-IF-CONTENT" => {}
+                    _ if empty => {}
+                    "THE-HEADER" => {
+                        let element = self.parser.read_element(&start)?;
+                        if let Some(header) = element.child("REQ-IF-HEADER") {
+                            return Ok(Some(ReqIfItem::Header(parse_header(header)?)));
+                        }
+                    }
+                    _ if is_content_section(&name) => self.section = Some(name),
+                    _ => self.parser.skip(&start)?,
+                }
+                continue;
+            };
+            if section == "SPECIFICATIONS" && name == "SPECIFICATION" && !empty {
+                self.open
+                    .push(OpenElement::new(&start, self.parser.element)?);
+                continue;
+            }
+            let element = if empty {
+                XmlElement::from_start(&start)?
+            } else {
+                self.parser.read_element(&start)?
+            };
+            let mut strict = Recovery {
+                lenient: false,
+                problems: Vec::new(),
+            };
+            let item = match section.as_str() {
+                "DATATYPES" => parse_datatype(&element)?.map(ReqIfItem::Datatype),
+                "SPEC-TYPES" => parse_spec_type(&element)?.map(ReqIfItem::SpecType),
+                "SPEC-OBJECTS" => {
+                    parse_spec_object(&element, &mut strict)?.map(ReqIfItem::SpecObject)
+                }
+                "SPEC-RELATIONS" => {
+                    parse_spec_relation(&element, &mut strict)?.map(ReqIfItem::SpecRelation)
+                }
+                "SPECIFICATIONS" => {
+                    parse_specification(&element, &mut strict)?.map(ReqIfItem::Specification)
+                }
+                "SPEC-RELATION-GROUPS" => {
+                    parse_relation_group(&element, &mut strict)?.map(ReqIfItem::RelationGroup)
+                }
+                _ => None,
+            };
+            if let Some(item) = item {
+                return Ok(Some(item));
+            }
+        }
+    }
+
+    /// Read the next event inside the innermost open element, returning the
+    /// item for an element that ends
+    fn next_in_specification(&mut self) -> Result<Option<ReqIfItem>, ParseError> {
+        let (start, empty) = match self.parser.next_event()? {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(_) => return self.close(),
+            Event::Eof => {
+                let name = self.open.last().map(|o| o.element.name.clone());
+                let error = IllFormedError::MissingEndTag(name.unwrap_or_default());
+                return Err(quick_xml::Error::from(error).into());
+            }
+            _ => return Ok(None),
+        };
+        let tag = self.parser.reader.get_ref().tag_start();
+        let name = local_name_of(&start);
+        let open = self.open.last_mut().expect("an element is open");
+        if !open.in_children {
+            if name == "CHILDREN" {
+                open.in_children = !empty;
+            } else {
+                self.parser.element = tag;
+                let child = if empty {
+                    XmlElement::from_start(&start)?
+                } else {
+                    self.parser.read_element(&start)?
+                };
+                open.element.children.push(XmlNode::Element(child));
+            }
+        } else if name == "SPEC-HIERARCHY" {
+            self.open.push(OpenElement::new(&start, tag)?);
+            if empty {
+                return self.close();
+            }
+        } else if !empty {
+            self.parser.skip(&start)?;
+        }
+        Ok(None)
+    }
+
+    /// Leave the innermost open element's CHILDREN, or the element itself,
+    /// returning the item it makes
+    fn close(&mut self) -> Result<Option<ReqIfItem>, ParseError> {
+        let open = self.open.last_mut().expect("an element is open");
+        if open.in_children {
+            open.in_children = false;
+            return Ok(None);
+        }
+        let open = self.open.pop().expect("an element is open");
+        self.parser.element = open.start;
+        let mut strict = Recovery {
+            lenient: false,
+            problems: Vec::new(),
+        };
+        let Some(outer) = self.open.first() else {
+            return Ok(
+                parse_specification(&open.element, &mut strict)?.map(ReqIfItem::Specification)
+            );
+        };
+        let specification = outer
+            .element
+            .attr("IDENTIFIER")
+            .unwrap_or_default()
+            .to_string();
+        let parent = self.open[1..]
+            .last()
+            .and_then(|parent| parent.element.attr("IDENTIFIER"))
+            .map(str::to_string);
+        Ok(Some(ReqIfItem::Hierarchy {
+            specification,
+            parent,
+            hierarchy: parse_hierarchy(&open.element, &mut strict)?,
+        }))
+    }
+}
+
+/// Specification or SpecHierarchy element being read by [`ItemReader`]
+struct OpenElement {
+    /// Start tag and the children read so far, leaving out CHILDREN
+    element: XmlElement,
+    /// Position of the start tag, for errors
+    start: (u64, u64),
+    /// Whether the reader is inside the element's CHILDREN
+    in_children: bool,
+}
+
+impl OpenElement {
+    fn new(start: &BytesStart, position: (u64, u64)) -> Result<Self, ParseError> {
+        Ok(OpenElement {
+            element: XmlElement::from_start(start)?,
+            start: position,
+            in_children: false,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for ItemReader<R> {
+    type Item = Result<ReqIfItem, ReqIfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_item() {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                let e = self.parser.locate(e);
+                Some(Err(match &self.file {
+                    Some(file) => e.with_file(file.clone()),
+                    None => e,
+                }))
+            }
+        }
+    }
+}
+
+/// Problems met while parsing one content item. In lenient mode they are
+/// collected and parsing carries on; otherwise the first one is returned.
+struct Recovery {
+    lenient: bool,
+    problems: Vec<ParseError>,
+}
+
+impl Recovery {
+    /// `Some` value of `result`, or `None` after noting the error in lenient
+    /// mode
+    fn recover<T>(&mut self, result: Result<T, ParseError>) -> Result<Option<T>, ParseError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self.lenient => {
+                self.problems.push(e);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn or_default<T: Default>(&mut self, result: Result<T, ParseError>) -> Result<T, ParseError> {
+        Ok(self.recover(result)?.unwrap_or_default())
+    }
+}
+
+/// Sections of REQ-IF-CONTENT that map onto `CoreContent` fields
+const CONTENT_SECTIONS: &[&str] = &[
+    "DATATYPES",
+    "SPEC-TYPES",
+    "SPEC-OBJECTS",
+    "SPEC-RELATIONS",
+    "SPECIFICATIONS",
+    "SPEC-RELATION-GROUPS",
+];
+
+fn is_content_section(name: &str) -> bool {
+    CONTENT_SECTIONS.contains(&name)
+}
+
+/// Datatype suffixes of the typed ATTRIBUTE-DEFINITION-* and
+/// ATTRIBUTE-VALUE-* elements the model supports
+const VALUE_KINDS: &[&str] = &[
+    "BOOLEAN",
+    "DATE",
+    "INTEGER",
+    "REAL",
+    "STRING",
+    "ENUMERATION",
+    "XHTML",
+];
+
+/// Reject documents declaring an encoding other than UTF-8
+fn check_encoding(decl: &BytesDecl) -> Result<(), ParseError> {
+    if let Some(encoding) = decl.encoding() {
+        let encoding = String::from_utf8_lossy(&encoding?).into_owned();
+        if !matches!(encoding.to_ascii_lowercase().as_str(), "utf-8" | "utf8") {
+            return Err(ParseError::UnsupportedEncoding(encoding));
+        }
+    }
+    Ok(())
+}
+
+fn local_name_of(start: &BytesStart) -> String {
+    local_name(&String::from_utf8_lossy(start.name().as_ref())).to_string()
+}
+
+fn required_attr(e: &XmlElement, name: &str) -> Result<String, ParseError> {
+    e.attr(name)
+        .map(str::to_string)
+        .ok_or_else(|| ParseError::Missing {
+            element: e.local_name().to_string(),
+            what: format!("attribute {}", name),
+        })
+}
+
+fn optional_attr(e: &XmlElement, name: &str) -> Option<String> {
+    e.attr(name).map(str::to_string)
+}
+
+fn required_ref(e: &XmlElement, container: &str) -> Result<String, ParseError> {
+    e.ref_in(container).ok_or_else(|| ParseError::Missing {
+        element: e.local_name().to_string(),
+        what: format!("{} reference", container),
+    })
+}
+
+fn parse_typed<T: std::str::FromStr>(
+    e: &XmlElement,
+    what: &str,
+    value: &str,
+) -> Result<T, ParseError> {
+    value.trim().parse().map_err(|_| ParseError::InvalidValue {
+        element: e.local_name().to_string(),
+        what: what.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn parse_bool(e: &XmlElement, what: &str, value: &str) -> Result<bool, ParseError> {
+    match value.trim() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(ParseError::InvalidValue {
+            element: e.local_name().to_string(),
+            what: what.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn optional_number<T: std::str::FromStr>(
+    e: &XmlElement,
+    name: &str,
+) -> Result<Option<T>, ParseError> {
+    e.attr(name).map(|v| parse_typed(e, name, v)).transpose()
+}
+
+fn optional_bool(e: &XmlElement, name: &str) -> Result<Option<bool>, ParseError> {
+    e.attr(name).map(|v| parse_bool(e, name, v)).transpose()
+}
+
+/// SpecObjects, SpecRelations and Specifications whose TYPE is a spec type of
+/// another kind. Dangling type references are left to validation.
+fn check_spec_type_kinds(content: &CoreContent) -> Vec<ParseError> {
+    let kinds: HashMap<&str, SpecTypeKind> = content
+        .spec_types
+        .iter()
+        .map(|t| (t.ident.identifier.as_str(), t.kind))
+        .collect();
+    let typed = content
+        .spec_objects
+        .iter()
+        .map(|o| {
+            (
+                &o.ident.identifier,
+                &o.spec_type,
+                SpecTypeKind::SpecObjectType,
+            )
+        })
+        .chain(content.spec_relations.iter().map(|r| {
+            (
+                &r.ident.identifier,
+                &r.spec_type,
+                SpecTypeKind::SpecRelationType,
+            )
+        }))
+        .chain(content.specifications.iter().map(|s| {
+            (
+                &s.ident.identifier,
+                &s.spec_type,
+                SpecTypeKind::SpecificationType,
+            )
+        }));
+    let mut errors = Vec::new();
+    for (owner, spec_type, expected) in typed {
+        match kinds.get(spec_type.as_str()) {
+            Some(&found) if found != expected => errors.push(ParseError::WrongSpecTypeKind {
+                owner: owner.clone(),
+                spec_type: spec_type.clone(),
+                expected,
+                found,
+            }),
+            _ => {}
+        }
+    }
+    errors
+}
+
+/// Enumeration values with several entries whose attribute definition is not
+/// MULTI-VALUED
+fn check_enum_value_counts(content: &CoreContent) -> Vec<ParseError> {
+    let definitions: HashMap<&str, &AttributeDefinition> = content
+        .spec_types
+        .iter()
+        .flat_map(|t| &t.spec_attributes)
+        .map(|d| (d.ident.identifier.as_str(), d))
+        .collect();
+    let owners = content
+        .spec_objects
+        .iter()
+        .map(|o| (&o.ident.identifier, &o.values))
+        .chain(
+            content
+                .spec_relations
+                .iter()
+                .map(|r| (&r.ident.identifier, &r.values)),
+        )
+        .chain(
+            content
+                .specifications
+                .iter()
+                .map(|s| (&s.ident.identifier, &s.values)),
+        );
+    let mut errors = Vec::new();
+    for (owner, values) in owners {
+        for value in values {
+            let AttributeValue::Enumeration {
+                definition, values, ..
+            } = value
+            else {
+                continue;
+            };
+            if let Some(def) = definitions.get(definition.as_str()) {
+                if !def.accepts_enum_value_count(values.len()) {
+                    errors.push(ParseError::TooManyEnumValues {
+                        owner: owner.clone(),
+                        definition: definition.clone(),
+                        count: values.len(),
+                    });
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Collect the attributes and child elements of `e` that the model does not
+/// cover, so they can be written back unchanged
+fn extras(e: &XmlElement, known_attrs: &[&str], known_children: &[&str]) -> Extras {
+    Extras {
+        attributes: e
+            .attributes
+            .iter()
+            .filter(|(key, _)| !known_attrs.contains(&key.as_str()))
+            .cloned()
+            .collect(),
+        elements: e
+            .elements()
+            .filter(|c| !known_children.contains(&c.local_name()))
+            .cloned()
+            .collect(),
+        layout: Some(Layout {
+            attributes: e.attributes.iter().map(|(key, _)| key.clone()).collect(),
+            children: e
+                .elements()
+                .map(|c| Some(c.local_name()).filter(|name| known_children.contains(name)))
+                .map(|name| name.map(str::to_string))
+                .collect(),
+            items: Vec::new(),
+        }),
+    }
+}
+
+/// Record the next child of the element owning `extras`: a known child by
+/// name, or `None` for an unknown one
+fn note_child(extras: &mut Extras, name: Option<&str>) {
+    if let Some(layout) = &mut extras.layout {
+        layout.children.push(name.map(str::to_string));
+    }
+}
+
+/// Attributes every ReqIF Identifiable element carries
+const IDENTIFIABLE_ATTRS: &[&str] = &["IDENTIFIER", "LONG-NAME", "DESC", "LAST-CHANGE"];
+
+fn parse_identifiable(e: &XmlElement) -> Result<Identifiable, ParseError> {
+    Ok(Identifiable {
+        identifier: required_attr(e, "IDENTIFIER")?,
+        long_name: optional_attr(e, "LONG-NAME"),
+        desc: optional_attr(e, "DESC"),
+        last_change: optional_attr(e, "LAST-CHANGE"),
+    })
+}
+
+/// `extras` for an Identifiable element, whose IDENTIFIABLE_ATTRS are always
+/// known
+fn identifiable_extras(e: &XmlElement, known_attrs: &[&str], known_children: &[&str]) -> Extras {
+    let known: Vec<&str> = IDENTIFIABLE_ATTRS
+        .iter()
+        .chain(known_attrs)
+        .copied()
+        .collect();
+    extras(e, &known, known_children)
+}
+
+fn parse_header(e: &XmlElement) -> Result<ReqIFHeader, ParseError> {
+    let text = |name: &str| e.child(name).map(|c| c.text());
+    Ok(ReqIFHeader {
+        identifier: required_attr(e, "IDENTIFIER")?,
+        creation_time: text("CREATION-TIME").unwrap_or_default(),
+        source_tool_id: text("SOURCE-TOOL-ID").unwrap_or_default(),
+        reqif_tool_id: text("REQ-IF-TOOL-ID").unwrap_or_default(),
+        reqif_version: text("REQ-IF-VERSION").unwrap_or_default(),
+        repository_id: text("REPOSITORY-ID"),
+        title: text("TITLE"),
+        comment: text("COMMENT"),
+        extras: extras(
+            e,
+            &["IDENTIFIER"],
+            &[
+                "COMMENT",
+                "CREATION-TIME",
+                "REPOSITORY-ID",
+                "REQ-IF-TOOL-ID",
+                "REQ-IF-VERSION",
+                "SOURCE-TOOL-ID",
+                "TITLE",
+            ],
+        ),
+    })
+}
+
+fn parse_datatype(e: &XmlElement) -> Result<Option<DatatypeDefinition>, ParseError> {
+    let Some(kind) = e.local_name().strip_prefix("DATATYPE-DEFINITION-") else {
+        return Ok(None);
+    };
+    if !VALUE_KINDS.contains(&kind) {
+        return Ok(None);
+    }
+    let ident = parse_identifiable(e)?;
+    let known_attrs: &[&str] = match kind {
+        "INTEGER" => &["MIN", "MAX"],
+        "REAL" => &["MIN", "MAX", "ACCURACY"],
+        "STRING" => &["MAX-LENGTH"],
+        _ => &[],
+    };
+    let mut extras = identifiable_extras(e, known_attrs, &["SPECIFIED-VALUES"]);
+    Ok(Some(match kind {
+        "BOOLEAN" => DatatypeDefinition::Boolean { ident, extras },
+        "DATE" => DatatypeDefinition::Date { ident, extras },
+        "INTEGER" => DatatypeDefinition::Integer {
+            ident,
+            min: optional_number(e, "MIN")?,
+            max: optional_number(e, "MAX")?,
+            extras,
+        },
+        "REAL" => DatatypeDefinition::Real {
+            ident,
+            min: optional_number(e, "MIN")?,
+            max: optional_number(e, "MAX")?,
+            accuracy: optional_number(e, "ACCURACY")?,
+            extras,
+        },
+        "STRING" => DatatypeDefinition::String {
+            ident,
+            max_length: optional_number(e, "MAX-LENGTH")?,
+            extras,
+        },
+        "ENUMERATION" => {
+            let mut values = Vec::new();
+            let mut unknown = Vec::new();
+            if let Some(specified) = e.child("SPECIFIED-VALUES") {
+                for value in specified.elements() {
+                    if value.local_name() == "ENUM-VALUE" {
+                        values.push(parse_enum_value(value)?);
+                    } else {
+                        unknown.push((values.len(), value.clone()));
+                    }
+                }
+            }
+            extras.push_container_items("SPECIFIED-VALUES", unknown);
+            DatatypeDefinition::Enumeration {
+                ident,
+                values,
+                extras,
+            }
+        }
+        _ => DatatypeDefinition::XHTML { ident, extras },
+    }))
+}
+
+fn parse_enum_value(e: &XmlElement) -> Result<EnumValue, ParseError> {
+    let embedded = e
+        .child("PROPERTIES")
+        .and_then(|p| p.child("EMBEDDED-VALUE"));
+    Ok(EnumValue {
+        ident: parse_identifiable(e)?,
+        properties: embedded.and_then(|v| optional_attr(v, "KEY")),
+        other_content: embedded.and_then(|v| optional_attr(v, "OTHER-CONTENT")),
+        extras: identifiable_extras(e, &[], &["PROPERTIES"]),
+    })
+}
+
+fn parse_spec_type(e: &XmlElement) -> Result<Option<SpecType>, ParseError> {
+    let Some(kind) = SpecTypeKind::from_element_name(e.local_name()) else {
+        return Ok(None);
+    };
+    let mut extras = identifiable_extras(e, &[], &["SPEC-ATTRIBUTES"]);
+    let mut spec_attributes = Vec::new();
+    let mut unknown = Vec::new();
+    if let Some(attrs) = e.child("SPEC-ATTRIBUTES") {
+        for attr in attrs.elements() {
+            match parse_attribute_definition(attr)? {
+                Some(definition) => spec_attributes.push(definition),
+                None => unknown.push((spec_attributes.len(), attr.clone())),
+            }
+        }
+    }
+    extras.push_container_items("SPEC-ATTRIBUTES", unknown);
+    Ok(Some(SpecType {
+        ident: parse_identifiable(e)?,
+        kind,
+        spec_attributes,
+        extras,
+    }))
+}
+
+fn parse_attribute_definition(e: &XmlElement) -> Result<Option<AttributeDefinition>, ParseError> {
+    match e.local_name().strip_prefix("ATTRIBUTE-DEFINITION-") {
+        Some(kind) if VALUE_KINDS.contains(&kind) => {}
+        _ => return Ok(None),
+    }
+    // A DEFAULT-VALUE of a kind the model does not know stays in extras
+    let default_value = match e.child("DEFAULT-VALUE").and_then(|d| d.elements().next()) {
+        Some(value) => parse_attribute_value(value)?,
+        None => None,
+    };
+    let known_children: &[&str] = match default_value {
+        Some(_) => &["TYPE", "DEFAULT-VALUE"],
+        None => &["TYPE"],
+    };
+    Ok(Some(AttributeDefinition {
+        ident: parse_identifiable(e)?,
+        datatype_ref: required_ref(e, "TYPE")?,
+        multi_valued: optional_bool(e, "MULTI-VALUED")?.unwrap_or(false),
+        is_editable: optional_bool(e, "IS-EDITABLE")?,
+        default_value,
+        extras: identifiable_extras(e, &["MULTI-VALUED", "IS-EDITABLE"], known_children),
+    }))
+}
+
+/// Parse the VALUES container of `e`; value kinds the model does not know,
+/// and in lenient mode values that cannot be read, are kept in `extras`
+fn parse_values(
+    e: &XmlElement,
+    extras: &mut Extras,
+    recovery: &mut Recovery,
+) -> Result<Vec<AttributeValue>, ParseError> {
+    let mut values = Vec::new();
+    let mut unknown = Vec::new();
+    if let Some(container) = e.child("VALUES") {
+        for element in container.elements() {
+            match recovery.recover(parse_attribute_value(element))?.flatten() {
+                Some(value) => values.push(value),
+                None => unknown.push((values.len(), element.clone())),
+            }
+        }
+    }
+    extras.push_container_items("VALUES", unknown);
+    Ok(values)
+}
+
+/// Parse one ATTRIBUTE-VALUE-* element, or `None` for kinds the model does
+/// not know
+fn parse_attribute_value(e: &XmlElement) -> Result<Option<AttributeValue>, ParseError> {
+    let definition = || required_ref(e, "DEFINITION");
+    let the_value = || required_attr(e, "THE-VALUE");
+    let extras = || extras(e, &["THE-VALUE"], &["DEFINITION", "VALUES", "THE-VALUE"]);
+    Ok(Some(match e.local_name() {
+        "ATTRIBUTE-VALUE-BOOLEAN" => AttributeValue::Boolean {
+            definition: definition()?,
+            value: parse_bool(e, "THE-VALUE", &the_value()?)?,
+            extras: extras(),
+        },
+        "ATTRIBUTE-VALUE-DATE" => AttributeValue::Date {
+            definition: definition()?,
+            value: parse_typed(e, "THE-VALUE", &the_value()?)?,
+            extras: extras(),
+        },
+        "ATTRIBUTE-VALUE-INTEGER" => AttributeValue::Integer {
+            definition: definition()?,
+            value: parse_typed(e, "THE-VALUE", &the_value()?)?,
+            extras: extras(),
+        },
+        "ATTRIBUTE-VALUE-REAL" => AttributeValue::Real {
+            definition: definition()?,
+            value: parse_typed(e, "THE-VALUE", &the_value()?)?,
+            extras: extras(),
+        },
+        "ATTRIBUTE-VALUE-STRING" => AttributeValue::String {
+            definition: definition()?,
+            value: the_value()?,
+            extras: extras(),
+        },
+        "ATTRIBUTE-VALUE-ENUMERATION" => AttributeValue::Enumeration {
+            definition: definition()?,
+            values: e
+                .child("VALUES")
+                .map(|v| {
+                    v.elements()
+                        .filter(|r| r.local_name() == "ENUM-VALUE-REF")
+                        .map(XmlElement::text)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            extras: extras(),
+        },
+        "ATTRIBUTE-VALUE-XHTML" => AttributeValue::XHTML {
+            definition: definition()?,
+            value: e
+                .child("THE-VALUE")
+                .map(|v| v.inner_xml())
+                .unwrap_or_default(),
+            extras: extras(),
+        },
+        _ => return Ok(None),
+    }))
+}
+
+fn parse_spec_object(
+    e: &XmlElement,
+    recovery: &mut Recovery,
+) -> Result<Option<SpecObject>, ParseError> {
+    if e.local_name() != "SPEC-OBJECT" {
+        return Ok(None);
+    }
+    let mut extras = identifiable_extras(e, &[], &["TYPE", "VALUES"]);
+    Ok(Some(SpecObject {
+        ident: parse_identifiable(e)?,
+        spec_type: recovery.or_default(required_ref(e, "TYPE"))?,
+        values: parse_values(e, &mut extras, recovery)?,
+        extras,
+    }))
+}
+
+fn parse_spec_relation(
+    e: &XmlElement,
+    recovery: &mut Recovery,
+) -> Result<Option<SpecRelation>, ParseError> {
+    if e.local_name() != "SPEC-RELATION" {
+        return Ok(None);
+    }
+    let mut extras = identifiable_extras(e, &[], &["TYPE", "VALUES", "SOURCE", "TARGET"]);
+    Ok(Some(SpecRelation {
+        ident: parse_identifiable(e)?,
+        spec_type: recovery.or_default(required_ref(e, "TYPE"))?,
+        source: required_ref(e, "SOURCE")?,
+        target: required_ref(e, "TARGET")?,
+        values: parse_values(e, &mut extras, recovery)?,
+        extras,
+    }))
+}
+
+fn parse_specification(
+    e: &XmlElement,
+    recovery: &mut Recovery,
+) -> Result<Option<Specification>, ParseError> {
+    if e.local_name() != "SPECIFICATION" {
+        return Ok(None);
+    }
+    let mut extras = identifiable_extras(e, &[], &["TYPE", "VALUES", "CHILDREN"]);
+    Ok(Some(Specification {
+        ident: parse_identifiable(e)?,
+        spec_type: recovery.or_default(required_ref(e, "TYPE"))?,
+        values: parse_values(e, &mut extras, recovery)?,
+        children: parse_children(e, &mut extras, recovery)?,
+        extras,
+    }))
+}
+
+fn parse_relation_group(
+    e: &XmlElement,
+    recovery: &mut Recovery,
+) -> Result<Option<RelationGroup>, ParseError> {
+    if e.local_name() != "RELATION-GROUP" {
+        return Ok(None);
+    }
+    Ok(Some(RelationGroup {
+        ident: parse_identifiable(e)?,
+        spec_type: recovery.or_default(required_ref(e, "TYPE"))?,
+        source_specification: required_ref(e, "SOURCE-SPECIFICATION")?,
+        target_specification: required_ref(e, "TARGET-SPECIFICATION")?,
+        spec_relations: e
+            .child("SPEC-RELATIONS")
+            .map(|c| {
+                c.elements()
+                    .filter(|r| r.local_name() == "SPEC-RELATION-REF")
+                    .map(XmlElement::text)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        extras: identifiable_extras(
+            e,
+            &[],
+            &[
+                "TYPE",
+                "SOURCE-SPECIFICATION",
+                "TARGET-SPECIFICATION",
+                "SPEC-RELATIONS",
+            ],
+        ),
+    }))
+}
+
+fn parse_children(
+    e: &XmlElement,
+    extras: &mut Extras,
+    recovery: &mut Recovery,
+) -> Result<Vec<SpecHierarchy>, ParseError> {
+    let mut children = Vec::new();
+    let mut unknown = Vec::new();
+    if let Some(container) = e.child("CHILDREN") {
+        for child in container.elements() {
+            if child.local_name() != "SPEC-HIERARCHY" {
+                unknown.push((children.len(), child.clone()));
+                continue;
+            }
+            let hierarchy = parse_hierarchy(child, recovery);
+            match recovery.recover(hierarchy)? {
+                Some(hierarchy) => children.push(hierarchy),
+                None => unknown.push((children.len(), child.clone())),
+            }
+        }
+    }
+    extras.push_container_items("CHILDREN", unknown);
+    Ok(children)
+}
+
+fn parse_hierarchy(e: &XmlElement, recovery: &mut Recovery) -> Result<SpecHierarchy, ParseError> {
+    let mut extras = identifiable_extras(
+        e,
+        &["IS-EDITABLE", "IS-TABLE-INTERNAL"],
+        &["OBJECT", "CHILDREN"],
+    );
+    Ok(SpecHierarchy {
+        ident: parse_identifiable(e)?,
+        object: required_ref(e, "OBJECT")?,
+        is_editable: optional_bool(e, "IS-EDITABLE")?,
+        is_table_internal: optional_bool(e, "IS-TABLE-INTERNAL")?,
+        children: parse_children(e, &mut extras, recovery)?,
+        extras,
+    })
+}
+
+fn parse_tool_extension(e: &XmlElement) -> ToolExtension {
+    // The content is kept as markup, so only the attributes are extras
+    let tag = XmlElement {
+        name: e.name.clone(),
+        attributes: e.attributes.clone(),
+        children: Vec::new(),
+    };
+    ToolExtension {
+        content: e.inner_xml(),
+        extras: extras(&tag, &[], &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    #[test]
+    fn test_parse_small_fixture() {
+        let reqif = parse_str(SMALL).unwrap();
+        assert_eq!(reqif.header.identifier, "reqif-test-001");
+        assert_eq!(reqif.header.creation_time, "2025-11-21T00:00:00Z");
+        assert_eq!(reqif.header.title.as_deref(), Some("Small Test ReqIF File"));
+        assert_eq!(reqif.header.reqif_tool_id, "ReqSmith");
+        assert_eq!(reqif.header.reqif_version, "1.2");
+        assert_eq!(reqif.header.repository_id, None);
+
+        let content = &reqif.core_content;
+        assert_eq!(content.datatype_definitions.len(), 3);
+        assert_eq!(content.spec_types.len(), 2);
+        assert_eq!(content.spec_types[0].kind, SpecTypeKind::SpecObjectType);
+        assert_eq!(content.spec_types[1].kind, SpecTypeKind::SpecificationType);
+        assert_eq!(
+            content.spec_types[0].spec_attributes[0]
+                .ident
+                .long_name
+                .as_deref(),
+            Some("Title")
+        );
+        assert_eq!(content.spec_objects.len(), 3);
+        assert_eq!(content.specifications.len(), 1);
+        assert_eq!(content.specifications[0].children.len(), 3);
+        assert_eq!(content.specifications[0].children[1].object, "req-002");
+
+        let req = &content.spec_objects[0];
+        assert_eq!(req.spec_type, "sot-requirement");
+        assert_eq!(req.values.len(), 3);
+        match &req.values[1] {
+            AttributeValue::XHTML {
+                definition, value, ..
+            } => {
+                assert_eq!(definition, "ad-description");
+                assert!(value.starts_with("<xhtml:div>The system shall initialize"));
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+        assert!(matches!(
+            req.values[2],
+            AttributeValue::Integer { value: 10, .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_nested_hierarchy_and_relations() {
+        let xml = r#"<REQ-IF xmlns="http://www.omg.org/spec/ReqIF/20110401/reqif.xsd">
+  <THE-HEADER><REQ-IF-HEADER IDENTIFIER="h"/></THE-HEADER>
+  <CORE-CONTENT><REQ-IF-CONTENT>
+    <DATATYPES>
+      <DATATYPE-DEFINITION-ENUMERATION IDENTIFIER="dt-enum">
+        <SPECIFIED-VALUES>
+          <ENUM-VALUE IDENTIFIER="ev-high" LONG-NAME="High">
+            <PROPERTIES><EMBEDDED-VALUE KEY="1" OTHER-CONTENT=""/></PROPERTIES>
+          </ENUM-VALUE>
+        </SPECIFIED-VALUES>
+      </DATATYPE-DEFINITION-ENUMERATION>
+    </DATATYPES>
+    <SPEC-RELATIONS>
+      <SPEC-RELATION IDENTIFIER="rel-1">
+        <TYPE><SPEC-RELATION-TYPE-REF>srt</SPEC-RELATION-TYPE-REF></TYPE>
+        <SOURCE><SPEC-OBJECT-REF>a</SPEC-OBJECT-REF></SOURCE>
+        <TARGET><SPEC-OBJECT-REF>b</SPEC-OBJECT-REF></TARGET>
+      </SPEC-RELATION>
+    </SPEC-RELATIONS>
+    <SPECIFICATIONS>
+      <SPECIFICATION IDENTIFIER="s">
+        <TYPE><SPECIFICATION-TYPE-REF>st</SPECIFICATION-TYPE-REF></TYPE>
+        <CHILDREN>
+          <SPEC-HIERARCHY IDENTIFIER="h1" LONG-NAME="Section 1" DESC="Start-up">
+            <OBJECT><SPEC-OBJECT-REF>a</SPEC-OBJECT-REF></OBJECT>
+            <CHILDREN>
+              <SPEC-HIERARCHY IDENTIFIER="h2">
+                <OBJECT><SPEC-OBJECT-REF>b</SPEC-OBJECT-REF></OBJECT>
+              </SPEC-HIERARCHY>
+            </CHILDREN>
+          </SPEC-HIERARCHY>
+        </CHILDREN>
+      </SPECIFICATION>
+    </SPECIFICATIONS>
+  </REQ-IF-CONTENT></CORE-CONTENT>
+</REQ-IF>"#;
+        let reqif = parse_str(xml).unwrap();
+        let content = &reqif.core_content;
+        match &content.datatype_definitions[0] {
+            DatatypeDefinition::Enumeration { values, .. } => {
+                assert_eq!(values[0].ident.long_name.as_deref(), Some("High"));
+                assert_eq!(values[0].properties.as_deref(), Some("1"));
+            }
+            other => panic!("unexpected datatype {:?}", other),
+        }
+        assert_eq!(content.spec_relations[0].source, "a");
+        assert_eq!(content.spec_relations[0].target, "b");
+        let root = &content.specifications[0].children[0];
+        assert_eq!(root.ident.long_name.as_deref(), Some("Section 1"));
+        assert_eq!(root.ident.desc.as_deref(), Some("Start-up"));
+        assert!(root.extras.is_empty());
+        assert_eq!(root.children[0].ident.identifier, "h2");
+        assert_eq!(root.children[0].object, "b");
+    }
+
+    #[test]
+    fn test_parse_date_values() {
+        let xml = r#"<REQ-IF><THE-HEADER><REQ-IF-HEADER IDENTIFIER="h"/></THE-HEADER>
+  <CORE-CONTENT><REQ-IF-CONTENT>
+    <DATATYPES><DATATYPE-DEFINITION-DATE IDENTIFIER="dt-date" LONG-NAME="Date"/></DATATYPES>
+    <SPEC-OBJECTS>
+      <SPEC-OBJECT IDENTIFIER="a">
+        <TYPE><SPEC-OBJECT-TYPE-REF>sot</SPEC-OBJECT-TYPE-REF></TYPE>
+        <VALUES>
+          <ATTRIBUTE-VALUE-DATE THE-VALUE="2025-03-01T12:00:00+01:00">
+            <DEFINITION><ATTRIBUTE-DEFINITION-DATE-REF>ad-date</ATTRIBUTE-DEFINITION-DATE-REF></DEFINITION>
+          </ATTRIBUTE-VALUE-DATE>
+        </VALUES>
+      </SPEC-OBJECT>
+    </SPEC-OBJECTS>
+  </REQ-IF-CONTENT></CORE-CONTENT></REQ-IF>"#;
+        let reqif = parse_str(xml).unwrap();
+        assert!(matches!(
+            reqif.core_content.datatype_definitions[0],
+            DatatypeDefinition::Date { .. }
+        ));
+        match &reqif.core_content.spec_objects[0].values[0] {
+            AttributeValue::Date { value, .. } => {
+                assert_eq!(value.to_string(), "2025-03-01T12:00:00+01:00")
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+
+        let invalid = xml.replace("2025-03-01T12:00:00+01:00", "tomorrow");
+        assert!(matches!(
+            parse_str(&invalid),
+            Err(ReqIfError::Schema {
+                source: ParseError::InvalidValue { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_multi_valued_enumeration() {
+        let xml = r#"<REQ-IF><THE-HEADER><REQ-IF-HEADER IDENTIFIER="h"/></THE-HEADER>
+  <CORE-CONTENT><REQ-IF-CONTENT>
+    <SPEC-TYPES>
+      <SPEC-OBJECT-TYPE IDENTIFIER="sot">
+        <SPEC-ATTRIBUTES>
+          <ATTRIBUTE-DEFINITION-ENUMERATION IDENTIFIER="ad-tags" MULTI-VALUED="true">
+            <TYPE><DATATYPE-DEFINITION-ENUMERATION-REF>dt-enum</DATATYPE-DEFINITION-ENUMERATION-REF></TYPE>
+          </ATTRIBUTE-DEFINITION-ENUMERATION>
+        </SPEC-ATTRIBUTES>
+      </SPEC-OBJECT-TYPE>
+    </SPEC-TYPES>
+    <SPEC-OBJECTS>
+      <SPEC-OBJECT IDENTIFIER="a">
+        <TYPE><SPEC-OBJECT-TYPE-REF>sot</SPEC-OBJECT-TYPE-REF></TYPE>
+        <VALUES>
+          <ATTRIBUTE-VALUE-ENUMERATION>
+            <DEFINITION><ATTRIBUTE-DEFINITION-ENUMERATION-REF>ad-tags</ATTRIBUTE-DEFINITION-ENUMERATION-REF></DEFINITION>
+            <VALUES>
+              <ENUM-VALUE-REF>ev-safety</ENUM-VALUE-REF>
+              <ENUM-VALUE-REF>ev-security</ENUM-VALUE-REF>
+            </VALUES>
+          </ATTRIBUTE-VALUE-ENUMERATION>
+        </VALUES>
+      </SPEC-OBJECT>
+    </SPEC-OBJECTS>
+  </REQ-IF-CONTENT></CORE-CONTENT></REQ-IF>"#;
+        let reqif = parse_str(xml).unwrap();
+        assert!(reqif.core_content.spec_types[0].spec_attributes[0].multi_valued);
+        match &reqif.core_content.spec_objects[0].values[0] {
+            AttributeValue::Enumeration { values, .. } => {
+                assert_eq!(values, &["ev-safety", "ev-security"])
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+
+        let single = xml.replace(r#" MULTI-VALUED="true""#, "");
+        assert!(matches!(
+            parse_str(&single),
+            Err(ReqIfError::Schema {
+                source: ParseError::TooManyEnumValues { count: 2, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_default_value_and_editable_flags() {
+        let xml = SMALL
+            .replace(
+                r#"<ATTRIBUTE-DEFINITION-INTEGER IDENTIFIER="ad-priority" LONG-NAME="Priority">"#,
+                r#"<ATTRIBUTE-DEFINITION-INTEGER IDENTIFIER="ad-priority" LONG-NAME="Priority" IS-EDITABLE="false">
+              <DEFAULT-VALUE>
+                <ATTRIBUTE-VALUE-INTEGER THE-VALUE="3">
+                  <DEFINITION><ATTRIBUTE-DEFINITION-INTEGER-REF>ad-priority</ATTRIBUTE-DEFINITION-INTEGER-REF></DEFINITION>
+                </ATTRIBUTE-VALUE-INTEGER>
+              </DEFAULT-VALUE>"#,
+            )
+            .replace(
+                r#"<SPEC-HIERARCHY IDENTIFIER="sh-001">"#,
+                r#"<SPEC-HIERARCHY IDENTIFIER="sh-001" IS-EDITABLE="true" IS-TABLE-INTERNAL="false">"#,
+            );
+        let mut reqif = parse_str(&xml).unwrap();
+        let content = &mut reqif.core_content;
+        let priority = &content.spec_types[0].spec_attributes[2];
+        assert_eq!(priority.is_editable, Some(false));
+        assert!(!priority.editable());
+        assert!(priority.extras.is_empty());
+        let hierarchy = &content.specifications[0].children[0];
+        assert_eq!(hierarchy.is_editable, Some(true));
+        assert_eq!(hierarchy.is_table_internal, Some(false));
+
+        content.spec_objects[2].values.pop();
+        let object = &content.spec_objects[2];
+        assert!(matches!(
+            content.value_or_default(object, "ad-priority"),
+            Some(AttributeValue::Integer { value: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_relation_groups() {
+        let xml = r#"<REQ-IF><THE-HEADER><REQ-IF-HEADER IDENTIFIER="h"/></THE-HEADER>
+  <CORE-CONTENT><REQ-IF-CONTENT>
+    <SPEC-TYPES>
+      <RELATION-GROUP-TYPE IDENTIFIER="rgt"/>
+    </SPEC-TYPES>
+    <SPEC-RELATIONS>
+      <SPEC-RELATION IDENTIFIER="r1">
+        <TYPE><SPEC-RELATION-TYPE-REF>srt</SPEC-RELATION-TYPE-REF></TYPE>
+        <SOURCE><SPEC-OBJECT-REF>sys-1</SPEC-OBJECT-REF></SOURCE>
+        <TARGET><SPEC-OBJECT-REF>sw-1</SPEC-OBJECT-REF></TARGET>
+      </SPEC-RELATION>
+      <SPEC-RELATION IDENTIFIER="r2">
+        <TYPE><SPEC-RELATION-TYPE-REF>srt</SPEC-RELATION-TYPE-REF></TYPE>
+        <SOURCE><SPEC-OBJECT-REF>sys-2</SPEC-OBJECT-REF></SOURCE>
+        <TARGET><SPEC-OBJECT-REF>sw-1</SPEC-OBJECT-REF></TARGET>
+      </SPEC-RELATION>
+    </SPEC-RELATIONS>
+    <SPEC-RELATION-GROUPS>
+      <RELATION-GROUP IDENTIFIER="rg1">
+        <SOURCE-SPECIFICATION><SPECIFICATION-REF>sys</SPECIFICATION-REF></SOURCE-SPECIFICATION>
+        <SPEC-RELATIONS>
+          <SPEC-RELATION-REF>r2</SPEC-RELATION-REF>
+          <SPEC-RELATION-REF>r1</SPEC-RELATION-REF>
+        </SPEC-RELATIONS>
+        <TARGET-SPECIFICATION><SPECIFICATION-REF>sw</SPECIFICATION-REF></TARGET-SPECIFICATION>
+        <TYPE><RELATION-GROUP-TYPE-REF>rgt</RELATION-GROUP-TYPE-REF></TYPE>
+      </RELATION-GROUP>
+    </SPEC-RELATION-GROUPS>
+  </REQ-IF-CONTENT></CORE-CONTENT></REQ-IF>"#;
+        let reqif = parse_str(xml).unwrap();
+        let content = &reqif.core_content;
+        assert_eq!(content.spec_types[0].kind, SpecTypeKind::RelationGroupType);
+        let group = &content.spec_relation_groups[0];
+        assert_eq!(group.source_specification, "sys");
+        assert_eq!(group.target_specification, "sw");
+        assert_eq!(group.spec_relations, ["r2", "r1"]);
+
+        let links: Vec<_> = content
+            .links_between("sw", "sys")
+            .iter()
+            .map(|r| r.ident.identifier.as_str())
+            .collect();
+        assert_eq!(links, ["r1", "r2"]);
+        assert!(content.links_between("sys", "other").is_empty());
+    }
+
+    #[test]
+    fn test_spec_type_of_wrong_kind_is_an_error() {
+        let xml = SMALL.replace(
+            "<SPEC-OBJECT-TYPE-REF>sot-requirement</SPEC-OBJECT-TYPE-REF>",
+            "<SPEC-OBJECT-TYPE-REF>st-specification</SPEC-OBJECT-TYPE-REF>",
+        );
+        match parse_str(&xml) {
+            Err(ReqIfError::UnresolvedReference {
+                source:
+                    ParseError::WrongSpecTypeKind {
+                        owner,
+                        expected,
+                        found,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(owner, "req-001");
+                assert_eq!(expected, SpecTypeKind::SpecObjectType);
+                assert_eq!(found, SpecTypeKind::SpecificationType);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_type_reference_is_an_error() {
+        let xml = r#"<REQ-IF><THE-HEADER><REQ-IF-HEADER IDENTIFIER="h"/></THE-HEADER>
+  <CORE-CONTENT><REQ-IF-CONTENT><SPEC-OBJECTS>
+    <SPEC-OBJECT IDENTIFIER="a"/>
+  </SPEC-OBJECTS></REQ-IF-CONTENT></CORE-CONTENT></REQ-IF>"#;
+        match parse_str(xml) {
+            Err(ReqIfError::Schema {
+                location,
+                source: ParseError::Missing { element, .. },
+            }) => {
+                assert_eq!(element, "SPEC-OBJECT");
+                assert_eq!(location, SourceLocation::new(3, 5));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lenient_mode_recovers_broken_items() {
+        let xml = SMALL
+            .replacen(
+                "<TYPE><SPEC-OBJECT-TYPE-REF>sot-requirement</SPEC-OBJECT-TYPE-REF></TYPE>",
+                "stray text",
+                1,
+            )
+            .replace(r#"THE-VALUE="9""#, r#"THE-VALUE="nine""#);
+        assert!(parse_str(&xml).is_err());
+
+        let Recovered { reqif, problems } = parse_str_lenient(&xml).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert!(problems.iter().all(|p| matches!(
+            p,
+            ReqIfError::Schema {
+                source: ParseError::Missing { .. } | ParseError::InvalidValue { .. },
+                ..
+            }
+        )));
+        let line_of = |id: &str| {
+            SMALL
+                .lines()
+                .position(|l| l.contains(&format!(r#"<SPEC-OBJECT IDENTIFIER="{}""#, id)))
+                .unwrap() as u64
+                + 1
+        };
+        assert_eq!(problems[0].location().line, line_of("req-001"));
+        assert_eq!(problems[1].location().line, line_of("req-002"));
+
+        // Both objects are kept; the unreadable value survives a save
+        let objects = &reqif.core_content.spec_objects;
+        assert_eq!(objects.len(), 3);
+        assert_eq!(objects[0].spec_type, "");
+        assert_eq!(objects[1].values.len(), 2);
+        let saved = crate::reqif::serializer::to_string(&reqif).unwrap();
+        assert!(saved.contains(r#"THE-VALUE="nine""#));
+        assert_eq!(parse_str_lenient(&saved).unwrap().problems.len(), 2);
+    }
+
+    #[test]
+    fn test_item_reader_yields_items_in_document_order() {
+        let reqif = parse_str(SMALL).unwrap();
+        let items: Vec<ReqIfItem> = items_from_reader(SMALL.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(
+            matches!(&items[0], ReqIfItem::Header(h) if h.identifier == reqif.header.identifier)
+        );
+        let objects: Vec<&str> = items
+            .iter()
+            .filter_map(|item| match item {
+                ReqIfItem::SpecObject(o) => Some(o.ident.identifier.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(objects, ["req-001", "req-002", "req-003"]);
+        let count = |f: fn(&ReqIfItem) -> bool| items.iter().filter(|i| f(i)).count();
+        let content = &reqif.core_content;
+        assert_eq!(
+            count(|i| matches!(i, ReqIfItem::Datatype(_))),
+            content.datatype_definitions.len()
+        );
+        assert_eq!(
+            count(|i| matches!(i, ReqIfItem::SpecType(_))),
+            content.spec_types.len()
+        );
+        assert_eq!(
+            count(|i| matches!(i, ReqIfItem::Specification(_))),
+            content.specifications.len()
+        );
+
+        // Hierarchy entries come one at a time, each once its element ends
+        let nested = SMALL.replace(
+            r#"<SPEC-HIERARCHY IDENTIFIER="sh-002">"#,
+            r#"<SPEC-HIERARCHY IDENTIFIER="sh-002"><CHILDREN>
+                <SPEC-HIERARCHY IDENTIFIER="sh-004">
+                  <OBJECT><SPEC-OBJECT-REF>req-003</SPEC-OBJECT-REF></OBJECT>
+                </SPEC-HIERARCHY>
+              </CHILDREN>"#,
+        );
+        let items: Vec<ReqIfItem> = items_from_reader(nested.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let entries: Vec<(&str, Option<&str>, &str)> = items
+            .iter()
+            .filter_map(|item| match item {
+                ReqIfItem::Hierarchy {
+                    specification,
+                    parent,
+                    hierarchy,
+                } => {
+                    assert_eq!(specification, "spec-001");
+                    assert!(hierarchy.children.is_empty());
+                    Some((
+                        hierarchy.ident.identifier.as_str(),
+                        parent.as_deref(),
+                        hierarchy.object.as_str(),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("sh-001", None, "req-001"),
+                ("sh-004", Some("sh-002"), "req-003"),
+                ("sh-002", None, "req-002"),
+                ("sh-003", None, "req-003")
+            ]
+        );
+        assert!(matches!(
+            items.last(),
+            Some(ReqIfItem::Specification(s)) if s.children.is_empty() && s.values.len() == 1
+        ));
+
+        // Items before a broken one are still delivered, then iteration stops
+        let broken = SMALL.replace(r#"THE-VALUE="9""#, r#"THE-VALUE="nine""#);
+        let results: Vec<_> = items_from_reader(broken.as_bytes()).collect();
+        let error = results.last().unwrap().as_ref().unwrap_err();
+        assert!(matches!(error, ReqIfError::Schema { .. }));
+        assert!(results.iter().rev().skip(1).all(Result::is_ok));
+        assert!(results.iter().any(|r| matches!(
+            r,
+            Ok(ReqIfItem::SpecObject(o)) if o.ident.identifier == "req-001"
+        )));
+    }
+
+    #[test]
+    fn test_errors_carry_file_line_and_column() {
+        let malformed =
+            "<REQ-IF>\n  <THE-HEADER>\n    <REQ-IF-HEADER IDENTIFIER=\"h\">\n  </THE-HEADER>";
+        let error = parse_str(malformed).unwrap_err();
+        assert!(matches!(error, ReqIfError::MalformedXml { .. }));
+        assert_eq!(error.location().line, 4);
+
+        // Errors found after reading point at the offending element
+        let xml = SMALL.replace(
+            "<SPEC-OBJECT-TYPE-REF>sot-requirement</SPEC-OBJECT-TYPE-REF>",
+            "<SPEC-OBJECT-TYPE-REF>st-specification</SPEC-OBJECT-TYPE-REF>",
+        );
+        let line = SMALL
+            .lines()
+            .position(|l| l.contains(r#"<SPEC-OBJECT IDENTIFIER="req-001""#))
+            .unwrap() as u64
+            + 1;
+        let path = std::env::temp_dir().join(format!(
+            "reqsmith-error-location-{}.reqif",
+            std::process::id()
+        ));
+        std::fs::write(&path, xml).unwrap();
+        let error = parse_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.location().line, line);
+        assert_eq!(
+            error.location().file.as_deref(),
+            Some(path.display().to_string().as_str())
+        );
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}:{}:", path.display(), line)));
+
+        let utf16 = r#"<?xml version="1.0" encoding="UTF-16"?><REQ-IF/>"#;
+        assert!(matches!(
+            parse_str(utf16),
+            Err(ReqIfError::Unsupported { .. })
+        ));
+    }
+}
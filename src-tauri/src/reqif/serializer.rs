@@ -0,0 +1,996 @@
+// ReqIF serializer - Writes the data model back to ReqIF 1.2 XML
+
+use super::model::*;
+use super::xml::XmlElement;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// ReqIF 1.2 XML namespace
+pub const REQIF_NAMESPACE: &str = "http://www.omg.org/spec/ReqIF/20110401/reqif.xsd";
+/// XHTML namespace used inside ATTRIBUTE-VALUE-XHTML content
+pub const XHTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+/// REQ-IF-TOOL-ID written when the header has none
+const TOOL_ID: &str = "ReqSmith";
+/// REQ-IF-VERSION written when the header has none
+const REQIF_VERSION: &str = "1.2";
+
+/// Errors raised while writing a ReqIF document
+#[derive(Debug, Error)]
+pub enum SerializeError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("attribute definition '{attribute}' references unknown datatype '{datatype}'")]
+    UnknownDatatype { attribute: String, datatype: String },
+}
+
+/// Serialize a ReqIF document to a string
+pub fn to_string(reqif: &ReqIF) -> Result<String, SerializeError> {
+    let mut out = Vec::new();
+    write(reqif, &mut out)?;
+    Ok(String::from_utf8(out).expect("serializer only emits UTF-8"))
+}
+
+/// Serialize a ReqIF document to a `.reqif` file on disk
+pub fn write_file(reqif: &ReqIF, path: impl AsRef<Path>) -> Result<(), SerializeError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(reqif, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Serialize a ReqIF document to any writer
+pub fn write<W: Write>(reqif: &ReqIF, out: W) -> Result<(), SerializeError> {
+    Serializer::new(reqif, out).write_document()
+}
+
+/// Known attributes and the unknown ones preserved in `extras`, in the order
+/// they were read; attributes the layout does not list follow, known first
+fn with_extras<'b>(known: Vec<(&'b str, &'b str)>, extras: &'b Extras) -> Vec<(&'b str, &'b str)> {
+    let mut rest: Vec<(&str, &str)> = known
+        .into_iter()
+        .chain(
+            extras
+                .attributes
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        )
+        .collect();
+    let mut attrs = Vec::with_capacity(rest.len());
+    for name in extras.layout.iter().flat_map(|l| &l.attributes) {
+        if let Some(index) = rest.iter().position(|(k, _)| k == name) {
+            attrs.push(rest.remove(index));
+        }
+    }
+    attrs.extend(rest);
+    attrs
+}
+
+/// Sections of REQ-IF-CONTENT in schema order
+const CONTENT_SECTIONS: &[&str] = &[
+    "DATATYPES",
+    "SPEC-TYPES",
+    "SPEC-OBJECTS",
+    "SPEC-RELATIONS",
+    "SPECIFICATIONS",
+    "SPEC-RELATION-GROUPS",
+];
+
+struct Serializer<'a, W: Write> {
+    reqif: &'a ReqIF,
+    writer: Writer<W>,
+    /// Datatype identifier -> type suffix
+    datatype_kinds: HashMap<&'a str, &'static str>,
+}
+
+impl<'a, W: Write> Serializer<'a, W> {
+    fn new(reqif: &'a ReqIF, out: W) -> Self {
+        let datatype_kinds = reqif
+            .core_content
+            .datatype_definitions
+            .iter()
+            .map(|dt| (dt.identifier(), datatype_kind(dt)))
+            .collect();
+        Self {
+            reqif,
+            writer: Writer::new_with_indent(out, b' ', 2),
+            datatype_kinds,
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &[(&str, &str)]) -> Result<(), SerializeError> {
+        let mut start = BytesStart::new(name);
+        start.extend_attributes(attrs.iter().copied());
+        self.writer.write_event(Event::Start(start))?;
+        Ok(())
+    }
+
+    fn end(&mut self, name: &str) -> Result<(), SerializeError> {
+        self.writer.write_event(Event::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+
+    fn empty(&mut self, name: &str, attrs: &[(&str, &str)]) -> Result<(), SerializeError> {
+        let mut start = BytesStart::new(name);
+        start.extend_attributes(attrs.iter().copied());
+        self.writer.write_event(Event::Empty(start))?;
+        Ok(())
+    }
+
+    fn text_element(&mut self, name: &str, text: &str) -> Result<(), SerializeError> {
+        self.writer
+            .create_element(name)
+            .write_text_content(BytesText::new(text))?;
+        Ok(())
+    }
+
+    /// Write `<CONTAINER><REF-NAME>id</REF-NAME></CONTAINER>`. An empty `id`
+    /// (a reference missing from a leniently parsed document) is left out.
+    fn reference(
+        &mut self,
+        container: &str,
+        ref_name: &str,
+        id: &str,
+    ) -> Result<(), SerializeError> {
+        if id.is_empty() {
+            return Ok(());
+        }
+        self.start(container, &[])?;
+        self.text_element(ref_name, id)?;
+        self.end(container)
+    }
+
+    /// Write a preserved element subtree verbatim on its own line
+    fn raw_element(&mut self, element: &XmlElement) -> Result<(), SerializeError> {
+        self.writer.write_indent()?;
+        self.writer
+            .get_mut()
+            .write_all(element.to_xml().as_bytes())?;
+        Ok(())
+    }
+
+    /// Write the children of an element owning `extras` in the order they
+    /// were read, with the unknown ones back between the known ones.
+    /// `write` writes the child `name` of `known` and is told whether the
+    /// element had it, or `None` for elements not read from a document.
+    /// Known children the element lacked go before the next known child that
+    /// follows them in `known`. `containers` name the groups of unknown
+    /// container items in `extras`.
+    fn children(
+        &mut self,
+        extras: &Extras,
+        known: &[&str],
+        containers: &[&str],
+        mut write: impl FnMut(&mut Self, &str, Option<bool>) -> Result<(), SerializeError>,
+    ) -> Result<(), SerializeError> {
+        let mut unknown = extras.unknown_elements(containers);
+        let Some(layout) = &extras.layout else {
+            for name in known {
+                write(self, name, None)?;
+            }
+            for element in unknown {
+                self.raw_element(element)?;
+            }
+            return Ok(());
+        };
+        // Index into `known` of each child, `None` for unknown ones
+        let mut order = Vec::new();
+        for child in &layout.children {
+            match child {
+                Some(name) => match known.iter().position(|k| k == name) {
+                    Some(index) if !order.contains(&Some(index)) => order.push(Some(index)),
+                    _ => {}
+                },
+                None => order.push(None),
+            }
+        }
+        let mut missing = (0..known.len())
+            .filter(|index| !order.contains(&Some(*index)))
+            .peekable();
+        for slot in &order {
+            match *slot {
+                Some(index) => {
+                    while let Some(before) = missing.next_if(|m| *m < index) {
+                        write(self, known[before], Some(false))?;
+                    }
+                    write(self, known[index], Some(true))?;
+                }
+                None => {
+                    if let Some(element) = unknown.next() {
+                        self.raw_element(element)?;
+                    }
+                }
+            }
+        }
+        for index in missing {
+            write(self, known[index], Some(false))?;
+        }
+        for element in unknown {
+            self.raw_element(element)?;
+        }
+        Ok(())
+    }
+
+    /// Write the items of the known container `name` through `write`, with
+    /// the unknown items preserved in `extras` back between them
+    fn items<T>(
+        &mut self,
+        extras: &Extras,
+        name: &str,
+        items: &[T],
+        mut write: impl FnMut(&mut Self, &T) -> Result<(), SerializeError>,
+    ) -> Result<(), SerializeError> {
+        let mut unknown = extras.positioned_items(name).peekable();
+        for (index, item) in items.iter().enumerate() {
+            while let Some((_, element)) = unknown.next_if(|(position, _)| *position <= index) {
+                self.raw_element(element)?;
+            }
+            write(self, item)?;
+        }
+        for (_, element) in unknown {
+            self.raw_element(element)?;
+        }
+        Ok(())
+    }
+
+    fn write_document(mut self) -> Result<(), SerializeError> {
+        self.writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        let reqif = self.reqif;
+        let attrs = with_extras(
+            vec![("xmlns", REQIF_NAMESPACE), ("xmlns:xhtml", XHTML_NAMESPACE)],
+            &reqif.extras,
+        );
+        self.start("REQ-IF", &attrs)?;
+        self.children(
+            &reqif.extras,
+            &["THE-HEADER", "CORE-CONTENT", "TOOL-EXTENSIONS"],
+            &[],
+            |s, name, _| match name {
+                "THE-HEADER" => s.write_header(),
+                "CORE-CONTENT" => s.write_core_content(),
+                _ => s.write_tool_extensions(),
+            },
+        )?;
+        self.end("REQ-IF")?;
+        self.writer.get_mut().write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<(), SerializeError> {
+        let wrapper = &self.reqif.wrappers.header;
+        self.start("THE-HEADER", &with_extras(Vec::new(), wrapper))?;
+        self.children(wrapper, &["REQ-IF-HEADER"], &[], |s, _, _| {
+            s.write_reqif_header()
+        })?;
+        self.end("THE-HEADER")
+    }
+
+    fn write_reqif_header(&mut self) -> Result<(), SerializeError> {
+        let header = &self.reqif.header;
+        let attrs = with_extras(vec![("IDENTIFIER", &header.identifier)], &header.extras);
+        self.start("REQ-IF-HEADER", &attrs)?;
+        let known = [
+            "COMMENT",
+            "CREATION-TIME",
+            "REPOSITORY-ID",
+            "REQ-IF-TOOL-ID",
+            "REQ-IF-VERSION",
+            "SOURCE-TOOL-ID",
+            "TITLE",
+        ];
+        self.children(&header.extras, &known, &[], |s, name, present| {
+            let text = match name {
+                "COMMENT" => header.comment.as_deref(),
+                "CREATION-TIME" => Some(header.creation_time.as_str()),
+                "REPOSITORY-ID" => header.repository_id.as_deref(),
+                "REQ-IF-TOOL-ID" => or_default(&header.reqif_tool_id, TOOL_ID, present),
+                "REQ-IF-VERSION" => or_default(&header.reqif_version, REQIF_VERSION, present),
+                "SOURCE-TOOL-ID" => Some(header.source_tool_id.as_str()),
+                _ => header
+                    .title
+                    .as_deref()
+                    .or_else(|| or_default("", "", present)),
+            };
+            match text {
+                Some(text) => s.text_element(name, text),
+                None => Ok(()),
+            }
+        })?;
+        self.end("REQ-IF-HEADER")
+    }
+
+    fn write_core_content(&mut self) -> Result<(), SerializeError> {
+        let reqif = self.reqif;
+        let wrapper = &reqif.wrappers.core_content;
+        self.start("CORE-CONTENT", &with_extras(Vec::new(), wrapper))?;
+        self.children(wrapper, &["REQ-IF-CONTENT"], &[], |s, _, _| {
+            s.write_content(&reqif.core_content)
+        })?;
+        self.end("CORE-CONTENT")
+    }
+
+    fn write_content(&mut self, content: &CoreContent) -> Result<(), SerializeError> {
+        let extras = &content.extras;
+        self.start("REQ-IF-CONTENT", &with_extras(Vec::new(), extras))?;
+        self.children(
+            extras,
+            CONTENT_SECTIONS,
+            CONTENT_SECTIONS,
+            |s, name, present| {
+                let count = match name {
+                    "DATATYPES" => content.datatype_definitions.len(),
+                    "SPEC-TYPES" => content.spec_types.len(),
+                    "SPEC-OBJECTS" => content.spec_objects.len(),
+                    "SPEC-RELATIONS" => content.spec_relations.len(),
+                    "SPECIFICATIONS" => content.specifications.len(),
+                    _ => content.spec_relation_groups.len(),
+                };
+                if present == Some(false) && count == 0 && !has_items(extras, name) {
+                    return Ok(());
+                }
+                s.start(name, &[])?;
+                match name {
+                    "DATATYPES" => s.items(
+                        extras,
+                        name,
+                        &content.datatype_definitions,
+                        Self::write_datatype,
+                    ),
+                    "SPEC-TYPES" => {
+                        s.items(extras, name, &content.spec_types, Self::write_spec_type)
+                    }
+                    "SPEC-OBJECTS" => {
+                        s.items(extras, name, &content.spec_objects, Self::write_spec_object)
+                    }
+                    "SPEC-RELATIONS" => s.items(
+                        extras,
+                        name,
+                        &content.spec_relations,
+                        Self::write_spec_relation,
+                    ),
+                    "SPECIFICATIONS" => s.items(
+                        extras,
+                        name,
+                        &content.specifications,
+                        Self::write_specification,
+                    ),
+                    _ => s.items(
+                        extras,
+                        name,
+                        &content.spec_relation_groups,
+                        Self::write_relation_group,
+                    ),
+                }?;
+                s.end(name)
+            },
+        )?;
+        self.end("REQ-IF-CONTENT")
+    }
+
+    fn write_datatype(&mut self, datatype: &DatatypeDefinition) -> Result<(), SerializeError> {
+        let name = format!("DATATYPE-DEFINITION-{}", datatype_kind(datatype));
+        let mut attrs: Vec<_> = identifiable_attrs(datatype.ident())
+            .into_iter()
+            .map(|(k, v)| (k, v.to_string()))
+            .collect();
+        match datatype {
+            DatatypeDefinition::Integer { min, max, .. } => {
+                push_opt(&mut attrs, "MAX", max);
+                push_opt(&mut attrs, "MIN", min);
+            }
+            DatatypeDefinition::Real {
+                min, max, accuracy, ..
+            } => {
+                push_opt(&mut attrs, "ACCURACY", accuracy);
+                push_opt(&mut attrs, "MAX", &max.map(xsd_double));
+                push_opt(&mut attrs, "MIN", &min.map(xsd_double));
+            }
+            DatatypeDefinition::String { max_length, .. } => {
+                push_opt(&mut attrs, "MAX-LENGTH", max_length);
+            }
+            _ => {}
+        }
+        let extras = datatype.extras();
+        let attrs = with_extras(
+            attrs.iter().map(|(k, v)| (*k, v.as_str())).collect(),
+            extras,
+        );
+
+        let values = match datatype {
+            DatatypeDefinition::Enumeration { values, .. } => values.as_slice(),
+            _ if extras.elements.is_empty() => return self.empty(&name, &attrs),
+            _ => &[],
+        };
+        self.start(&name, &attrs)?;
+        let enumeration = matches!(datatype, DatatypeDefinition::Enumeration { .. });
+        let container = "SPECIFIED-VALUES";
+        self.children(extras, &[container], &[container], |s, _, present| {
+            if !enumeration
+                || (present == Some(false) && values.is_empty() && !has_items(extras, container))
+            {
+                return Ok(());
+            }
+            s.start(container, &[])?;
+            let mut index = 0;
+            s.items(extras, container, values, |s, value| {
+                index += 1;
+                s.write_enum_value(index - 1, value)
+            })?;
+            s.end(container)
+        })?;
+        self.end(&name)
+    }
+
+    fn write_enum_value(&mut self, index: usize, value: &EnumValue) -> Result<(), SerializeError> {
+        let attrs = identifiable_attrs(&value.ident);
+        let key = value
+            .properties
+            .clone()
+            .unwrap_or_else(|| index.to_string());
+        self.start("ENUM-VALUE", &with_extras(attrs, &value.extras))?;
+        let embedded = value.properties.is_some() || value.other_content.is_some();
+        self.children(&value.extras, &["PROPERTIES"], &[], |s, _, present| {
+            if present == Some(false) && !embedded {
+                return Ok(());
+            }
+            s.start("PROPERTIES", &[])?;
+            s.empty(
+                "EMBEDDED-VALUE",
+                &[
+                    ("KEY", &key),
+                    (
+                        "OTHER-CONTENT",
+                        value.other_content.as_deref().unwrap_or_default(),
+                    ),
+                ],
+            )?;
+            s.end("PROPERTIES")
+        })?;
+        self.end("ENUM-VALUE")
+    }
+
+    fn write_spec_type(&mut self, spec_type: &SpecType) -> Result<(), SerializeError> {
+        let name = spec_type.kind.element_name();
+        let attrs = identifiable_attrs(&spec_type.ident);
+        let extras = &spec_type.extras;
+        let container = "SPEC-ATTRIBUTES";
+        self.start(name, &with_extras(attrs, extras))?;
+        self.children(extras, &[container], &[container], |s, _, present| {
+            let definitions = &spec_type.spec_attributes;
+            if present == Some(false) && definitions.is_empty() && !has_items(extras, container) {
+                return Ok(());
+            }
+            s.start(container, &[])?;
+            s.items(
+                extras,
+                container,
+                definitions,
+                Self::write_attribute_definition,
+            )?;
+            s.end(container)
+        })?;
+        self.end(name)
+    }
+
+    fn write_attribute_definition(
+        &mut self,
+        definition: &AttributeDefinition,
+    ) -> Result<(), SerializeError> {
+        let kind = *self
+            .datatype_kinds
+            .get(definition.datatype_ref.as_str())
+            .ok_or_else(|| SerializeError::UnknownDatatype {
+                attribute: definition.ident.identifier.clone(),
+                datatype: definition.datatype_ref.clone(),
+            })?;
+        let name = format!("ATTRIBUTE-DEFINITION-{}", kind);
+        let mut attrs = identifiable_attrs(&definition.ident);
+        if let Some(is_editable) = definition.is_editable {
+            attrs.push(("IS-EDITABLE", bool_str(is_editable)));
+        }
+        // Written when set, or kept as read; false is the schema default
+        if kind == "ENUMERATION"
+            && (definition.multi_valued || definition.extras.had_attribute("MULTI-VALUED"))
+        {
+            attrs.push(("MULTI-VALUED", bool_str(definition.multi_valued)));
+        }
+        self.start(&name, &with_extras(attrs, &definition.extras))?;
+        self.children(
+            &definition.extras,
+            &["DEFAULT-VALUE", "TYPE"],
+            &[],
+            |s, child, _| match (child, &definition.default_value) {
+                ("DEFAULT-VALUE", Some(default_value)) => {
+                    s.start("DEFAULT-VALUE", &[])?;
+                    s.write_attribute_value(default_value)?;
+                    s.end("DEFAULT-VALUE")
+                }
+                ("DEFAULT-VALUE", None) => Ok(()),
+                _ => s.reference(
+                    "TYPE",
+                    &format!("DATATYPE-DEFINITION-{}-REF", kind),
+                    &definition.datatype_ref,
+                ),
+            },
+        )?;
+        self.end(&name)
+    }
+
+    /// Write the VALUES container of an element owning `extras`; an empty
+    /// one only if the element had it
+    fn write_values(
+        &mut self,
+        values: &[AttributeValue],
+        extras: &Extras,
+        present: Option<bool>,
+    ) -> Result<(), SerializeError> {
+        if values.is_empty() && present != Some(true) && !has_items(extras, "VALUES") {
+            return Ok(());
+        }
+        self.start("VALUES", &[])?;
+        self.items(extras, "VALUES", values, Self::write_attribute_value)?;
+        self.end("VALUES")
+    }
+
+    fn write_attribute_value(&mut self, value: &AttributeValue) -> Result<(), SerializeError> {
+        let kind = value_kind(value);
+        let name = format!("ATTRIBUTE-VALUE-{}", kind);
+        let the_value = match value {
+            AttributeValue::Boolean { value, .. } => Some(value.to_string()),
+            AttributeValue::Date { value, .. } => Some(value.to_string()),
+            AttributeValue::Integer { value, .. } => Some(value.to_string()),
+            AttributeValue::Real { value, .. } => Some(xsd_double(*value)),
+            AttributeValue::String { value, .. } => Some(value.clone()),
+            AttributeValue::Enumeration { .. } | AttributeValue::XHTML { .. } => None,
+        };
+        let mut attrs = Vec::new();
+        if let Some(v) = &the_value {
+            attrs.push(("THE-VALUE", v.as_str()));
+        }
+        self.start(&name, &with_extras(attrs, value.extras()))?;
+        let known = ["DEFINITION", "VALUES", "THE-VALUE"];
+        self.children(value.extras(), &known, &[], |s, child, present| {
+            match (child, value) {
+                ("DEFINITION", _) => s.reference(
+                    "DEFINITION",
+                    &format!("ATTRIBUTE-DEFINITION-{}-REF", kind),
+                    value.definition(),
+                ),
+                ("VALUES", AttributeValue::Enumeration { values, .. }) if values.is_empty() => {
+                    match present {
+                        Some(false) => Ok(()),
+                        _ => s.empty("VALUES", &[]),
+                    }
+                }
+                ("VALUES", AttributeValue::Enumeration { values, .. }) => {
+                    s.start("VALUES", &[])?;
+                    for value in values {
+                        s.text_element("ENUM-VALUE-REF", value)?;
+                    }
+                    s.end("VALUES")
+                }
+                ("THE-VALUE", AttributeValue::XHTML { value, .. }) => {
+                    s.start("THE-VALUE", &[])?;
+                    // XHTML content is stored as serialized markup and written verbatim
+                    s.writer
+                        .write_event(Event::Text(BytesText::from_escaped(value.as_str())))?;
+                    s.end("THE-VALUE")
+                }
+                _ => Ok(()),
+            }
+        })?;
+        self.end(&name)
+    }
+
+    fn write_spec_object(&mut self, object: &SpecObject) -> Result<(), SerializeError> {
+        let attrs = identifiable_attrs(&object.ident);
+        let extras = &object.extras;
+        self.start("SPEC-OBJECT", &with_extras(attrs, extras))?;
+        self.children(
+            extras,
+            &["TYPE", "VALUES"],
+            &["VALUES"],
+            |s, name, present| match name {
+                "TYPE" => s.reference("TYPE", "SPEC-OBJECT-TYPE-REF", &object.spec_type),
+                _ => s.write_values(&object.values, extras, present),
+            },
+        )?;
+        self.end("SPEC-OBJECT")
+    }
+
+    fn write_spec_relation(&mut self, relation: &SpecRelation) -> Result<(), SerializeError> {
+        let attrs = identifiable_attrs(&relation.ident);
+        let extras = &relation.extras;
+        self.start("SPEC-RELATION", &with_extras(attrs, extras))?;
+        self.children(
+            extras,
+            &["TYPE", "VALUES", "SOURCE", "TARGET"],
+            &["VALUES"],
+            |s, name, present| match name {
+                "TYPE" => s.reference("TYPE", "SPEC-RELATION-TYPE-REF", &relation.spec_type),
+                "VALUES" => s.write_values(&relation.values, extras, present),
+                "SOURCE" => s.reference("SOURCE", "SPEC-OBJECT-REF", &relation.source),
+                _ => s.reference("TARGET", "SPEC-OBJECT-REF", &relation.target),
+            },
+        )?;
+        self.end("SPEC-RELATION")
+    }
+
+    fn write_relation_group(&mut self, group: &RelationGroup) -> Result<(), SerializeError> {
+        let attrs = identifiable_attrs(&group.ident);
+        self.start("RELATION-GROUP", &with_extras(attrs, &group.extras))?;
+        let known = [
+            "SOURCE-SPECIFICATION",
+            "SPEC-RELATIONS",
+            "TARGET-SPECIFICATION",
+            "TYPE",
+        ];
+        self.children(&group.extras, &known, &[], |s, name, present| match name {
+            "SOURCE-SPECIFICATION" => s.reference(
+                "SOURCE-SPECIFICATION",
+                "SPECIFICATION-REF",
+                &group.source_specification,
+            ),
+            "SPEC-RELATIONS" if group.spec_relations.is_empty() => match present {
+                Some(false) => Ok(()),
+                _ => s.empty("SPEC-RELATIONS", &[]),
+            },
+            "SPEC-RELATIONS" => {
+                s.start("SPEC-RELATIONS", &[])?;
+                for relation in &group.spec_relations {
+                    s.text_element("SPEC-RELATION-REF", relation)?;
+                }
+                s.end("SPEC-RELATIONS")
+            }
+            "TARGET-SPECIFICATION" => s.reference(
+                "TARGET-SPECIFICATION",
+                "SPECIFICATION-REF",
+                &group.target_specification,
+            ),
+            _ => s.reference("TYPE", "RELATION-GROUP-TYPE-REF", &group.spec_type),
+        })?;
+        self.end("RELATION-GROUP")
+    }
+
+    fn write_specification(&mut self, specification: &Specification) -> Result<(), SerializeError> {
+        let attrs = identifiable_attrs(&specification.ident);
+        let extras = &specification.extras;
+        self.start("SPECIFICATION", &with_extras(attrs, extras))?;
+        self.children(
+            extras,
+            &["TYPE", "VALUES", "CHILDREN"],
+            &["VALUES", "CHILDREN"],
+            |s, name, present| match name {
+                "TYPE" => s.reference("TYPE", "SPECIFICATION-TYPE-REF", &specification.spec_type),
+                "VALUES" => s.write_values(&specification.values, extras, present),
+                _ => s.write_children(&specification.children, extras, present),
+            },
+        )?;
+        self.end("SPECIFICATION")
+    }
+
+    /// Write the CHILDREN container of an element owning `extras`; an empty
+    /// one only if the element had it
+    fn write_children(
+        &mut self,
+        children: &[SpecHierarchy],
+        extras: &Extras,
+        present: Option<bool>,
+    ) -> Result<(), SerializeError> {
+        if children.is_empty() && present != Some(true) && !has_items(extras, "CHILDREN") {
+            return Ok(());
+        }
+        self.start("CHILDREN", &[])?;
+        self.items(extras, "CHILDREN", children, Self::write_hierarchy)?;
+        self.end("CHILDREN")
+    }
+
+    fn write_hierarchy(&mut self, hierarchy: &SpecHierarchy) -> Result<(), SerializeError> {
+        let mut attrs = identifiable_attrs(&hierarchy.ident);
+        if let Some(is_editable) = hierarchy.is_editable {
+            attrs.push(("IS-EDITABLE", bool_str(is_editable)));
+        }
+        if let Some(is_table_internal) = hierarchy.is_table_internal {
+            attrs.push(("IS-TABLE-INTERNAL", bool_str(is_table_internal)));
+        }
+        let extras = &hierarchy.extras;
+        self.start("SPEC-HIERARCHY", &with_extras(attrs, extras))?;
+        self.children(
+            extras,
+            &["OBJECT", "CHILDREN"],
+            &["CHILDREN"],
+            |s, name, present| match name {
+                "OBJECT" => s.reference("OBJECT", "SPEC-OBJECT-REF", &hierarchy.object),
+                _ => s.write_children(&hierarchy.children, extras, present),
+            },
+        )?;
+        self.end("SPEC-HIERARCHY")
+    }
+
+    fn write_tool_extensions(&mut self) -> Result<(), SerializeError> {
+        let reqif = self.reqif;
+        let wrapper = &reqif.wrappers.tool_extensions;
+        let container = "TOOL-EXTENSIONS";
+        if reqif.tool_extensions.is_empty() && !has_items(wrapper, container) {
+            return Ok(());
+        }
+        self.start(container, &with_extras(Vec::new(), wrapper))?;
+        self.items(
+            wrapper,
+            container,
+            &reqif.tool_extensions,
+            |s, extension| {
+                let attrs = with_extras(Vec::new(), &extension.extras);
+                s.start("REQ-IF-TOOL-EXTENSION", &attrs)?;
+                s.writer.write_event(Event::Text(BytesText::from_escaped(
+                    extension.content.as_str(),
+                )))?;
+                s.end("REQ-IF-TOOL-EXTENSION")
+            },
+        )?;
+        self.end(container)
+    }
+}
+
+/// Whether `extras` keeps unknown items for the known container `name`
+fn has_items(extras: &Extras, name: &str) -> bool {
+    extras.container_items(name).next().is_some()
+}
+
+/// DESC, IDENTIFIER, LAST-CHANGE and LONG-NAME of an Identifiable element
+fn identifiable_attrs(ident: &Identifiable) -> Vec<(&'static str, &str)> {
+    let mut attrs = Vec::new();
+    if let Some(desc) = &ident.desc {
+        attrs.push(("DESC", desc.as_str()));
+    }
+    attrs.push(("IDENTIFIER", ident.identifier.as_str()));
+    if let Some(last_change) = &ident.last_change {
+        attrs.push(("LAST-CHANGE", last_change.as_str()));
+    }
+    if let Some(long_name) = &ident.long_name {
+        attrs.push(("LONG-NAME", long_name.as_str()));
+    }
+    attrs
+}
+
+fn bool_str(value: bool) -> &'static str {
+    if value {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+/// `value`, or `default` when it is empty; nothing for an empty value of an
+/// element read without it
+fn or_default<'b>(value: &'b str, default: &'b str, present: Option<bool>) -> Option<&'b str> {
+    match present {
+        _ if !value.is_empty() => Some(value),
+        Some(false) => None,
+        _ => Some(default),
+    }
+}
+
+/// `value` as an xsd:double literal: `INF`, `-INF` and `NaN` spelled out,
+/// and exponent notation for very large or small magnitudes
+fn xsd_double(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "INF" } else { "-INF" }.to_string()
+    } else if value != 0.0 && !(1e-6..1e16).contains(&value.abs()) {
+        format!("{:E}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn push_opt<T: ToString>(
+    attrs: &mut Vec<(&'static str, String)>,
+    name: &'static str,
+    value: &Option<T>,
+) {
+    if let Some(value) = value {
+        attrs.push((name, value.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+    use crate::reqif::xml::XmlNode;
+    use quick_xml::Reader;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    #[test]
+    fn test_serialize_typed_elements() {
+        let reqif = parser::parse_str(SMALL).unwrap();
+        let xml = to_string(&reqif).unwrap();
+        assert!(xml.contains(&format!(r#"<REQ-IF xmlns="{}""#, REQIF_NAMESPACE)));
+        assert!(xml.contains(r#"<SPEC-OBJECT-TYPE IDENTIFIER="sot-requirement""#));
+        assert!(xml.contains(r#"<SPECIFICATION-TYPE IDENTIFIER="st-specification""#));
+        assert!(xml.contains(r#"<ATTRIBUTE-DEFINITION-XHTML IDENTIFIER="ad-description""#));
+        assert!(xml.contains(
+            "<DATATYPE-DEFINITION-INTEGER-REF>dt-int-001</DATATYPE-DEFINITION-INTEGER-REF>"
+        ));
+        assert!(xml.contains(r#"<ATTRIBUTE-VALUE-INTEGER THE-VALUE="10">"#));
+        assert!(xml.contains("<THE-VALUE><xhtml:div>The system shall initialize"));
+    }
+
+    #[test]
+    fn test_round_trip_small_fixture() {
+        let original = parser::parse_str(SMALL).unwrap();
+        let xml = to_string(&original).unwrap();
+        assert_eq!(tree(&xml), tree(SMALL));
+        let reparsed = parser::parse_str(&xml).unwrap();
+        assert_eq!(
+            serde_json::to_value(&original).unwrap(),
+            serde_json::to_value(&reparsed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_real_values_are_xsd_doubles() {
+        let xml = SMALL.replace(
+            "<DATATYPES>",
+            r#"<DATATYPES>
+        <DATATYPE-DEFINITION-REAL IDENTIFIER="dt-real" ACCURACY="3" MAX="INF" MIN="-1.7976931348623157E308"/>"#,
+        );
+        let reqif = parser::parse_str(&xml).unwrap();
+        let written = to_string(&reqif).unwrap();
+        assert!(written.contains(r#"MAX="INF" MIN="-1.7976931348623157E308""#));
+        assert_eq!(tree(&written), tree(&xml));
+
+        assert_eq!(xsd_double(f64::NEG_INFINITY), "-INF");
+        assert_eq!(xsd_double(f64::NAN), "NaN");
+        assert_eq!(xsd_double(2.5e-9), "2.5E-9");
+        assert_eq!(xsd_double(0.25), "0.25");
+        assert_eq!(xsd_double(0.0), "0");
+    }
+
+    const VENDOR: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<REQ-IF xmlns="http://www.omg.org/spec/ReqIF/20110401/reqif.xsd" xmlns:xhtml="http://www.w3.org/1999/xhtml" xmlns:doors="urn:doors" doors:version="9.7">
+  <THE-HEADER>
+    <REQ-IF-HEADER IDENTIFIER="h">
+      <CREATION-TIME>2025-01-01T00:00:00Z</CREATION-TIME>
+      <REPOSITORY-ID>repo-1</REPOSITORY-ID>
+      <doors:project>Braking</doors:project>
+      <REQ-IF-TOOL-ID>DOORS</REQ-IF-TOOL-ID>
+      <REQ-IF-VERSION>1.0</REQ-IF-VERSION>
+      <SOURCE-TOOL-ID>DOORS 9.7</SOURCE-TOOL-ID>
+      <TITLE>Vendor</TITLE>
+    </REQ-IF-HEADER>
+    <doors:session id="s"/>
+  </THE-HEADER>
+  <CORE-CONTENT doors:scope="all">
+    <doors:baseline id="b"/>
+    <REQ-IF-CONTENT>
+      <DATATYPES>
+        <DATATYPE-DEFINITION-XHTML IDENTIFIER="dt-x" LAST-CHANGE="2025-01-01T00:00:00Z"/>
+        <DATATYPE-DEFINITION-TABLE IDENTIFIER="dt-table"/>
+        <DATATYPE-DEFINITION-DATE IDENTIFIER="dt-date"/>
+        <DATATYPE-DEFINITION-ENUMERATION IDENTIFIER="dt-enum">
+          <SPECIFIED-VALUES>
+            <ENUM-VALUE IDENTIFIER="ev-1" LONG-NAME="Open"/>
+          </SPECIFIED-VALUES>
+        </DATATYPE-DEFINITION-ENUMERATION>
+      </DATATYPES>
+      <SPEC-TYPES>
+        <SPEC-OBJECT-TYPE IDENTIFIER="sot">
+          <SPEC-ATTRIBUTES>
+            <ATTRIBUTE-DEFINITION-XHTML IDENTIFIER="ad-x" IS-EDITABLE="false">
+              <TYPE><DATATYPE-DEFINITION-XHTML-REF>dt-x</DATATYPE-DEFINITION-XHTML-REF></TYPE>
+            </ATTRIBUTE-DEFINITION-XHTML>
+            <ATTRIBUTE-DEFINITION-DATE IDENTIFIER="ad-date">
+              <TYPE><DATATYPE-DEFINITION-DATE-REF>dt-date</DATATYPE-DEFINITION-DATE-REF></TYPE>
+            </ATTRIBUTE-DEFINITION-DATE>
+            <ATTRIBUTE-DEFINITION-ENUMERATION IDENTIFIER="ad-status">
+              <TYPE><DATATYPE-DEFINITION-ENUMERATION-REF>dt-enum</DATATYPE-DEFINITION-ENUMERATION-REF></TYPE>
+            </ATTRIBUTE-DEFINITION-ENUMERATION>
+          </SPEC-ATTRIBUTES>
+        </SPEC-OBJECT-TYPE>
+      </SPEC-TYPES>
+      <SPEC-OBJECTS>
+        <SPEC-OBJECT doors:absno="17" IDENTIFIER="o1" LONG-NAME="Object 1">
+          <ALTERNATIVE-ID><ALTERNATIVE-ID IDENTIFIER="alt-1"/></ALTERNATIVE-ID>
+          <TYPE><SPEC-OBJECT-TYPE-REF>sot</SPEC-OBJECT-TYPE-REF></TYPE>
+          <doors:note>Reviewed<!-- by QA --><?doors-sync full?></doors:note>
+          <VALUES>
+            <ATTRIBUTE-VALUE-XHTML IS-SIMPLIFIED="false">
+              <DEFINITION><ATTRIBUTE-DEFINITION-XHTML-REF>ad-x</ATTRIBUTE-DEFINITION-XHTML-REF></DEFINITION>
+              <THE-VALUE><xhtml:p>New <!-- draft --><xhtml:b>text</xhtml:b></xhtml:p></THE-VALUE>
+              <THE-ORIGINAL-VALUE><xhtml:p>Old <xhtml:i>text</xhtml:i></xhtml:p></THE-ORIGINAL-VALUE>
+            </ATTRIBUTE-VALUE-XHTML>
+            <ATTRIBUTE-VALUE-TABLE THE-VALUE="2x2"/>
+            <ATTRIBUTE-VALUE-DATE THE-VALUE="2025-03-01T12:00:00+01:00">
+              <DEFINITION><ATTRIBUTE-DEFINITION-DATE-REF>ad-date</ATTRIBUTE-DEFINITION-DATE-REF></DEFINITION>
+            </ATTRIBUTE-VALUE-DATE>
+          </VALUES>
+        </SPEC-OBJECT>
+      </SPEC-OBJECTS>
+      <SPECIFICATIONS>
+        <SPECIFICATION IDENTIFIER="s1">
+          <TYPE><SPECIFICATION-TYPE-REF>st</SPECIFICATION-TYPE-REF></TYPE>
+          <VALUES/>
+          <CHILDREN>
+            <SPEC-HIERARCHY IS-TABLE-INTERNAL="false" IDENTIFIER="sh1">
+              <OBJECT><SPEC-OBJECT-REF>o1</SPEC-OBJECT-REF></OBJECT>
+            </SPEC-HIERARCHY>
+          </CHILDREN>
+        </SPECIFICATION>
+      </SPECIFICATIONS>
+      <SPEC-RELATION-GROUPS>
+        <RELATION-GROUP IDENTIFIER="rg1" LONG-NAME="System to Software">
+          <SOURCE-SPECIFICATION><SPECIFICATION-REF>s1</SPECIFICATION-REF></SOURCE-SPECIFICATION>
+          <SPEC-RELATIONS/>
+          <TARGET-SPECIFICATION><SPECIFICATION-REF>s2</SPECIFICATION-REF></TARGET-SPECIFICATION>
+          <TYPE><RELATION-GROUP-TYPE-REF>rgt</RELATION-GROUP-TYPE-REF></TYPE>
+        </RELATION-GROUP>
+        <doors:link-set id="ls"/>
+      </SPEC-RELATION-GROUPS>
+    </REQ-IF-CONTENT>
+  </CORE-CONTENT>
+  <TOOL-EXTENSIONS doors:count="1">
+    <REQ-IF-TOOL-EXTENSION doors:kind="layout"><doors:layout width="3"/></REQ-IF-TOOL-EXTENSION>
+  </TOOL-EXTENSIONS>
+  <doors:trailer id="t"/>
+</REQ-IF>
+"#;
+
+    /// Element tree of `xml` without whitespace-only text and comments, to
+    /// compare documents regardless of indentation and section comments
+    fn tree(xml: &str) -> XmlElement {
+        fn strip(element: &mut XmlElement) {
+            element.children.retain(|node| match node {
+                XmlNode::Text(t) => !t.trim().is_empty(),
+                XmlNode::Comment(_) => false,
+                _ => true,
+            });
+            for node in &mut element.children {
+                if let XmlNode::Element(child) = node {
+                    strip(child);
+                }
+            }
+        }
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        loop {
+            if let Event::Start(start) = reader.read_event_into(&mut buf).unwrap() {
+                let start = start.into_owned();
+                let mut root = XmlElement::read(&mut reader, &start, &mut buf).unwrap();
+                strip(&mut root);
+                return root;
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_preserves_unknown_content() {
+        let first = to_string(&parser::parse_str(VENDOR).unwrap()).unwrap();
+        assert_eq!(tree(&first), tree(VENDOR));
+        // Writing a reloaded document again must not change it
+        let second = to_string(&parser::parse_str(&first).unwrap()).unwrap();
+        assert_eq!(first, second);
+        assert!(first.contains("<!-- draft -->") && first.contains("<?doors-sync full?>"));
+        assert!(!first.contains("MULTI-VALUED") && !first.contains("PROPERTIES"));
+    }
+
+    #[test]
+    fn test_unknown_datatype_is_an_error() {
+        let mut reqif = parser::parse_str(SMALL).unwrap();
+        reqif.core_content.datatype_definitions.clear();
+        assert!(matches!(
+            to_string(&reqif),
+            Err(SerializeError::UnknownDatatype { .. })
+        ));
+    }
+}
@@ -0,0 +1,698 @@
+// ReqIF document store - In-memory ReqIF document with identifier indexes
+// and resolved references
+
+use super::model::*;
+use std::collections::HashMap;
+
+/// Position of a SpecHierarchy: its Specification and the child indexes
+/// leading to it
+#[derive(Debug, Clone)]
+struct HierarchyPos {
+    specification: usize,
+    path: Vec<usize>,
+}
+
+/// Identifier indexes over a ReqIF document
+#[derive(Debug, Clone, Default)]
+struct Index {
+    datatypes: HashMap<String, usize>,
+    spec_types: HashMap<String, usize>,
+    /// Attribute definition -> (spec type, position in SPEC-ATTRIBUTES)
+    attribute_definitions: HashMap<String, (usize, usize)>,
+    spec_objects: HashMap<String, usize>,
+    spec_relations: HashMap<String, usize>,
+    specifications: HashMap<String, usize>,
+    relation_groups: HashMap<String, usize>,
+    hierarchies: HashMap<String, HierarchyPos>,
+    /// SpecObject -> SpecRelations with that object as source
+    outgoing: HashMap<String, Vec<String>>,
+    /// SpecObject -> SpecRelations with that object as target
+    incoming: HashMap<String, Vec<String>>,
+    /// SpecObject -> SpecHierarchies referencing it
+    object_hierarchies: HashMap<String, Vec<String>>,
+}
+
+impl Index {
+    fn build(content: &CoreContent) -> Self {
+        let mut index = Index::default();
+        for (i, datatype) in content.datatype_definitions.iter().enumerate() {
+            index.datatypes.insert(datatype.identifier().to_string(), i);
+        }
+        index.index_spec_types(&content.spec_types);
+        number(&mut index.spec_objects, &content.spec_objects, 0, |o| {
+            &o.ident
+        });
+        number(&mut index.spec_relations, &content.spec_relations, 0, |r| {
+            &r.ident
+        });
+        for relation in &content.spec_relations {
+            index.link_relation(relation);
+        }
+        for (i, specification) in content.specifications.iter().enumerate() {
+            index
+                .specifications
+                .insert(specification.ident.identifier.clone(), i);
+            for child in &specification.children {
+                index.link_hierarchy(child);
+            }
+            index.place_hierarchies(i, &mut Vec::new(), &specification.children, 0);
+        }
+        for (i, group) in content.spec_relation_groups.iter().enumerate() {
+            index
+                .relation_groups
+                .insert(group.ident.identifier.clone(), i);
+        }
+        index
+    }
+
+    fn index_spec_types(&mut self, spec_types: &[SpecType]) {
+        self.spec_types.clear();
+        self.attribute_definitions.clear();
+        for (i, spec_type) in spec_types.iter().enumerate() {
+            self.spec_types
+                .insert(spec_type.ident.identifier.clone(), i);
+            for (j, definition) in spec_type.spec_attributes.iter().enumerate() {
+                self.attribute_definitions
+                    .insert(definition.ident.identifier.clone(), (i, j));
+            }
+        }
+    }
+
+    fn link_relation(&mut self, relation: &SpecRelation) {
+        let id = &relation.ident.identifier;
+        self.outgoing
+            .entry(relation.source.clone())
+            .or_default()
+            .push(id.clone());
+        self.incoming
+            .entry(relation.target.clone())
+            .or_default()
+            .push(id.clone());
+    }
+
+    fn unlink_relation(&mut self, relation: &SpecRelation) {
+        let id = &relation.ident.identifier;
+        for (map, end) in [
+            (&mut self.outgoing, &relation.source),
+            (&mut self.incoming, &relation.target),
+        ] {
+            if let Some(ids) = map.get_mut(end) {
+                ids.retain(|r| r != id);
+                if ids.is_empty() {
+                    map.remove(end);
+                }
+            }
+        }
+    }
+
+    /// Index the objects referenced by `hierarchy` and its descendants
+    fn link_hierarchy(&mut self, hierarchy: &SpecHierarchy) {
+        self.object_hierarchies
+            .entry(hierarchy.object.clone())
+            .or_default()
+            .push(hierarchy.ident.identifier.clone());
+        for child in &hierarchy.children {
+            self.link_hierarchy(child);
+        }
+    }
+
+    /// Drop `hierarchy` and its descendants from the indexes
+    fn unlink_hierarchy(&mut self, hierarchy: &SpecHierarchy) {
+        let id = &hierarchy.ident.identifier;
+        self.hierarchies.remove(id);
+        if let Some(ids) = self.object_hierarchies.get_mut(&hierarchy.object) {
+            ids.retain(|h| h != id);
+            if ids.is_empty() {
+                self.object_hierarchies.remove(&hierarchy.object);
+            }
+        }
+        for child in &hierarchy.children {
+            self.unlink_hierarchy(child);
+        }
+    }
+
+    /// Record the positions of `children[from..]`, found at `path`, and of
+    /// their descendants
+    fn place_hierarchies(
+        &mut self,
+        specification: usize,
+        path: &mut Vec<usize>,
+        children: &[SpecHierarchy],
+        from: usize,
+    ) {
+        for (i, child) in children.iter().enumerate().skip(from) {
+            path.push(i);
+            self.hierarchies.insert(
+                child.ident.identifier.clone(),
+                HierarchyPos {
+                    specification,
+                    path: path.clone(),
+                },
+            );
+            self.place_hierarchies(specification, path, &child.children, 0);
+            path.pop();
+        }
+    }
+}
+
+/// A loaded ReqIF document with O(1) lookup of every identifiable element
+/// and reverse indexes for relations and hierarchies.
+///
+/// The document is only reachable read-only; changes go through the store's
+/// methods so the indexes stay consistent.
+#[derive(Debug, Clone)]
+pub struct DocumentStore {
+    reqif: ReqIF,
+    index: Index,
+}
+
+impl From<ReqIF> for DocumentStore {
+    fn from(reqif: ReqIF) -> Self {
+        Self::new(reqif)
+    }
+}
+
+impl DocumentStore {
+    pub fn new(reqif: ReqIF) -> Self {
+        let index = Index::build(&reqif.core_content);
+        Self { reqif, index }
+    }
+
+    pub fn reqif(&self) -> &ReqIF {
+        &self.reqif
+    }
+
+    pub fn content(&self) -> &CoreContent {
+        &self.reqif.core_content
+    }
+
+    pub fn into_reqif(self) -> ReqIF {
+        self.reqif
+    }
+
+    // Lookup by identifier
+
+    pub fn datatype(&self, id: &str) -> Option<&DatatypeDefinition> {
+        let i = *self.index.datatypes.get(id)?;
+        Some(&self.content().datatype_definitions[i])
+    }
+
+    pub fn spec_type(&self, id: &str) -> Option<&SpecType> {
+        let i = *self.index.spec_types.get(id)?;
+        Some(&self.content().spec_types[i])
+    }
+
+    pub fn attribute_definition(&self, id: &str) -> Option<&AttributeDefinition> {
+        let (i, j) = *self.index.attribute_definitions.get(id)?;
+        Some(&self.content().spec_types[i].spec_attributes[j])
+    }
+
+    pub fn spec_object(&self, id: &str) -> Option<&SpecObject> {
+        let i = *self.index.spec_objects.get(id)?;
+        Some(&self.content().spec_objects[i])
+    }
+
+    pub fn spec_relation(&self, id: &str) -> Option<&SpecRelation> {
+        let i = *self.index.spec_relations.get(id)?;
+        Some(&self.content().spec_relations[i])
+    }
+
+    pub fn specification(&self, id: &str) -> Option<&Specification> {
+        let i = *self.index.specifications.get(id)?;
+        Some(&self.content().specifications[i])
+    }
+
+    pub fn relation_group(&self, id: &str) -> Option<&RelationGroup> {
+        let i = *self.index.relation_groups.get(id)?;
+        Some(&self.content().spec_relation_groups[i])
+    }
+
+    pub fn hierarchy(&self, id: &str) -> Option<&SpecHierarchy> {
+        let pos = self.index.hierarchies.get(id)?;
+        let (first, rest) = pos.path.split_first()?;
+        let mut node = &self.content().specifications[pos.specification].children[*first];
+        for i in rest {
+            node = &node.children[*i];
+        }
+        Some(node)
+    }
+
+    /// Specification, parent SpecHierarchy (if nested) and position among
+    /// its siblings of SpecHierarchy `id`
+    pub fn hierarchy_placement(&self, id: &str) -> Option<(&str, Option<&str>, usize)> {
+        let pos = self.index.hierarchies.get(id)?;
+        let (index, parent_path) = pos.path.split_last()?;
+        let specification = &self.content().specifications[pos.specification];
+        let mut parent: Option<&SpecHierarchy> = None;
+        for i in parent_path {
+            let children = parent.map_or(&specification.children, |p| &p.children);
+            parent = Some(&children[*i]);
+        }
+        Some((
+            &specification.ident.identifier,
+            parent.map(|p| p.ident.identifier.as_str()),
+            *index,
+        ))
+    }
+
+    // Resolved references
+
+    pub fn relation_source(&self, relation: &SpecRelation) -> Option<&SpecObject> {
+        self.spec_object(&relation.source)
+    }
+
+    pub fn relation_target(&self, relation: &SpecRelation) -> Option<&SpecObject> {
+        self.spec_object(&relation.target)
+    }
+
+    pub fn hierarchy_object(&self, hierarchy: &SpecHierarchy) -> Option<&SpecObject> {
+        self.spec_object(&hierarchy.object)
+    }
+
+    pub fn value_definition(&self, value: &AttributeValue) -> Option<&AttributeDefinition> {
+        self.attribute_definition(value.definition())
+    }
+
+    pub fn definition_datatype(
+        &self,
+        definition: &AttributeDefinition,
+    ) -> Option<&DatatypeDefinition> {
+        self.datatype(&definition.datatype_ref)
+    }
+
+    /// Definition `definition` if it belongs to the type of `object`
+    pub fn object_attribute(
+        &self,
+        object: &SpecObject,
+        definition: &str,
+    ) -> Option<&AttributeDefinition> {
+        self.type_attribute(&object.spec_type, definition)
+    }
+
+    /// Definition `definition` if it belongs to spec type `spec_type`
+    pub fn type_attribute(
+        &self,
+        spec_type: &str,
+        definition: &str,
+    ) -> Option<&AttributeDefinition> {
+        let (i, j) = *self.index.attribute_definitions.get(definition)?;
+        let owner = &self.content().spec_types[i];
+        (owner.ident.identifier == spec_type).then(|| &owner.spec_attributes[j])
+    }
+
+    /// Value of `definition` on `object`, falling back to the definition's
+    /// DEFAULT-VALUE when the object has none
+    pub fn value_or_default<'a>(
+        &'a self,
+        object: &'a SpecObject,
+        definition: &str,
+    ) -> Option<&'a AttributeValue> {
+        object
+            .values
+            .iter()
+            .find(|v| v.definition() == definition)
+            .or_else(|| {
+                self.object_attribute(object, definition)?
+                    .default_value
+                    .as_ref()
+            })
+    }
+
+    // Reverse lookups
+
+    /// SpecRelations whose source is SpecObject `object`
+    pub fn outgoing_relations(&self, object: &str) -> impl Iterator<Item = &SpecRelation> {
+        self.relations_at(self.index.outgoing.get(object))
+    }
+
+    /// SpecRelations whose target is SpecObject `object`
+    pub fn incoming_relations(&self, object: &str) -> impl Iterator<Item = &SpecRelation> {
+        self.relations_at(self.index.incoming.get(object))
+    }
+
+    /// SpecHierarchies that place SpecObject `object` in a Specification
+    pub fn hierarchies_of(&self, object: &str) -> impl Iterator<Item = &SpecHierarchy> {
+        self.index
+            .object_hierarchies
+            .get(object)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.hierarchy(id))
+    }
+
+    fn relations_at<'a>(
+        &'a self,
+        ids: Option<&'a Vec<String>>,
+    ) -> impl Iterator<Item = &'a SpecRelation> {
+        ids.into_iter()
+            .flatten()
+            .filter_map(|id| self.spec_relation(id))
+    }
+
+    // Changes
+
+    /// Attribute values of SpecObject `object`. Values hold no indexed
+    /// references, so they may be changed freely.
+    pub(crate) fn values_mut(&mut self, object: &str) -> Option<&mut Vec<AttributeValue>> {
+        let i = *self.index.spec_objects.get(object)?;
+        Some(&mut self.reqif.core_content.spec_objects[i].values)
+    }
+
+    /// Append a SpecObject, replacing any object with the same identifier
+    pub fn insert_spec_object(&mut self, object: SpecObject) {
+        match self.index.spec_objects.get(&object.ident.identifier) {
+            Some(&i) => self.reqif.core_content.spec_objects[i] = object,
+            None => self.insert_spec_object_at(usize::MAX, object),
+        }
+    }
+
+    /// Insert a SpecObject with a new identifier at `index`, or append it
+    /// when past the end
+    pub fn insert_spec_object_at(&mut self, index: usize, object: SpecObject) {
+        let objects = &mut self.reqif.core_content.spec_objects;
+        let index = index.min(objects.len());
+        objects.insert(index, object);
+        number(&mut self.index.spec_objects, objects, index, |o| &o.ident);
+    }
+
+    /// Remove a SpecObject. Relations and hierarchies referencing it are left
+    /// in place for the caller to deal with.
+    pub fn remove_spec_object(&mut self, id: &str) -> Option<SpecObject> {
+        let i = self.index.spec_objects.remove(id)?;
+        let objects = &mut self.reqif.core_content.spec_objects;
+        let object = objects.remove(i);
+        number(&mut self.index.spec_objects, objects, i, |o| &o.ident);
+        Some(object)
+    }
+
+    /// Position of SpecObject `id` in the document
+    pub fn spec_object_index(&self, id: &str) -> Option<usize> {
+        self.index.spec_objects.get(id).copied()
+    }
+
+    /// Append a SpecRelation, replacing any relation with the same identifier
+    /// in place
+    pub fn insert_spec_relation(&mut self, relation: SpecRelation) {
+        match self.index.spec_relations.get(&relation.ident.identifier) {
+            Some(&i) => {
+                let old =
+                    std::mem::replace(&mut self.reqif.core_content.spec_relations[i], relation);
+                self.index.unlink_relation(&old);
+                self.index
+                    .link_relation(&self.reqif.core_content.spec_relations[i]);
+            }
+            None => self.insert_spec_relation_at(usize::MAX, relation),
+        }
+    }
+
+    /// Insert a SpecRelation with a new identifier at `index`, or append it
+    /// when past the end
+    pub fn insert_spec_relation_at(&mut self, index: usize, relation: SpecRelation) {
+        self.index.link_relation(&relation);
+        let relations = &mut self.reqif.core_content.spec_relations;
+        let index = index.min(relations.len());
+        relations.insert(index, relation);
+        number(&mut self.index.spec_relations, relations, index, |r| {
+            &r.ident
+        });
+    }
+
+    pub fn remove_spec_relation(&mut self, id: &str) -> Option<SpecRelation> {
+        let i = self.index.spec_relations.remove(id)?;
+        let relations = &mut self.reqif.core_content.spec_relations;
+        let relation = relations.remove(i);
+        number(&mut self.index.spec_relations, relations, i, |r| &r.ident);
+        self.index.unlink_relation(&relation);
+        Some(relation)
+    }
+
+    /// Position of SpecRelation `id` in the document
+    pub fn spec_relation_index(&self, id: &str) -> Option<usize> {
+        self.index.spec_relations.get(id).copied()
+    }
+
+    /// Relation groups listing SpecRelation `relation`, once per listing
+    pub fn groups_of(&self, relation: &str) -> Vec<String> {
+        self.content()
+            .spec_relation_groups
+            .iter()
+            .flat_map(|g| {
+                g.spec_relations
+                    .iter()
+                    .filter(|r| *r == relation)
+                    .map(|_| g.ident.identifier.clone())
+            })
+            .collect()
+    }
+
+    /// List SpecRelation `relation` at `index` in relation group `group`, or
+    /// last when past the end. Group members are not indexed.
+    pub fn insert_group_relation(
+        &mut self,
+        group: &str,
+        index: usize,
+        relation: String,
+    ) -> Option<()> {
+        let i = *self.index.relation_groups.get(group)?;
+        let relations = &mut self.reqif.core_content.spec_relation_groups[i].spec_relations;
+        relations.insert(index.min(relations.len()), relation);
+        Some(())
+    }
+
+    /// Drop the first listing of SpecRelation `relation` from relation group
+    /// `group`, returning its position
+    pub fn remove_group_relation(&mut self, group: &str, relation: &str) -> Option<usize> {
+        let i = *self.index.relation_groups.get(group)?;
+        let relations = &mut self.reqif.core_content.spec_relation_groups[i].spec_relations;
+        let index = relations.iter().position(|r| r == relation)?;
+        relations.remove(index);
+        Some(index)
+    }
+
+    /// Insert SpecHierarchy `hierarchy` at `index` among the children of
+    /// `parent`, or of Specification `specification` itself, appending when
+    /// past the end. `None` if the Specification or parent is unknown, or the
+    /// parent lies in another Specification.
+    pub fn insert_hierarchy(
+        &mut self,
+        specification: &str,
+        parent: Option<&str>,
+        index: usize,
+        hierarchy: SpecHierarchy,
+    ) -> Option<()> {
+        let s = *self.index.specifications.get(specification)?;
+        let mut path = match parent {
+            Some(parent) => {
+                let pos = self.index.hierarchies.get(parent)?;
+                if pos.specification != s {
+                    return None;
+                }
+                pos.path.clone()
+            }
+            None => Vec::new(),
+        };
+        let children = children_mut(&mut self.reqif.core_content.specifications[s], &path);
+        let index = index.min(children.len());
+        self.index.link_hierarchy(&hierarchy);
+        children.insert(index, hierarchy);
+        self.index.place_hierarchies(s, &mut path, children, index);
+        Some(())
+    }
+
+    /// Remove SpecHierarchy `id` with its descendants
+    pub fn remove_hierarchy(&mut self, id: &str) -> Option<SpecHierarchy> {
+        let pos = self.index.hierarchies.get(id)?.clone();
+        let (&index, parent) = pos.path.split_last()?;
+        let specification = &mut self.reqif.core_content.specifications[pos.specification];
+        let children = children_mut(specification, parent);
+        let hierarchy = children.remove(index);
+        self.index.unlink_hierarchy(&hierarchy);
+        self.index
+            .place_hierarchies(pos.specification, &mut parent.to_vec(), children, index);
+        Some(hierarchy)
+    }
+
+    /// Insert a SpecType with a new identifier at `index`, or append it when
+    /// past the end
+    pub fn insert_spec_type_at(&mut self, index: usize, spec_type: SpecType) {
+        let spec_types = &mut self.reqif.core_content.spec_types;
+        let index = index.min(spec_types.len());
+        spec_types.insert(index, spec_type);
+        self.index.index_spec_types(spec_types);
+    }
+
+    /// Replace the SpecType with the same identifier, returning it
+    pub fn replace_spec_type(&mut self, spec_type: SpecType) -> Option<SpecType> {
+        let i = *self.index.spec_types.get(&spec_type.ident.identifier)?;
+        let spec_types = &mut self.reqif.core_content.spec_types;
+        let old = std::mem::replace(&mut spec_types[i], spec_type);
+        self.index.index_spec_types(spec_types);
+        Some(old)
+    }
+
+    pub fn remove_spec_type(&mut self, id: &str) -> Option<SpecType> {
+        let i = *self.index.spec_types.get(id)?;
+        let spec_types = &mut self.reqif.core_content.spec_types;
+        let spec_type = spec_types.remove(i);
+        self.index.index_spec_types(spec_types);
+        Some(spec_type)
+    }
+
+    /// Position of SpecType `id` in the document
+    pub fn spec_type_index(&self, id: &str) -> Option<usize> {
+        self.index.spec_types.get(id).copied()
+    }
+
+    /// Tool extensions of the document; they are not indexed, so changing
+    /// them needs no reindexing
+    pub fn tool_extensions_mut(&mut self) -> &mut Vec<ToolExtension> {
+        &mut self.reqif.tool_extensions
+    }
+
+    /// Apply an arbitrary change to the document and rebuild all indexes.
+    /// Edits should use the targeted methods above, which only update the
+    /// index entries they touch.
+    pub fn modify<T>(&mut self, change: impl FnOnce(&mut ReqIF) -> T) -> T {
+        let result = change(&mut self.reqif);
+        self.reindex();
+        result
+    }
+
+    fn reindex(&mut self) {
+        self.index = Index::build(&self.reqif.core_content);
+    }
+}
+
+/// Record the positions of `items[from..]` in `map`, e.g. after an
+/// insertion or removal at `from` shifted them
+fn number<T>(
+    map: &mut HashMap<String, usize>,
+    items: &[T],
+    from: usize,
+    ident: impl Fn(&T) -> &Identifiable,
+) {
+    for (i, item) in items.iter().enumerate().skip(from) {
+        map.insert(ident(item).identifier.clone(), i);
+    }
+}
+
+/// Children of the SpecHierarchy at `path`, or of `specification` when
+/// `path` is empty
+fn children_mut<'a>(
+    specification: &'a mut Specification,
+    path: &[usize],
+) -> &'a mut Vec<SpecHierarchy> {
+    let mut children = &mut specification.children;
+    for &i in path {
+        children = &mut children[i].children;
+    }
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    fn relation(id: &str, source: &str, target: &str) -> SpecRelation {
+        SpecRelation {
+            ident: Identifiable::new(id),
+            spec_type: "srt".into(),
+            source: source.into(),
+            target: target.into(),
+            values: Vec::new(),
+            extras: Extras::default(),
+        }
+    }
+
+    #[test]
+    fn test_lookup_and_resolve() {
+        let store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let object = store.spec_object("req-002").unwrap();
+        let spec_type = store.spec_type(&object.spec_type).unwrap();
+        assert_eq!(spec_type.ident.identifier, "sot-requirement");
+
+        let definition = store.value_definition(&object.values[2]).unwrap();
+        assert_eq!(definition.ident.identifier, "ad-priority");
+        assert!(matches!(
+            store.definition_datatype(definition),
+            Some(DatatypeDefinition::Integer { .. })
+        ));
+
+        let hierarchy = store.hierarchy("sh-001").unwrap();
+        assert_eq!(
+            store.hierarchy_object(hierarchy).unwrap().ident.identifier,
+            "req-001"
+        );
+        assert_eq!(store.hierarchies_of("req-002").count(), 1);
+        assert!(store.spec_object("req-404").is_none());
+    }
+
+    #[test]
+    fn test_reverse_indexes_follow_edits() {
+        let mut store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        store.insert_spec_relation(relation("r1", "req-001", "req-003"));
+        store.insert_spec_relation(relation("r2", "req-002", "req-003"));
+        let sources: Vec<_> = store
+            .incoming_relations("req-003")
+            .filter_map(|r| store.relation_source(r))
+            .map(|o| o.ident.identifier.as_str())
+            .collect();
+        assert_eq!(sources, ["req-001", "req-002"]);
+        assert_eq!(store.outgoing_relations("req-001").count(), 1);
+
+        store.remove_spec_relation("r1");
+        assert_eq!(store.incoming_relations("req-003").count(), 1);
+        assert_eq!(store.outgoing_relations("req-001").count(), 0);
+        assert_eq!(store.spec_relation("r2").unwrap().source, "req-002");
+
+        store.remove_spec_object("req-001");
+        assert!(store.spec_object("req-001").is_none());
+        assert_eq!(
+            store.spec_object("req-003").unwrap().ident.identifier,
+            "req-003"
+        );
+
+        // Replacing keeps the relation's place; the reverse indexes move
+        store.insert_spec_relation(relation("r3", "req-002", "req-002"));
+        store.insert_spec_relation(relation("r2", "req-003", "req-002"));
+        let ids: Vec<_> = store
+            .content()
+            .spec_relations
+            .iter()
+            .map(|r| r.ident.identifier.as_str())
+            .collect();
+        assert_eq!(ids, ["r2", "r3"]);
+        assert_eq!(store.incoming_relations("req-003").count(), 0);
+        assert_eq!(store.incoming_relations("req-002").count(), 2);
+    }
+
+    #[test]
+    fn test_hierarchy_edits_keep_positions() {
+        let mut store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let mut hierarchy = store.remove_hierarchy("sh-001").unwrap();
+        assert!(store.hierarchy("sh-001").is_none());
+        assert_eq!(store.hierarchies_of("req-001").count(), 0);
+        assert_eq!(
+            store.hierarchy_placement("sh-002"),
+            Some(("spec-001", None, 0))
+        );
+
+        hierarchy.children.clear();
+        store
+            .insert_hierarchy("spec-001", Some("sh-003"), 0, hierarchy)
+            .unwrap();
+        assert_eq!(
+            store.hierarchy_placement("sh-001"),
+            Some(("spec-001", Some("sh-003"), 0))
+        );
+        assert_eq!(store.hierarchy("sh-001").unwrap().object, "req-001");
+        assert_eq!(store.hierarchies_of("req-001").count(), 1);
+        assert_eq!(
+            store.hierarchy_placement("sh-003"),
+            Some(("spec-001", None, 1))
+        );
+    }
+}
@@ -0,0 +1,659 @@
+// ReqIF tables - Windows of a Specification's SpecObjects as rows of display
+// values, so the frontend can virtualize large requirement tables
+
+use super::model::*;
+use super::store::DocumentStore;
+use crate::search::filter::{parse_filter, FilterError};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Reasons a table query is refused
+#[derive(Debug, Error)]
+pub enum TableError {
+    #[error("unknown specification '{0}'")]
+    UnknownSpecification(String),
+    #[error("unknown attribute definition '{0}'")]
+    UnknownColumn(String),
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+}
+
+/// Order rows by the values of one attribute definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub definition: String,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// Window of rows and the columns to fill in
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TableQuery {
+    pub specification: String,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: usize,
+    /// Attribute definitions to show; empty for every attribute of the spec
+    /// types used in the specification
+    #[serde(default)]
+    pub columns: Vec<String>,
+    /// Sort keys in priority order; without any, rows are in hierarchy order
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+    /// Filter query that rows must match, see `search::filter`
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Attribute definition whose values group the rows; groups come in
+    /// value order, ahead of the sort keys
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// SpecHierarchy entries whose sub-entries are left out
+    #[serde(default)]
+    pub collapsed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Column {
+    pub definition: String,
+    /// LONG-NAME of the definition, or its identifier
+    pub name: String,
+    /// Datatype kind, e.g. `STRING`, when the datatype resolves
+    pub datatype: Option<&'static str>,
+}
+
+/// One SpecHierarchy entry of the specification
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Row {
+    pub hierarchy: String,
+    pub object: String,
+    /// Nesting level, 0 for the specification's direct children
+    pub depth: usize,
+    /// Display text per column; `None` where the object has no value
+    pub cells: Vec<Option<String>>,
+    /// Display text of the grouping value, when grouping
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TablePage {
+    pub columns: Vec<Column>,
+    /// Number of rows in the whole table
+    pub total: usize,
+    pub offset: usize,
+    pub rows: Vec<Row>,
+}
+
+/// Rows `query.offset..query.offset + query.limit` of the table for
+/// `query.specification`
+pub fn table_page(store: &DocumentStore, query: &TableQuery) -> Result<TablePage, TableError> {
+    Ok(TableOrder::new(store, query)?.page(store, query))
+}
+
+/// Columns and rows of a table in display order, so that windows of it
+/// only fill in cells
+#[derive(Debug, Clone)]
+pub struct TableOrder {
+    /// Attribute definitions shown
+    columns: Vec<String>,
+    entries: Vec<OrderedEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct OrderedEntry {
+    hierarchy: String,
+    object: String,
+    depth: usize,
+}
+
+impl TableOrder {
+    /// Walk, filter and sort the table for `query`, ignoring its window
+    pub fn new(store: &DocumentStore, query: &TableQuery) -> Result<Self, TableError> {
+        let specification = store
+            .specification(&query.specification)
+            .ok_or_else(|| TableError::UnknownSpecification(query.specification.clone()))?;
+        let collapsed: HashSet<&str> = query.collapsed.iter().map(String::as_str).collect();
+        let mut entries = Vec::new();
+        collect_entries(store, &specification.children, 0, &collapsed, &mut entries);
+        if let Some(filter) = &query.filter {
+            let filter = parse_filter(filter)?;
+            filter.check(store)?;
+            let mut kept = Vec::with_capacity(entries.len());
+            for entry in entries {
+                if filter.matches(store, entry.object)? {
+                    kept.push(entry);
+                }
+            }
+            entries = kept;
+        }
+
+        let columns = if query.columns.is_empty() {
+            default_columns(store, &entries)
+                .into_iter()
+                .map(|d| d.ident.identifier.clone())
+                .collect()
+        } else {
+            for id in &query.columns {
+                if store.attribute_definition(id).is_none() {
+                    return Err(TableError::UnknownColumn(id.clone()));
+                }
+            }
+            query.columns.clone()
+        };
+        let group = query.group_by.as_ref().map(|definition| SortKey {
+            definition: definition.clone(),
+            descending: false,
+        });
+        let keys: Vec<&SortKey> = group.iter().chain(&query.sort).collect();
+        for key in &keys {
+            if store.attribute_definition(&key.definition).is_none() {
+                return Err(TableError::UnknownColumn(key.definition.clone()));
+            }
+        }
+
+        let mut keyed: Vec<(Vec<Option<SortValue>>, Entry)> = entries
+            .into_iter()
+            .map(|entry| {
+                let values = keys
+                    .iter()
+                    .map(|key| {
+                        store
+                            .value_or_default(entry.object, &key.definition)
+                            .map(|v| SortValue::of(store, v))
+                    })
+                    .collect();
+                (values, entry)
+            })
+            .collect();
+        if !keys.is_empty() {
+            // Stable, so rows with equal keys keep their hierarchy order
+            keyed.sort_by(|(a, _), (b, _)| {
+                keys.iter()
+                    .zip(a.iter().zip(b))
+                    .map(|(key, (a, b))| {
+                        let order = compare_sort_values(a.as_ref(), b.as_ref());
+                        if key.descending {
+                            order.reverse()
+                        } else {
+                            order
+                        }
+                    })
+                    .find(|order| order.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        let entries = keyed
+            .into_iter()
+            .map(|(_, entry)| OrderedEntry {
+                hierarchy: entry.hierarchy.ident.identifier.clone(),
+                object: entry.object.ident.identifier.clone(),
+                depth: entry.depth,
+            })
+            .collect();
+        Ok(Self { columns, entries })
+    }
+
+    /// Rows `query.offset..query.offset + query.limit`, with their cells
+    pub fn page(&self, store: &DocumentStore, query: &TableQuery) -> TablePage {
+        let definitions: Vec<&AttributeDefinition> = self
+            .columns
+            .iter()
+            .filter_map(|id| store.attribute_definition(id))
+            .collect();
+        let rows = self
+            .entries
+            .iter()
+            .skip(query.offset)
+            .take(query.limit)
+            .map(|entry| {
+                let object = store.spec_object(&entry.object);
+                let value = |definition: &str| {
+                    object
+                        .and_then(|object| store.value_or_default(object, definition))
+                        .map(|v| display_value(store, v))
+                };
+                Row {
+                    hierarchy: entry.hierarchy.clone(),
+                    object: entry.object.clone(),
+                    depth: entry.depth,
+                    cells: definitions
+                        .iter()
+                        .map(|d| value(&d.ident.identifier))
+                        .collect(),
+                    group: query
+                        .group_by
+                        .as_ref()
+                        .map(|definition| value(definition).unwrap_or_default()),
+                }
+            })
+            .collect();
+        let columns = definitions
+            .iter()
+            .map(|d| Column {
+                definition: d.ident.identifier.clone(),
+                name: d
+                    .ident
+                    .long_name
+                    .clone()
+                    .unwrap_or_else(|| d.ident.identifier.clone()),
+                datatype: store.definition_datatype(d).map(datatype_kind),
+            })
+            .collect();
+        TablePage {
+            columns,
+            total: self.entries.len(),
+            offset: query.offset,
+            rows,
+        }
+    }
+}
+
+/// Order of the last table queried, kept while the document revision and
+/// the query apart from its window stay the same
+#[derive(Debug, Default)]
+pub struct TableCache {
+    /// Document identifier and revision, and the query without its window
+    key: Option<(u64, u64, TableQuery)>,
+    order: Option<TableOrder>,
+}
+
+impl TableCache {
+    /// Page for `query` on revision `revision` of document `document`
+    pub fn page(
+        &mut self,
+        store: &DocumentStore,
+        document: u64,
+        revision: u64,
+        query: &TableQuery,
+    ) -> Result<TablePage, TableError> {
+        let key = Some((
+            document,
+            revision,
+            TableQuery {
+                offset: 0,
+                limit: 0,
+                ..query.clone()
+            },
+        ));
+        let order = match self.order.take() {
+            Some(order) if self.key == key => order,
+            _ => TableOrder::new(store, query)?,
+        };
+        let page = order.page(store, query);
+        self.key = key;
+        self.order = Some(order);
+        Ok(page)
+    }
+}
+
+/// Hierarchy entry whose object resolves
+struct Entry<'a> {
+    hierarchy: &'a SpecHierarchy,
+    object: &'a SpecObject,
+    depth: usize,
+}
+
+/// Depth-first walk of `children`; entries with a dangling object and the
+/// sub-entries of collapsed entries are left out
+fn collect_entries<'a>(
+    store: &'a DocumentStore,
+    children: &'a [SpecHierarchy],
+    depth: usize,
+    collapsed: &HashSet<&str>,
+    entries: &mut Vec<Entry<'a>>,
+) {
+    for hierarchy in children {
+        if let Some(object) = store.hierarchy_object(hierarchy) {
+            entries.push(Entry {
+                hierarchy,
+                object,
+                depth,
+            });
+        }
+        if !collapsed.contains(hierarchy.ident.identifier.as_str()) {
+            collect_entries(store, &hierarchy.children, depth + 1, collapsed, entries);
+        }
+    }
+}
+
+/// Attribute definitions of the spec types used by `entries`, in the order
+/// the types first appear
+fn default_columns<'a>(
+    store: &'a DocumentStore,
+    entries: &[Entry],
+) -> Vec<&'a AttributeDefinition> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter(|e| seen.insert(e.object.spec_type.as_str()))
+        .filter_map(|e| store.spec_type(&e.object.spec_type))
+        .flat_map(|t| &t.spec_attributes)
+        .collect()
+}
+
+/// What a value sorts by, worked out once per row
+#[derive(Debug)]
+enum SortValue {
+    Boolean(bool),
+    Date(DateValue),
+    Integer(i64),
+    Real(f64),
+    /// Positions among the values the datatype declares
+    Enumeration(Vec<usize>),
+    Text(String),
+}
+
+impl SortValue {
+    fn of(store: &DocumentStore, value: &AttributeValue) -> Self {
+        match value {
+            AttributeValue::Boolean { value, .. } => SortValue::Boolean(*value),
+            AttributeValue::Date { value, .. } => SortValue::Date(*value),
+            AttributeValue::Integer { value, .. } => SortValue::Integer(*value),
+            AttributeValue::Real { value, .. } => SortValue::Real(*value),
+            AttributeValue::Enumeration { .. } => {
+                SortValue::Enumeration(enum_positions(store, value))
+            }
+            other => SortValue::Text(sort_text(store, other)),
+        }
+    }
+
+    /// Order between values of different kinds, which only a malformed
+    /// document mixes under one attribute
+    fn rank(&self) -> u8 {
+        match self {
+            SortValue::Boolean(_) => 0,
+            SortValue::Date(_) => 1,
+            SortValue::Integer(_) => 2,
+            SortValue::Real(_) => 3,
+            SortValue::Enumeration(_) => 4,
+            SortValue::Text(_) => 5,
+        }
+    }
+}
+
+/// Order of two values of the same attribute; missing values sort last
+fn compare_sort_values(a: Option<&SortValue>, b: Option<&SortValue>) -> Ordering {
+    use SortValue::*;
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(Boolean(a)), Some(Boolean(b))) => a.cmp(b),
+        (Some(Date(a)), Some(Date(b))) => a.cmp(b),
+        (Some(Integer(a)), Some(Integer(b))) => a.cmp(b),
+        (Some(Real(a)), Some(Real(b))) => a.total_cmp(b),
+        (Some(Enumeration(a)), Some(Enumeration(b))) => a.cmp(b),
+        (Some(Text(a)), Some(Text(b))) => a.cmp(b),
+        (Some(a), Some(b)) => a.rank().cmp(&b.rank()),
+    }
+}
+
+/// Positions of an enumeration value's entries among the values its
+/// datatype declares, in that order; undeclared entries come last
+fn enum_positions(store: &DocumentStore, value: &AttributeValue) -> Vec<usize> {
+    let AttributeValue::Enumeration {
+        definition, values, ..
+    } = value
+    else {
+        return Vec::new();
+    };
+    let specified = match store
+        .attribute_definition(definition)
+        .and_then(|d| store.definition_datatype(d))
+    {
+        Some(DatatypeDefinition::Enumeration { values, .. }) => values.as_slice(),
+        _ => &[],
+    };
+    let mut positions: Vec<usize> = values
+        .iter()
+        .map(|id| {
+            specified
+                .iter()
+                .position(|v| &v.ident.identifier == id)
+                .unwrap_or(usize::MAX)
+        })
+        .collect();
+    positions.sort_unstable();
+    positions
+}
+
+fn sort_text(store: &DocumentStore, value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::String { value, .. } => value.to_lowercase(),
+        AttributeValue::XHTML { value, .. } => xhtml_text(value).to_lowercase(),
+        other => display_value(store, other).to_lowercase(),
+    }
+}
+
+/// Text shown in a table cell for `value`
+pub fn display_value(store: &DocumentStore, value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::Boolean { value, .. } => value.to_string(),
+        AttributeValue::Date { value, .. } => value.to_string(),
+        AttributeValue::Integer { value, .. } => value.to_string(),
+        AttributeValue::Real { value, .. } => value.to_string(),
+        AttributeValue::String { value, .. } => value.clone(),
+        AttributeValue::Enumeration {
+            definition, values, ..
+        } => {
+            let specified = store
+                .attribute_definition(definition)
+                .and_then(|d| store.definition_datatype(d));
+            values
+                .iter()
+                .map(|id| match specified {
+                    Some(DatatypeDefinition::Enumeration { values, .. }) => values
+                        .iter()
+                        .find(|v| &v.ident.identifier == id)
+                        .and_then(|v| v.ident.long_name.clone())
+                        .unwrap_or_else(|| id.clone()),
+                    _ => id.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        AttributeValue::XHTML { value, .. } => xhtml_text(value),
+    }
+}
+
+/// Text content of an XHTML fragment with whitespace collapsed
+pub fn xhtml_text(xhtml: &str) -> String {
+    let mut reader = Reader::from_str(xhtml);
+    let mut text = String::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Text(t)) => match t.unescape() {
+                Ok(t) => text.push_str(&t),
+                Err(_) => text.push_str(&String::from_utf8_lossy(&t)),
+            },
+            Ok(Event::CData(c)) => text.push_str(&String::from_utf8_lossy(&c)),
+            // Keep words of adjacent block elements apart; inline elements
+            // may split a word
+            Ok(Event::Start(e) | Event::Empty(e)) if is_block(e.local_name().as_ref()) => {
+                text.push(' ')
+            }
+            Ok(Event::End(e)) if is_block(e.local_name().as_ref()) => text.push(' '),
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether an XHTML element, by local name, breaks the text flow
+fn is_block(name: &[u8]) -> bool {
+    const BLOCKS: &[&str] = &[
+        "address",
+        "blockquote",
+        "br",
+        "caption",
+        "dd",
+        "div",
+        "dl",
+        "dt",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "hr",
+        "li",
+        "object",
+        "ol",
+        "p",
+        "pre",
+        "table",
+        "td",
+        "th",
+        "tr",
+        "ul",
+    ];
+    BLOCKS
+        .iter()
+        .any(|b| b.as_bytes().eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    fn query(columns: &[&str], sort: Vec<SortKey>) -> TableQuery {
+        TableQuery {
+            specification: "spec-001".into(),
+            offset: 0,
+            limit: 10,
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            sort,
+            filter: None,
+            group_by: None,
+            collapsed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_page_in_hierarchy_order_with_default_columns() {
+        let store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let page = table_page(&store, &query(&[], Vec::new())).unwrap();
+        let names: Vec<&str> = page.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Title", "Description", "Priority"]);
+        assert_eq!(page.columns[2].datatype, Some("INTEGER"));
+        assert_eq!(page.total, 3);
+        let objects: Vec<&str> = page.rows.iter().map(|r| r.object.as_str()).collect();
+        assert_eq!(objects, ["req-001", "req-002", "req-003"]);
+        assert_eq!(
+            page.rows[0].cells[0].as_deref(),
+            Some("System shall start within 5 seconds")
+        );
+        assert!(!page.rows[0].cells[1].as_deref().unwrap().contains('<'));
+
+        let mut window = query(&["ad-priority"], Vec::new());
+        window.offset = 1;
+        window.limit = 1;
+        let page = table_page(&store, &window).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.rows.len(), 1);
+        assert_eq!(page.rows[0].object, "req-002");
+        assert_eq!(page.rows[0].cells, [Some("9".to_string())]);
+    }
+
+    #[test]
+    fn test_sort_keys_and_unknown_columns() {
+        let store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let by_priority = vec![SortKey {
+            definition: "ad-priority".into(),
+            descending: false,
+        }];
+        let page = table_page(&store, &query(&["ad-title"], by_priority)).unwrap();
+        let objects: Vec<&str> = page.rows.iter().map(|r| r.object.as_str()).collect();
+        assert_eq!(objects, ["req-003", "req-002", "req-001"]);
+
+        assert!(matches!(
+            table_page(&store, &query(&["ad-nope"], Vec::new())),
+            Err(TableError::UnknownColumn(_))
+        ));
+        let mut missing = query(&[], Vec::new());
+        missing.specification = "spec-404".into();
+        assert!(matches!(
+            table_page(&store, &missing),
+            Err(TableError::UnknownSpecification(_))
+        ));
+    }
+
+    #[test]
+    fn test_cached_order_lasts_until_the_revision_changes() {
+        let mut store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let mut cache = TableCache::default();
+        let by_priority = vec![SortKey {
+            definition: "ad-priority".into(),
+            descending: false,
+        }];
+        let mut window = query(&["ad-title"], by_priority);
+        window.limit = 1;
+        let first = |page: TablePage| page.rows[0].object.clone();
+        assert_eq!(first(cache.page(&store, 1, 0, &window).unwrap()), "req-003");
+
+        let lowest = AttributeValue::Integer {
+            definition: "ad-priority".into(),
+            value: 0,
+            extras: Extras::default(),
+        };
+        crate::reqif::edit::set_value(&mut store, "req-001", lowest).unwrap();
+        window.offset = 1;
+        let page = cache.page(&store, 1, 0, &window).unwrap();
+        assert_eq!((page.total, first(page)), (3, "req-002".to_string()));
+        window.offset = 0;
+        assert_eq!(first(cache.page(&store, 1, 1, &window).unwrap()), "req-001");
+    }
+
+    #[test]
+    fn test_filter_and_group_rows() {
+        let traced = include_str!("../../../tests/fixtures/traced.reqif");
+        let store = DocumentStore::new(parser::parse_str(traced).unwrap());
+        let mut grouped = query(&["ad-sys-title"], Vec::new());
+        grouped.specification = "spec-sys".into();
+        grouped.filter = Some("Priority > 5".into());
+        grouped.group_by = Some("ad-sys-status".into());
+        let page = table_page(&store, &grouped).unwrap();
+        let rows: Vec<(&str, Option<&str>)> = page
+            .rows
+            .iter()
+            .map(|r| (r.object.as_str(), r.group.as_deref()))
+            .collect();
+        assert_eq!(
+            rows,
+            // Groups in the order the enumeration declares its values
+            [
+                ("sys-002", Some("Draft")),
+                ("sys-004", Some("Draft")),
+                ("sys-001", Some("Approved"))
+            ]
+        );
+
+        grouped.filter = Some("Priority >".into());
+        assert!(matches!(
+            table_page(&store, &grouped),
+            Err(TableError::Filter(_))
+        ));
+    }
+
+    #[test]
+    fn test_xhtml_text() {
+        assert_eq!(
+            xhtml_text(
+                "<xhtml:div><xhtml:p>Start &amp; stop</xhtml:p><xhtml:p>fast</xhtml:p></xhtml:div>"
+            ),
+            "Start & stop fast"
+        );
+        assert_eq!(
+            xhtml_text("<xhtml:p>re<xhtml:b>quire</xhtml:b>ment<xhtml:br/>met</xhtml:p>"),
+            "requirement met"
+        );
+    }
+}
@@ -0,0 +1,1124 @@
+// ReqIF undo - Reversible edits to a document, recorded in an undo/redo
+// journal
+
+use super::edit::{self, EditError};
+use super::model::*;
+use super::store::DocumentStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Where a SpecHierarchy goes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    pub specification: String,
+    /// Parent SpecHierarchy; `None` for a top-level entry
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Position among the parent's children; past the end appends
+    pub index: usize,
+}
+
+/// A change to a document as requested by the user. Edits are checked
+/// before anything changes, and each becomes one undo step unless grouped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Edit {
+    SetValue {
+        object: String,
+        value: AttributeValue,
+    },
+    ClearValue {
+        object: String,
+        definition: String,
+    },
+    CreateObject {
+        object: SpecObject,
+    },
+    /// Delete a SpecObject together with the relations linking it and the
+    /// hierarchies placing it; their sub-entries move up in their place
+    DeleteObject {
+        object: String,
+    },
+    InsertHierarchy {
+        hierarchy: SpecHierarchy,
+        placement: Placement,
+    },
+    RemoveHierarchy {
+        hierarchy: String,
+    },
+    /// Move a SpecHierarchy with its sub-entries. The index counts the new
+    /// siblings without the moved entry.
+    MoveHierarchy {
+        hierarchy: String,
+        placement: Placement,
+    },
+    AddRelation {
+        relation: SpecRelation,
+    },
+    /// Remove a SpecRelation, and its listings in relation groups
+    RemoveRelation {
+        relation: String,
+    },
+    /// Add a spec type, or replace the one with the same identifier. The
+    /// kind of a type in use and definitions that have values are kept.
+    PutSpecType {
+        spec_type: SpecType,
+    },
+    RemoveSpecType {
+        spec_type: String,
+    },
+}
+
+impl Edit {
+    /// Description shown for the undo step
+    pub fn label(&self) -> &'static str {
+        match self {
+            Edit::SetValue { .. } => "Set value",
+            Edit::ClearValue { .. } => "Clear value",
+            Edit::CreateObject { .. } => "Create object",
+            Edit::DeleteObject { .. } => "Delete object",
+            Edit::InsertHierarchy { .. } => "Insert hierarchy",
+            Edit::RemoveHierarchy { .. } => "Remove hierarchy",
+            Edit::MoveHierarchy { .. } => "Move hierarchy",
+            Edit::AddRelation { .. } => "Add relation",
+            Edit::RemoveRelation { .. } => "Remove relation",
+            Edit::PutSpecType { .. } => "Edit spec type",
+            Edit::RemoveSpecType { .. } => "Remove spec type",
+        }
+    }
+}
+
+/// Labels of the steps that undo and redo would revert or repeat
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct History {
+    pub undo: Option<String>,
+    pub redo: Option<String>,
+}
+
+/// What edits, undos and redos have touched, e.g. to update derived data
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changed {
+    /// SpecObjects whose values were set, or that were added or removed
+    pub objects: BTreeSet<String>,
+    /// SpecRelations that were added or removed
+    pub relations: BTreeSet<String>,
+    /// Spec types that were edited, added or removed
+    pub spec_types: BTreeSet<String>,
+}
+
+impl Changed {
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty() && self.relations.is_empty() && self.spec_types.is_empty()
+    }
+
+    fn record(&mut self, changes: &[Change]) {
+        for change in changes {
+            match change {
+                Change::Values { object, .. } | Change::RemoveObject { object } => {
+                    self.objects.insert(object.clone());
+                }
+                Change::InsertObject { object, .. } => {
+                    self.objects.insert(object.ident.identifier.clone());
+                }
+                Change::RemoveRelation { relation } => {
+                    self.relations.insert(relation.clone());
+                }
+                Change::InsertRelation { relation, .. } => {
+                    self.relations.insert(relation.ident.identifier.clone());
+                }
+                Change::RemoveSpecType { spec_type } => {
+                    self.spec_types.insert(spec_type.clone());
+                }
+                Change::InsertSpecType { spec_type, .. }
+                | Change::ReplaceSpecType { spec_type } => {
+                    self.spec_types.insert(spec_type.ident.identifier.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks of a document
+#[derive(Debug, Default)]
+pub struct Journal {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// Step collecting the edits of the open group
+    group: Option<Step>,
+    /// Nesting depth of `begin_group` calls
+    depth: usize,
+    /// Touched since the last `take_changed`
+    changed: Changed,
+}
+
+/// Changes reverting one step, to be applied last to first
+#[derive(Debug)]
+struct Step {
+    label: String,
+    changes: Vec<Change>,
+}
+
+impl Journal {
+    /// Check and apply `edit`, recording how to revert it. A failing edit
+    /// leaves the document unchanged.
+    pub fn apply(&mut self, store: &mut DocumentStore, edit: Edit) -> Result<(), EditError> {
+        let label = edit.label();
+        let mut changes = Vec::new();
+        if let Err(e) = perform(store, edit, &mut changes) {
+            // Revert whatever part of the edit was applied
+            let _ = replay(store, changes);
+            return Err(e);
+        }
+        self.redo.clear();
+        self.changed.record(&changes);
+        match &mut self.group {
+            Some(group) => group.changes.extend(changes),
+            None => self.undo.push(Step {
+                label: label.to_string(),
+                changes,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Collect the following edits into one undo step until the matching
+    /// `end_group`. Groups nest; the outermost label is kept.
+    pub fn begin_group(&mut self, label: &str) {
+        if self.depth == 0 {
+            self.group = Some(Step {
+                label: label.to_string(),
+                changes: Vec::new(),
+            });
+        }
+        self.depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth == 0 {
+            if let Some(step) = self.group.take().filter(|s| !s.changes.is_empty()) {
+                self.undo.push(step);
+            }
+        }
+    }
+
+    /// Revert the last step, closing any open group first. Returns the
+    /// step's label, or `None` when there is nothing to undo.
+    pub fn undo(&mut self, store: &mut DocumentStore) -> Result<Option<String>, EditError> {
+        self.close_groups();
+        let Some(step) = self.undo.pop() else {
+            return Ok(None);
+        };
+        let changes = self.replay_step(store, step.changes)?;
+        self.redo.push(Step {
+            label: step.label.clone(),
+            changes,
+        });
+        Ok(Some(step.label))
+    }
+
+    /// Repeat the last undone step
+    pub fn redo(&mut self, store: &mut DocumentStore) -> Result<Option<String>, EditError> {
+        self.close_groups();
+        let Some(step) = self.redo.pop() else {
+            return Ok(None);
+        };
+        let changes = self.replay_step(store, step.changes)?;
+        self.undo.push(Step {
+            label: step.label.clone(),
+            changes,
+        });
+        Ok(Some(step.label))
+    }
+
+    /// What has been touched since the last call
+    pub fn take_changed(&mut self) -> Changed {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn history(&self) -> History {
+        History {
+            undo: self.undo.last().map(|s| s.label.clone()),
+            redo: self.redo.last().map(|s| s.label.clone()),
+        }
+    }
+
+    fn close_groups(&mut self) {
+        while self.depth > 0 {
+            self.end_group();
+        }
+    }
+
+    fn replay_step(
+        &mut self,
+        store: &mut DocumentStore,
+        changes: Vec<Change>,
+    ) -> Result<Vec<Change>, EditError> {
+        let inverses = replay(store, changes).map_err(|e| {
+            // The document no longer matches the journal
+            self.undo.clear();
+            self.redo.clear();
+            e
+        })?;
+        self.changed.record(&inverses);
+        Ok(inverses)
+    }
+}
+
+/// Apply `changes` last to first, returning the changes that revert them
+fn replay(store: &mut DocumentStore, changes: Vec<Change>) -> Result<Vec<Change>, EditError> {
+    let mut inverses = Vec::with_capacity(changes.len());
+    for change in changes.into_iter().rev() {
+        inverses.push(change.apply(store)?);
+    }
+    Ok(inverses)
+}
+
+/// Check `edit` and apply it as a series of changes, pushing the change
+/// reverting each onto `inverses`
+fn perform(
+    store: &mut DocumentStore,
+    edit: Edit,
+    inverses: &mut Vec<Change>,
+) -> Result<(), EditError> {
+    let mut run = |store: &mut DocumentStore, change: Change| -> Result<Change, EditError> {
+        let inverse = change.apply(store)?;
+        inverses.push(inverse.clone());
+        Ok(inverse)
+    };
+    match edit {
+        Edit::SetValue { object, value } => {
+            let values = current_values(store, &object)?;
+            edit::set_value(store, &object, value)?;
+            inverses.push(Change::Values { object, values });
+        }
+        Edit::ClearValue { object, definition } => {
+            let values = current_values(store, &object)?;
+            edit::clear_value(store, &object, &definition)?;
+            inverses.push(Change::Values { object, values });
+        }
+        Edit::CreateObject { object } => {
+            if store.spec_object(&object.ident.identifier).is_some() {
+                return Err(EditError::DuplicateIdentifier(object.ident.identifier));
+            }
+            check_kind(store, &object.spec_type, SpecTypeKind::SpecObjectType)?;
+            check_values(
+                store,
+                &object.ident.identifier,
+                &object.spec_type,
+                &object.values,
+            )?;
+            let index = store.content().spec_objects.len();
+            run(store, Change::InsertObject { index, object })?;
+        }
+        Edit::DeleteObject { object } => {
+            if store.spec_object(&object).is_none() {
+                return Err(EditError::UnknownObject(object));
+            }
+            let mut relations: Vec<String> = store
+                .outgoing_relations(&object)
+                .chain(store.incoming_relations(&object))
+                .map(|r| r.ident.identifier.clone())
+                .collect();
+            // A self-relation is both outgoing and incoming
+            relations.sort();
+            relations.dedup();
+            let hierarchies: Vec<String> = store
+                .hierarchies_of(&object)
+                .map(|h| h.ident.identifier.clone())
+                .collect();
+            for relation in relations {
+                for change in relation_removal(store, relation) {
+                    run(store, change)?;
+                }
+            }
+            for hierarchy in hierarchies {
+                // Sub-entries move up to follow the entry, which then goes
+                let children: Vec<String> = store
+                    .hierarchy(&hierarchy)
+                    .ok_or_else(|| EditError::UnknownHierarchy(hierarchy.clone()))?
+                    .children
+                    .iter()
+                    .map(|c| c.ident.identifier.clone())
+                    .collect();
+                let (specification, parent, index) = store
+                    .hierarchy_placement(&hierarchy)
+                    .map(|(s, p, i)| (s.to_string(), p.map(str::to_string), i))
+                    .ok_or_else(|| EditError::UnknownHierarchy(hierarchy.clone()))?;
+                for (offset, child) in children.into_iter().enumerate() {
+                    if let Change::InsertHierarchy { hierarchy, .. } =
+                        run(store, Change::RemoveHierarchy { hierarchy: child })?
+                    {
+                        let placement = Placement {
+                            specification: specification.clone(),
+                            parent: parent.clone(),
+                            index: index + 1 + offset,
+                        };
+                        run(
+                            store,
+                            Change::InsertHierarchy {
+                                placement,
+                                hierarchy,
+                            },
+                        )?;
+                    }
+                }
+                run(store, Change::RemoveHierarchy { hierarchy })?;
+            }
+            run(store, Change::RemoveObject { object })?;
+        }
+        Edit::InsertHierarchy {
+            hierarchy,
+            placement,
+        } => {
+            if store.hierarchy(&hierarchy.ident.identifier).is_some() {
+                return Err(EditError::DuplicateIdentifier(hierarchy.ident.identifier));
+            }
+            run(
+                store,
+                Change::InsertHierarchy {
+                    placement,
+                    hierarchy,
+                },
+            )?;
+        }
+        Edit::RemoveHierarchy { hierarchy } => {
+            if store.hierarchy(&hierarchy).is_none() {
+                return Err(EditError::UnknownHierarchy(hierarchy));
+            }
+            run(store, Change::RemoveHierarchy { hierarchy })?;
+        }
+        Edit::MoveHierarchy {
+            hierarchy,
+            placement,
+        } => {
+            let node = store
+                .hierarchy(&hierarchy)
+                .ok_or_else(|| EditError::UnknownHierarchy(hierarchy.clone()))?;
+            if let Some(parent) = &placement.parent {
+                if contains_hierarchy(node, parent) {
+                    return Err(EditError::InvalidMove(hierarchy));
+                }
+            }
+            if let Change::InsertHierarchy { hierarchy, .. } =
+                run(store, Change::RemoveHierarchy { hierarchy })?
+            {
+                run(
+                    store,
+                    Change::InsertHierarchy {
+                        placement,
+                        hierarchy,
+                    },
+                )?;
+            }
+        }
+        Edit::AddRelation { relation } => {
+            if store.spec_relation(&relation.ident.identifier).is_some() {
+                return Err(EditError::DuplicateIdentifier(relation.ident.identifier));
+            }
+            for end in [&relation.source, &relation.target] {
+                if store.spec_object(end).is_none() {
+                    return Err(EditError::UnknownObject(end.clone()));
+                }
+            }
+            check_kind(store, &relation.spec_type, SpecTypeKind::SpecRelationType)?;
+            check_values(
+                store,
+                &relation.ident.identifier,
+                &relation.spec_type,
+                &relation.values,
+            )?;
+            let index = store.content().spec_relations.len();
+            run(store, Change::InsertRelation { index, relation })?;
+        }
+        Edit::RemoveRelation { relation } => {
+            if store.spec_relation(&relation).is_none() {
+                return Err(EditError::UnknownRelation(relation));
+            }
+            for change in relation_removal(store, relation) {
+                run(store, change)?;
+            }
+        }
+        Edit::PutSpecType { spec_type } => {
+            check_spec_type(store, &spec_type)?;
+            if store.spec_type(&spec_type.ident.identifier).is_some() {
+                run(store, Change::ReplaceSpecType { spec_type })?;
+            } else {
+                let index = store.content().spec_types.len();
+                run(store, Change::InsertSpecType { index, spec_type })?;
+            }
+        }
+        Edit::RemoveSpecType { spec_type } => {
+            if store.spec_type(&spec_type).is_none() {
+                return Err(EditError::UnknownSpecType(spec_type));
+            }
+            if spec_type_in_use(store.content(), &spec_type) {
+                return Err(EditError::SpecTypeInUse(spec_type));
+            }
+            run(store, Change::RemoveSpecType { spec_type })?;
+        }
+    }
+    Ok(())
+}
+
+fn current_values(store: &DocumentStore, object: &str) -> Result<Vec<AttributeValue>, EditError> {
+    store
+        .spec_object(object)
+        .map(|o| o.values.clone())
+        .ok_or_else(|| EditError::UnknownObject(object.to_string()))
+}
+
+/// Check that spec type `id` exists and types elements of kind `expected`
+fn check_kind(store: &DocumentStore, id: &str, expected: SpecTypeKind) -> Result<(), EditError> {
+    let spec_type = store
+        .spec_type(id)
+        .ok_or_else(|| EditError::UnknownSpecType(id.to_string()))?;
+    if spec_type.kind != expected {
+        return Err(EditError::WrongSpecTypeKind {
+            spec_type: id.to_string(),
+            expected,
+        });
+    }
+    Ok(())
+}
+
+/// Check the values of a new element as if each were set on its own
+fn check_values(
+    store: &DocumentStore,
+    owner: &str,
+    spec_type: &str,
+    values: &[AttributeValue],
+) -> Result<(), EditError> {
+    values
+        .iter()
+        .try_for_each(|value| edit::check_new_value(store, owner, spec_type, value))
+}
+
+/// Changes removing SpecRelation `relation` along with its listings in
+/// relation groups
+fn relation_removal(store: &DocumentStore, relation: String) -> Vec<Change> {
+    let mut changes: Vec<Change> = store
+        .groups_of(&relation)
+        .into_iter()
+        .map(|group| Change::RemoveGroupRelation {
+            group,
+            relation: relation.clone(),
+        })
+        .collect();
+    changes.push(Change::RemoveRelation { relation });
+    changes
+}
+
+/// Whether `id` is `node` or one of its sub-entries
+fn contains_hierarchy(node: &SpecHierarchy, id: &str) -> bool {
+    node.ident.identifier == id || node.children.iter().any(|c| contains_hierarchy(c, id))
+}
+
+/// Check that `spec_type` can be put in place of the spec type with its
+/// identifier, if any: its datatypes resolve, and it neither changes the kind
+/// of a type in use nor drops definitions that still have values
+fn check_spec_type(store: &DocumentStore, spec_type: &SpecType) -> Result<(), EditError> {
+    let id = &spec_type.ident.identifier;
+    for definition in &spec_type.spec_attributes {
+        if store.datatype(&definition.datatype_ref).is_none() {
+            return Err(EditError::UnknownDatatype {
+                definition: definition.ident.identifier.clone(),
+                datatype: definition.datatype_ref.clone(),
+            });
+        }
+        let owner = store.content().spec_types.iter().find(|t| {
+            t.spec_attributes
+                .iter()
+                .any(|d| d.ident.identifier == definition.ident.identifier)
+        });
+        if owner.is_some_and(|t| t.ident.identifier != *id) {
+            return Err(EditError::DuplicateIdentifier(
+                definition.ident.identifier.clone(),
+            ));
+        }
+    }
+    let Some(current) = store.spec_type(id) else {
+        return Ok(());
+    };
+    if current.kind != spec_type.kind && spec_type_in_use(store.content(), id) {
+        return Err(EditError::SpecTypeInUse(id.clone()));
+    }
+    for definition in &current.spec_attributes {
+        let kept = spec_type
+            .spec_attributes
+            .iter()
+            .any(|d| d.ident.identifier == definition.ident.identifier);
+        if !kept && definition_has_values(store.content(), &definition.ident.identifier) {
+            return Err(EditError::DefinitionInUse(
+                definition.ident.identifier.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether any element holds a value for attribute definition `id`
+fn definition_has_values(content: &CoreContent, id: &str) -> bool {
+    let held = |values: &[AttributeValue]| values.iter().any(|v| v.definition() == id);
+    content.spec_objects.iter().any(|o| held(&o.values))
+        || content.spec_relations.iter().any(|r| held(&r.values))
+        || content.specifications.iter().any(|s| held(&s.values))
+}
+
+fn spec_type_in_use(content: &CoreContent, id: &str) -> bool {
+    content.spec_objects.iter().any(|o| o.spec_type == id)
+        || content.spec_relations.iter().any(|r| r.spec_type == id)
+        || content.specifications.iter().any(|s| s.spec_type == id)
+        || content
+            .spec_relation_groups
+            .iter()
+            .any(|g| g.spec_type == id)
+}
+
+/// Unchecked change to a document that yields its own inverse when applied
+#[derive(Debug, Clone)]
+enum Change {
+    Values {
+        object: String,
+        values: Vec<AttributeValue>,
+    },
+    InsertObject {
+        index: usize,
+        object: SpecObject,
+    },
+    RemoveObject {
+        object: String,
+    },
+    InsertHierarchy {
+        placement: Placement,
+        hierarchy: SpecHierarchy,
+    },
+    RemoveHierarchy {
+        hierarchy: String,
+    },
+    InsertRelation {
+        index: usize,
+        relation: SpecRelation,
+    },
+    RemoveRelation {
+        relation: String,
+    },
+    /// List a SpecRelation in a relation group
+    InsertGroupRelation {
+        group: String,
+        index: usize,
+        relation: String,
+    },
+    RemoveGroupRelation {
+        group: String,
+        relation: String,
+    },
+    InsertSpecType {
+        index: usize,
+        spec_type: SpecType,
+    },
+    RemoveSpecType {
+        spec_type: String,
+    },
+    ReplaceSpecType {
+        spec_type: SpecType,
+    },
+}
+
+impl Change {
+    /// Apply to `store`, returning the change that reverts it
+    fn apply(self, store: &mut DocumentStore) -> Result<Change, EditError> {
+        Ok(match self {
+            Change::Values { object, values } => {
+                let current = store
+                    .values_mut(&object)
+                    .ok_or_else(|| EditError::UnknownObject(object.clone()))?;
+                let values = std::mem::replace(current, values);
+                Change::Values { object, values }
+            }
+            Change::InsertObject { index, object } => {
+                let id = object.ident.identifier.clone();
+                store.insert_spec_object_at(index, object);
+                Change::RemoveObject { object: id }
+            }
+            Change::RemoveObject { object } => {
+                let index = store
+                    .spec_object_index(&object)
+                    .ok_or_else(|| EditError::UnknownObject(object.clone()))?;
+                let object = store
+                    .remove_spec_object(&object)
+                    .ok_or(EditError::UnknownObject(object))?;
+                Change::InsertObject { index, object }
+            }
+            Change::InsertHierarchy {
+                placement,
+                hierarchy,
+            } => {
+                let id = hierarchy.ident.identifier.clone();
+                if store.specification(&placement.specification).is_none() {
+                    return Err(EditError::UnknownSpecification(placement.specification));
+                }
+                store
+                    .insert_hierarchy(
+                        &placement.specification,
+                        placement.parent.as_deref(),
+                        placement.index,
+                        hierarchy,
+                    )
+                    .ok_or_else(|| {
+                        EditError::UnknownHierarchy(placement.parent.clone().unwrap_or_default())
+                    })?;
+                Change::RemoveHierarchy { hierarchy: id }
+            }
+            Change::RemoveHierarchy { hierarchy } => {
+                let (specification, parent, index) = store
+                    .hierarchy_placement(&hierarchy)
+                    .ok_or_else(|| EditError::UnknownHierarchy(hierarchy.clone()))?;
+                let placement = Placement {
+                    specification: specification.to_string(),
+                    parent: parent.map(str::to_string),
+                    index,
+                };
+                let hierarchy = store
+                    .remove_hierarchy(&hierarchy)
+                    .ok_or(EditError::UnknownHierarchy(hierarchy))?;
+                Change::InsertHierarchy {
+                    placement,
+                    hierarchy,
+                }
+            }
+            Change::InsertRelation { index, relation } => {
+                let id = relation.ident.identifier.clone();
+                store.insert_spec_relation_at(index, relation);
+                Change::RemoveRelation { relation: id }
+            }
+            Change::RemoveRelation { relation } => {
+                let index = store
+                    .spec_relation_index(&relation)
+                    .ok_or_else(|| EditError::UnknownRelation(relation.clone()))?;
+                let relation = store
+                    .remove_spec_relation(&relation)
+                    .ok_or(EditError::UnknownRelation(relation))?;
+                Change::InsertRelation { index, relation }
+            }
+            Change::InsertGroupRelation {
+                group,
+                index,
+                relation,
+            } => {
+                store
+                    .insert_group_relation(&group, index, relation.clone())
+                    .ok_or_else(|| EditError::UnknownRelationGroup(group.clone()))?;
+                Change::RemoveGroupRelation { group, relation }
+            }
+            Change::RemoveGroupRelation { group, relation } => {
+                let index = store
+                    .remove_group_relation(&group, &relation)
+                    .ok_or_else(|| EditError::UnknownRelation(relation.clone()))?;
+                Change::InsertGroupRelation {
+                    group,
+                    index,
+                    relation,
+                }
+            }
+            Change::InsertSpecType { index, spec_type } => {
+                let id = spec_type.ident.identifier.clone();
+                store.insert_spec_type_at(index, spec_type);
+                Change::RemoveSpecType { spec_type: id }
+            }
+            Change::RemoveSpecType { spec_type } => {
+                let index = store
+                    .spec_type_index(&spec_type)
+                    .ok_or_else(|| EditError::UnknownSpecType(spec_type.clone()))?;
+                let spec_type = store
+                    .remove_spec_type(&spec_type)
+                    .ok_or(EditError::UnknownSpecType(spec_type))?;
+                Change::InsertSpecType { index, spec_type }
+            }
+            Change::ReplaceSpecType { spec_type } => {
+                let id = spec_type.ident.identifier.clone();
+                let spec_type = store
+                    .replace_spec_type(spec_type)
+                    .ok_or(EditError::UnknownSpecType(id))?;
+                Change::ReplaceSpecType { spec_type }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    fn store() -> DocumentStore {
+        DocumentStore::new(parser::parse_str(SMALL).unwrap())
+    }
+
+    fn title(value: &str) -> AttributeValue {
+        AttributeValue::String {
+            definition: "ad-title".into(),
+            value: value.into(),
+            extras: Extras::default(),
+        }
+    }
+
+    fn objects(store: &DocumentStore) -> Vec<&str> {
+        let children = &store.specification("spec-001").unwrap().children;
+        children.iter().map(|h| h.object.as_str()).collect()
+    }
+
+    #[test]
+    fn test_undo_and_redo_value_and_structure_edits() {
+        let mut store = store();
+        let mut journal = Journal::default();
+        let original = format!("{:?}", store.reqif());
+
+        journal
+            .apply(
+                &mut store,
+                Edit::SetValue {
+                    object: "req-001".into(),
+                    value: title("Boot fast"),
+                },
+            )
+            .unwrap();
+        journal
+            .apply(
+                &mut store,
+                Edit::MoveHierarchy {
+                    hierarchy: "sh-003".into(),
+                    placement: Placement {
+                        specification: "spec-001".into(),
+                        parent: Some("sh-001".into()),
+                        index: 0,
+                    },
+                },
+            )
+            .unwrap();
+        journal
+            .apply(
+                &mut store,
+                Edit::DeleteObject {
+                    object: "req-002".into(),
+                },
+            )
+            .unwrap();
+        assert_eq!(objects(&store), ["req-001"]);
+        assert_eq!(
+            store.hierarchy_placement("sh-003"),
+            Some(("spec-001", Some("sh-001"), 0))
+        );
+        assert!(store.spec_object("req-002").is_none());
+        assert_eq!(journal.history().undo.as_deref(), Some("Delete object"));
+        let changed = journal.take_changed();
+        assert_eq!(
+            changed.objects.iter().collect::<Vec<_>>(),
+            ["req-001", "req-002"]
+        );
+        assert!(journal.take_changed().is_empty());
+
+        while journal.undo(&mut store).unwrap().is_some() {}
+        assert_eq!(format!("{:?}", store.reqif()), original);
+        assert_eq!(objects(&store), ["req-001", "req-002", "req-003"]);
+
+        assert_eq!(
+            journal.redo(&mut store).unwrap().as_deref(),
+            Some("Set value")
+        );
+        assert!(matches!(
+            &store.spec_object("req-001").unwrap().values[0],
+            AttributeValue::String { value, .. } if value == "Boot fast"
+        ));
+        assert_eq!(journal.history().redo.as_deref(), Some("Move hierarchy"));
+    }
+
+    #[test]
+    fn test_deleted_object_leaves_its_sub_entries() {
+        let mut store = store();
+        let mut journal = Journal::default();
+        journal
+            .apply(
+                &mut store,
+                Edit::MoveHierarchy {
+                    hierarchy: "sh-003".into(),
+                    placement: Placement {
+                        specification: "spec-001".into(),
+                        parent: Some("sh-002".into()),
+                        index: 0,
+                    },
+                },
+            )
+            .unwrap();
+        let before = format!("{:?}", store.reqif());
+
+        journal
+            .apply(
+                &mut store,
+                Edit::DeleteObject {
+                    object: "req-002".into(),
+                },
+            )
+            .unwrap();
+        assert_eq!(objects(&store), ["req-001", "req-003"]);
+        assert_eq!(
+            store.hierarchy_placement("sh-003"),
+            Some(("spec-001", None, 1))
+        );
+
+        journal.undo(&mut store).unwrap();
+        assert_eq!(format!("{:?}", store.reqif()), before);
+        assert_eq!(
+            store.hierarchy_placement("sh-003"),
+            Some(("spec-001", Some("sh-002"), 0))
+        );
+    }
+
+    #[test]
+    fn test_delete_object_with_self_relation() {
+        let traced = include_str!("../../../tests/fixtures/traced.reqif");
+        let mut store = DocumentStore::new(parser::parse_str(traced).unwrap());
+        let mut journal = Journal::default();
+        let relation = SpecRelation {
+            ident: Identifiable::new("rel-self"),
+            spec_type: "srt-depends".into(),
+            source: "sw-001".into(),
+            target: "sw-001".into(),
+            values: Vec::new(),
+            extras: Extras::default(),
+        };
+        journal
+            .apply(&mut store, Edit::AddRelation { relation })
+            .unwrap();
+        let original = format!("{:?}", store.reqif());
+
+        journal
+            .apply(
+                &mut store,
+                Edit::DeleteObject {
+                    object: "sw-001".into(),
+                },
+            )
+            .unwrap();
+        assert!(store.spec_object("sw-001").is_none());
+        assert!(store.spec_relation("rel-self").is_none());
+        assert!(store.spec_relation("rel-001").is_none());
+
+        journal.undo(&mut store).unwrap();
+        assert_eq!(format!("{:?}", store.reqif()), original);
+        assert_eq!(store.outgoing_relations("sw-001").count(), 3);
+    }
+
+    #[test]
+    fn test_removed_relations_leave_their_groups() {
+        let traced = include_str!("../../../tests/fixtures/traced.reqif");
+        let mut store = DocumentStore::new(parser::parse_str(traced).unwrap());
+        let members = |store: &DocumentStore| {
+            store
+                .relation_group("rg-001")
+                .unwrap()
+                .spec_relations
+                .clone()
+        };
+        store.modify(|reqif| {
+            reqif.core_content.spec_relation_groups.push(RelationGroup {
+                ident: Identifiable::new("rg-001"),
+                spec_type: "rgt-trace".into(),
+                source_specification: "spec-sw".into(),
+                target_specification: "spec-sys".into(),
+                spec_relations: vec!["rel-002".into(), "rel-001".into(), "rel-003".into()],
+                extras: Extras::default(),
+            })
+        });
+        let original = format!("{:?}", store.reqif());
+        let mut journal = Journal::default();
+
+        journal
+            .apply(
+                &mut store,
+                Edit::RemoveRelation {
+                    relation: "rel-001".into(),
+                },
+            )
+            .unwrap();
+        assert_eq!(members(&store), ["rel-002", "rel-003"]);
+        journal.undo(&mut store).unwrap();
+        assert_eq!(members(&store), ["rel-002", "rel-001", "rel-003"]);
+
+        // Deleting an object drops the relations linking it from groups too
+        journal
+            .apply(
+                &mut store,
+                Edit::DeleteObject {
+                    object: "sw-001".into(),
+                },
+            )
+            .unwrap();
+        assert_eq!(members(&store), ["rel-002", "rel-003"]);
+        journal.undo(&mut store).unwrap();
+        assert_eq!(format!("{:?}", store.reqif()), original);
+    }
+
+    #[test]
+    fn test_new_objects_and_relations_are_checked() {
+        let mut store = store();
+        let mut journal = Journal::default();
+        let mut create = |change: &dyn Fn(&mut SpecObject)| {
+            let mut object = store.spec_object("req-001").unwrap().clone();
+            object.ident = Identifiable::new("req-004");
+            change(&mut object);
+            journal.apply(&mut store, Edit::CreateObject { object })
+        };
+        assert!(matches!(
+            create(&|o| o.spec_type = "st-specification".into()),
+            Err(EditError::WrongSpecTypeKind { .. })
+        ));
+        assert!(matches!(
+            create(&|o| o.spec_type = "sot-nope".into()),
+            Err(EditError::UnknownSpecType(_))
+        ));
+        assert!(matches!(
+            create(&|o| o.values = vec![AttributeValue::Integer {
+                definition: "ad-priority".into(),
+                value: 500,
+                extras: Extras::default(),
+            }]),
+            Err(EditError::Constraint { .. })
+        ));
+        assert!(matches!(
+            create(&|o| o.values = vec![AttributeValue::String {
+                definition: "ad-spec-name".into(),
+                value: "Name".into(),
+                extras: Extras::default(),
+            }]),
+            Err(EditError::UnknownAttribute { .. })
+        ));
+        assert!(create(&|_| {}).is_ok());
+
+        let traced = include_str!("../../../tests/fixtures/traced.reqif");
+        let mut store = DocumentStore::new(parser::parse_str(traced).unwrap());
+        let mut add = |change: &dyn Fn(&mut SpecRelation)| {
+            let mut relation = store.spec_relation("rel-001").unwrap().clone();
+            relation.ident = Identifiable::new("rel-new");
+            change(&mut relation);
+            journal.apply(&mut store, Edit::AddRelation { relation })
+        };
+        assert!(matches!(
+            add(&|r| r.target = "sys-404".into()),
+            Err(EditError::UnknownObject(_))
+        ));
+        assert!(matches!(
+            add(&|r| r.spec_type = "sot-software".into()),
+            Err(EditError::WrongSpecTypeKind { .. })
+        ));
+        assert!(add(&|_| {}).is_ok());
+    }
+
+    #[test]
+    fn test_grouped_edits_undo_as_one_step() {
+        let mut store = store();
+        let mut journal = Journal::default();
+        let mut object = store.spec_object("req-001").unwrap().clone();
+        object.ident = Identifiable::new("req-004");
+
+        journal.begin_group("New requirement");
+        journal
+            .apply(&mut store, Edit::CreateObject { object })
+            .unwrap();
+        let hierarchy = SpecHierarchy {
+            ident: Identifiable::new("sh-004"),
+            object: "req-004".into(),
+            is_editable: None,
+            is_table_internal: None,
+            children: Vec::new(),
+            extras: Extras::default(),
+        };
+        journal
+            .apply(
+                &mut store,
+                Edit::InsertHierarchy {
+                    hierarchy,
+                    placement: Placement {
+                        specification: "spec-001".into(),
+                        parent: None,
+                        index: 99,
+                    },
+                },
+            )
+            .unwrap();
+        journal.end_group();
+        assert_eq!(objects(&store).last(), Some(&"req-004"));
+
+        assert_eq!(
+            journal.undo(&mut store).unwrap().as_deref(),
+            Some("New requirement")
+        );
+        assert!(store.spec_object("req-004").is_none());
+        assert_eq!(objects(&store).len(), 3);
+        assert_eq!(
+            journal.history(),
+            History {
+                undo: None,
+                redo: Some("New requirement".into())
+            }
+        );
+    }
+
+    #[test]
+    fn test_refused_edits_change_nothing() {
+        let mut store = store();
+        let mut journal = Journal::default();
+        assert!(matches!(
+            journal.apply(
+                &mut store,
+                Edit::MoveHierarchy {
+                    hierarchy: "sh-001".into(),
+                    placement: Placement {
+                        specification: "spec-001".into(),
+                        parent: Some("sh-001".into()),
+                        index: 0,
+                    },
+                },
+            ),
+            Err(EditError::InvalidMove(_))
+        ));
+        assert!(matches!(
+            journal.apply(
+                &mut store,
+                Edit::RemoveSpecType {
+                    spec_type: "sot-requirement".into()
+                }
+            ),
+            Err(EditError::SpecTypeInUse(_))
+        ));
+
+        let requirement = store.spec_type("sot-requirement").unwrap().clone();
+        let mut put = |change: &dyn Fn(&mut SpecType)| {
+            let mut spec_type = requirement.clone();
+            change(&mut spec_type);
+            journal.apply(&mut store, Edit::PutSpecType { spec_type })
+        };
+        assert!(matches!(
+            put(&|t| t.kind = SpecTypeKind::SpecRelationType),
+            Err(EditError::SpecTypeInUse(_))
+        ));
+        assert!(matches!(
+            put(&|t| t.spec_attributes.clear()),
+            Err(EditError::DefinitionInUse(_))
+        ));
+        assert!(matches!(
+            put(&|t| t.spec_attributes[0].datatype_ref = "dt-nope".into()),
+            Err(EditError::UnknownDatatype { .. })
+        ));
+        assert!(put(&|t| t.ident.long_name = Some("Requirement".into())).is_ok());
+        journal.undo(&mut store).unwrap();
+        assert_eq!(journal.history().undo, None);
+        assert_eq!(objects(&store), ["req-001", "req-002", "req-003"]);
+    }
+}
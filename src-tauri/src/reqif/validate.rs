@@ -0,0 +1,354 @@
+// ReqIF validation - Reference integrity and datatype constraint checks over
+// a loaded document, reported as machine-readable diagnostics
+
+use super::constraints::{check_value, ConstraintViolation};
+use super::model::*;
+use super::store::DocumentStore;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Stable identifier of a kind of problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagnosticCode {
+    /// Two identifiable elements share an IDENTIFIER
+    DuplicateIdentifier,
+    /// A reference names an element that does not exist
+    DanglingReference,
+    /// A value's definition is not an attribute of its owner's spec type
+    ForeignDefinition,
+    /// A value breaks a limit of its datatype
+    ConstraintViolation,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticCode::DuplicateIdentifier => "duplicate-identifier",
+            DiagnosticCode::DanglingReference => "dangling-reference",
+            DiagnosticCode::ForeignDefinition => "foreign-definition",
+            DiagnosticCode::ConstraintViolation => "constraint-violation",
+        }
+    }
+}
+
+/// One problem found in a document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: DiagnosticCode,
+    /// Identifier of the element the problem was found on
+    pub element: String,
+    /// Offending identifier, e.g. the unresolved reference
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}[{}] {}: {}",
+            severity,
+            self.code.as_str(),
+            self.element,
+            self.message
+        )
+    }
+}
+
+/// Check every identifier and reference of the document in `store`. Values
+/// breaking datatype limits are reported as warnings.
+pub fn validate(store: &DocumentStore) -> Vec<Diagnostic> {
+    validate_until(store, &AtomicBool::new(false)).unwrap_or_default()
+}
+
+/// [`validate`] that gives up, returning `None`, once `cancelled` is set
+pub fn validate_until(store: &DocumentStore, cancelled: &AtomicBool) -> Option<Vec<Diagnostic>> {
+    let mut validator = Validator {
+        store,
+        cancelled,
+        diagnostics: Vec::new(),
+    };
+    validator.check_identifiers();
+    validator.check_references();
+    (!validator.is_cancelled()).then_some(validator.diagnostics)
+}
+
+struct Validator<'a> {
+    store: &'a DocumentStore,
+    cancelled: &'a AtomicBool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn report(&mut self, code: DiagnosticCode, element: &str, reference: &str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code,
+            element: element.to_string(),
+            reference: Some(reference.to_string()),
+            message,
+        });
+    }
+
+    fn check_identifiers(&mut self) {
+        let content = self.store.content();
+        let mut ids: Vec<&Identifiable> = Vec::new();
+        for datatype in &content.datatype_definitions {
+            ids.push(datatype.ident());
+            if let DatatypeDefinition::Enumeration { values, .. } = datatype {
+                ids.extend(values.iter().map(|v| &v.ident));
+            }
+        }
+        for spec_type in &content.spec_types {
+            ids.push(&spec_type.ident);
+            ids.extend(spec_type.spec_attributes.iter().map(|d| &d.ident));
+        }
+        ids.extend(content.spec_objects.iter().map(|o| &o.ident));
+        ids.extend(content.spec_relations.iter().map(|r| &r.ident));
+        for specification in &content.specifications {
+            ids.push(&specification.ident);
+            collect_hierarchy_ids(&specification.children, &mut ids);
+        }
+        ids.extend(content.spec_relation_groups.iter().map(|g| &g.ident));
+
+        let mut seen = HashSet::new();
+        let mut reported = HashSet::new();
+        for ident in ids {
+            let id = ident.identifier.as_str();
+            if !seen.insert(id) && reported.insert(id) {
+                self.report(
+                    DiagnosticCode::DuplicateIdentifier,
+                    id,
+                    id,
+                    format!("identifier '{}' is used more than once", id),
+                );
+            }
+        }
+    }
+
+    fn check_references(&mut self) {
+        let store = self.store;
+        let content = store.content();
+        for spec_type in &content.spec_types {
+            for definition in &spec_type.spec_attributes {
+                let id = &definition.ident.identifier;
+                if store.datatype(&definition.datatype_ref).is_none() {
+                    self.dangling(id, "datatype", &definition.datatype_ref);
+                }
+                if let Some(value) = &definition.default_value {
+                    self.check_value(id, &spec_type.ident.identifier, value);
+                }
+            }
+        }
+        for object in &content.spec_objects {
+            if self.is_cancelled() {
+                return;
+            }
+            let id = &object.ident.identifier;
+            self.check_typed(id, &object.spec_type, &object.values);
+        }
+        for relation in &content.spec_relations {
+            if self.is_cancelled() {
+                return;
+            }
+            let id = &relation.ident.identifier;
+            self.check_typed(id, &relation.spec_type, &relation.values);
+            for end in [&relation.source, &relation.target] {
+                if store.spec_object(end).is_none() {
+                    self.dangling(id, "spec object", end);
+                }
+            }
+        }
+        for specification in &content.specifications {
+            if self.is_cancelled() {
+                return;
+            }
+            let id = &specification.ident.identifier;
+            self.check_typed(id, &specification.spec_type, &specification.values);
+            self.check_hierarchies(&specification.children);
+        }
+        for group in &content.spec_relation_groups {
+            let id = &group.ident.identifier;
+            if store.spec_type(&group.spec_type).is_none() {
+                self.dangling(id, "spec type", &group.spec_type);
+            }
+            for spec in [&group.source_specification, &group.target_specification] {
+                if store.specification(spec).is_none() {
+                    self.dangling(id, "specification", spec);
+                }
+            }
+            for relation in &group.spec_relations {
+                if store.spec_relation(relation).is_none() {
+                    self.dangling(id, "spec relation", relation);
+                }
+            }
+        }
+    }
+
+    /// Check the type reference and attribute values of an element
+    fn check_typed(&mut self, id: &str, spec_type: &str, values: &[AttributeValue]) {
+        if self.store.spec_type(spec_type).is_none() {
+            self.dangling(id, "spec type", spec_type);
+        }
+        for value in values {
+            self.check_value(id, spec_type, value);
+        }
+    }
+
+    fn check_value(&mut self, id: &str, spec_type: &str, value: &AttributeValue) {
+        let store = self.store;
+        let Some(definition) = store.value_definition(value) else {
+            self.dangling(id, "attribute definition", value.definition());
+            return;
+        };
+        let foreign = store.spec_type(spec_type).is_some_and(|t| {
+            !t.spec_attributes
+                .iter()
+                .any(|d| d.ident.identifier == definition.ident.identifier)
+        });
+        if foreign {
+            self.report(
+                DiagnosticCode::ForeignDefinition,
+                id,
+                value.definition(),
+                format!(
+                    "attribute definition '{}' does not belong to spec type '{}'",
+                    value.definition(),
+                    spec_type
+                ),
+            );
+        }
+        let Some(datatype) = store.definition_datatype(definition) else {
+            return;
+        };
+        for violation in check_value(value, datatype) {
+            match violation {
+                ConstraintViolation::UnknownEnumValue { value } => {
+                    self.dangling(id, "enum value", &value)
+                }
+                violation => self.diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    code: DiagnosticCode::ConstraintViolation,
+                    element: id.to_string(),
+                    reference: Some(definition.ident.identifier.clone()),
+                    message: violation.to_string(),
+                }),
+            }
+        }
+    }
+
+    fn check_hierarchies(&mut self, children: &[SpecHierarchy]) {
+        for child in children {
+            if self.store.spec_object(&child.object).is_none() {
+                self.dangling(&child.ident.identifier, "spec object", &child.object);
+            }
+            self.check_hierarchies(&child.children);
+        }
+    }
+
+    fn dangling(&mut self, id: &str, what: &str, reference: &str) {
+        self.report(
+            DiagnosticCode::DanglingReference,
+            id,
+            reference,
+            format!("{} '{}' does not exist", what, reference),
+        );
+    }
+}
+
+fn collect_hierarchy_ids<'a>(children: &'a [SpecHierarchy], ids: &mut Vec<&'a Identifiable>) {
+    for child in children {
+        ids.push(&child.ident);
+        collect_hierarchy_ids(&child.children, ids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    #[test]
+    fn test_valid_document_has_no_diagnostics() {
+        let store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        assert_eq!(validate(&store), Vec::new());
+    }
+
+    #[test]
+    fn test_reports_dangling_duplicate_and_foreign_references() {
+        let xml = SMALL
+            .replace(
+                r#"<SPEC-HIERARCHY IDENTIFIER="sh-001">
+              <OBJECT><SPEC-OBJECT-REF>req-001</SPEC-OBJECT-REF>"#,
+                r#"<SPEC-HIERARCHY IDENTIFIER="sh-001">
+              <OBJECT><SPEC-OBJECT-REF>req-404</SPEC-OBJECT-REF>"#,
+            )
+            .replace(r#"IDENTIFIER="req-003""#, r#"IDENTIFIER="req-002""#)
+            .replace(
+                "<ATTRIBUTE-DEFINITION-STRING-REF>ad-title</ATTRIBUTE-DEFINITION-STRING-REF>",
+                "<ATTRIBUTE-DEFINITION-STRING-REF>ad-spec-name</ATTRIBUTE-DEFINITION-STRING-REF>",
+            );
+        let diagnostics = validate(&DocumentStore::new(parser::parse_str(&xml).unwrap()));
+        let found = |code: DiagnosticCode, element: &str, reference: &str| {
+            diagnostics.iter().any(|d| {
+                d.code == code && d.element == element && d.reference.as_deref() == Some(reference)
+            })
+        };
+        assert!(found(
+            DiagnosticCode::DanglingReference,
+            "sh-001",
+            "req-404"
+        ));
+        assert!(found(
+            DiagnosticCode::DuplicateIdentifier,
+            "req-002",
+            "req-002"
+        ));
+        assert!(found(
+            DiagnosticCode::ForeignDefinition,
+            "req-001",
+            "ad-spec-name"
+        ));
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+
+        let json = serde_json::to_string(&diagnostics[0]).unwrap();
+        assert!(json.contains(r#""code":"duplicate-identifier""#));
+        assert!(diagnostics[0]
+            .to_string()
+            .starts_with("error[duplicate-identifier] req-002:"));
+    }
+
+    #[test]
+    fn test_constraint_violations_are_warnings() {
+        let out_of_range = SMALL.replace(r#"THE-VALUE="10""#, r#"THE-VALUE="101""#);
+        let diagnostics = validate(&DocumentStore::new(
+            parser::parse_str(&out_of_range).unwrap(),
+        ));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::ConstraintViolation);
+        assert_eq!(diagnostics[0].element, "req-001");
+    }
+}
@@ -0,0 +1,224 @@
+// ReqIF saved views - Named table layouts and filters, kept in the document
+// as a tool extension or in a sidecar file next to it
+
+use super::model::{Extras, ToolExtension};
+use super::table::{SortKey, TableQuery};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Element holding the views in a REQ-IF-TOOL-EXTENSION
+pub const VIEWS_ELEMENT: &str = "reqsmith:views";
+/// Namespace of [`VIEWS_ELEMENT`]
+pub const VIEWS_NAMESPACE: &str = "https://reqsmith.dev/reqif/views/1";
+
+#[derive(Debug, Error)]
+pub enum ViewError {
+    #[error("saved views are not valid: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("saved views are not valid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("cannot access saved views file {path}: {source}")]
+    Io { path: String, source: io::Error },
+}
+
+/// Named layout of a specification's requirements table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedView {
+    pub name: String,
+    pub specification: String,
+    /// Attribute definitions shown; empty for all
+    #[serde(default)]
+    pub columns: Vec<String>,
+    /// Filter query, see `search::filter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+    /// Attribute definition whose values group the rows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+    /// SpecHierarchy entries shown without their sub-entries
+    #[serde(default)]
+    pub collapsed: Vec<String>,
+}
+
+impl SavedView {
+    /// Table query for rows `offset..offset + limit` of the view
+    pub fn query(&self, offset: usize, limit: usize) -> TableQuery {
+        TableQuery {
+            specification: self.specification.clone(),
+            offset,
+            limit,
+            columns: self.columns.clone(),
+            sort: self.sort.clone(),
+            filter: self.filter.clone(),
+            group_by: self.group_by.clone(),
+            collapsed: self.collapsed.clone(),
+        }
+    }
+}
+
+/// Where a document's views are saved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewStorage {
+    /// In a REQ-IF-TOOL-EXTENSION of the document itself
+    #[default]
+    Document,
+    /// In a `.views.json` file next to the document
+    Sidecar,
+}
+
+/// Remove the extension holding the views element from `extensions` and
+/// return its views
+pub fn take_views(
+    extensions: &mut Vec<ToolExtension>,
+) -> Result<Option<Vec<SavedView>>, ViewError> {
+    let mut found = None;
+    for (index, extension) in extensions.iter().enumerate() {
+        if let Some(json) = views_json(&extension.content)? {
+            found = Some((index, json));
+            break;
+        }
+    }
+    let Some((index, json)) = found else {
+        return Ok(None);
+    };
+    extensions.remove(index);
+    Ok(Some(serde_json::from_str(&json)?))
+}
+
+/// Text of the top-level views element in the content of a tool extension
+fn views_json(content: &str) -> Result<Option<String>, ViewError> {
+    let mut reader = Reader::from_str(content);
+    let mut depth = 0;
+    let mut json = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                if depth == 0 && e.name().as_ref() == VIEWS_ELEMENT.as_bytes() {
+                    json = Some(String::new());
+                }
+                depth += 1;
+            }
+            Event::Empty(e) if depth == 0 && e.name().as_ref() == VIEWS_ELEMENT.as_bytes() => {
+                return Ok(Some(String::new()));
+            }
+            Event::End(_) => {
+                depth -= 1;
+                if depth == 0 && json.is_some() {
+                    return Ok(json);
+                }
+            }
+            Event::Text(t) => {
+                if let Some(json) = &mut json {
+                    json.push_str(&t.unescape()?);
+                }
+            }
+            Event::CData(c) => {
+                if let Some(json) = &mut json {
+                    json.push_str(&String::from_utf8_lossy(&c));
+                }
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Tool extension holding `views`
+pub fn views_extension(views: &[SavedView]) -> Result<ToolExtension, ViewError> {
+    let json = serde_json::to_string(views)?;
+    Ok(ToolExtension {
+        content: format!(
+            "<{0} xmlns:reqsmith=\"{1}\">{2}</{0}>",
+            VIEWS_ELEMENT,
+            VIEWS_NAMESPACE,
+            escape(&json)
+        ),
+        extras: Extras::default(),
+    })
+}
+
+/// Sidecar file for the document at `path`, e.g. `specs.reqif.views.json`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".views.json");
+    path.with_file_name(name)
+}
+
+/// Views in the sidecar file at `path`, or `None` when there is none
+pub fn read_sidecar(path: &Path) -> Result<Option<Vec<SavedView>>, ViewError> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(ViewError::Io {
+            path: path.display().to_string(),
+            source,
+        }),
+    }
+}
+
+pub fn write_sidecar(path: &Path, views: &[SavedView]) -> Result<(), ViewError> {
+    let json = serde_json::to_string_pretty(views)?;
+    std::fs::write(path, json).map_err(|source| ViewError::Io {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+/// Delete the sidecar file at `path`, if there is one
+pub fn remove_sidecar(path: &Path) -> Result<(), ViewError> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(ViewError::Io {
+            path: path.display().to_string(),
+            source: e,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::{parser, serializer};
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    #[test]
+    fn test_views_round_trip_through_tool_extension() {
+        let view = SavedView {
+            name: "Urgent <& open>".into(),
+            specification: "spec-001".into(),
+            columns: vec!["ad-title".into()],
+            filter: Some("Priority > 5 AND Title contains \"start\"".into()),
+            sort: vec![SortKey {
+                definition: "ad-priority".into(),
+                descending: true,
+            }],
+            group_by: None,
+            collapsed: vec!["sh-001".into()],
+        };
+        let mut reqif = parser::parse_str(SMALL).unwrap();
+        reqif
+            .tool_extensions
+            .push(views_extension(std::slice::from_ref(&view)).unwrap());
+        let xml = serializer::to_string(&reqif).unwrap();
+
+        let mut reparsed = parser::parse_str(&xml).unwrap();
+        let views = take_views(&mut reparsed.tool_extensions).unwrap();
+        assert_eq!(views, Some(vec![view]));
+        assert!(reparsed.tool_extensions.is_empty());
+        assert_eq!(take_views(&mut reparsed.tool_extensions).unwrap(), None);
+
+        assert_eq!(
+            sidecar_path(Path::new("/data/specs.reqifz")),
+            Path::new("/data/specs.reqifz.views.json")
+        );
+    }
+}
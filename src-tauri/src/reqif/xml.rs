@@ -0,0 +1,285 @@
+// Lightweight XML element tree used while reading ReqIF content
+//
+// The parser walks the document with `quick-xml` events and materializes one
+// element subtree at a time (a single SPEC-OBJECT, a DATATYPE-DEFINITION, ...)
+// rather than building a tree of the whole document.
+
+use quick_xml::errors::IllFormedError;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Read};
+
+/// Element node with its attributes and children in document order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+/// Child node of an element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+    /// Comment content, without the `<!--` and `-->` delimiters
+    Comment(String),
+    /// Processing instruction, without the `<?` and `?>` delimiters
+    ProcessingInstruction(String),
+}
+
+impl XmlElement {
+    /// Build an element (without children) from a start tag
+    pub fn from_start(start: &BytesStart) -> Result<Self, quick_xml::Error> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+        let mut attributes = Vec::new();
+        for attr in start.attributes() {
+            let attr = attr?;
+            let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+            let value = attr.unescape_value()?.into_owned();
+            attributes.push((key, value));
+        }
+        Ok(Self {
+            name,
+            attributes,
+            children: Vec::new(),
+        })
+    }
+
+    /// Read the remainder of an element whose start tag has just been consumed
+    pub fn read<R: BufRead>(
+        reader: &mut Reader<R>,
+        start: &BytesStart,
+        buf: &mut Vec<u8>,
+    ) -> Result<Self, quick_xml::Error> {
+        let mut stack = vec![Self::from_start(start)?];
+        loop {
+            buf.clear();
+            match reader.read_event_into(buf)? {
+                Event::Start(e) => stack.push(Self::from_start(&e)?),
+                Event::Empty(e) => {
+                    let element = Self::from_start(&e)?;
+                    push_child(&mut stack, XmlNode::Element(element));
+                }
+                Event::End(_) => {
+                    let element = stack.pop().expect("element stack is never empty");
+                    if stack.is_empty() {
+                        return Ok(element);
+                    }
+                    push_child(&mut stack, XmlNode::Element(element));
+                }
+                Event::Text(t) => {
+                    let text = t.unescape()?.into_owned();
+                    push_child(&mut stack, XmlNode::Text(text));
+                }
+                Event::CData(c) => {
+                    let text = String::from_utf8_lossy(&c).into_owned();
+                    push_child(&mut stack, XmlNode::Text(text));
+                }
+                Event::Eof => {
+                    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                    return Err(IllFormedError::MissingEndTag(name).into());
+                }
+                Event::Comment(c) => {
+                    let text = String::from_utf8_lossy(&c).into_owned();
+                    push_child(&mut stack, XmlNode::Comment(text));
+                }
+                Event::PI(pi) => {
+                    let text = String::from_utf8_lossy(&pi).into_owned();
+                    push_child(&mut stack, XmlNode::ProcessingInstruction(text));
+                }
+                // Declarations and doctypes cannot appear inside an element
+                _ => {}
+            }
+        }
+    }
+
+    /// Name without namespace prefix
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// Look up an attribute by its qualified name
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Iterate over element children
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|node| match node {
+            XmlNode::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// First child element with the given local name
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find(|e| e.local_name() == name)
+    }
+
+    /// Concatenated text content of this element (not recursive), trimmed
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            if let XmlNode::Text(t) = node {
+                text.push_str(t);
+            }
+        }
+        text.trim().to_string()
+    }
+
+    /// Text of the first child element wrapped in `container`, e.g. the ID in
+    /// `<TYPE><SPEC-OBJECT-TYPE-REF>id</SPEC-OBJECT-TYPE-REF></TYPE>`
+    pub fn ref_in(&self, container: &str) -> Option<String> {
+        self.child(container)
+            .and_then(|c| c.elements().next())
+            .map(|r| r.text())
+    }
+
+    /// Serialize this element, including its own tag, to an XML string
+    pub fn to_xml(&self) -> String {
+        let mut out = String::new();
+        write_element(self, &mut out);
+        out
+    }
+
+    /// Serialize the children of this element back to an XML string
+    pub fn inner_xml(&self) -> String {
+        let mut out = String::new();
+        for node in &self.children {
+            write_node(node, &mut out);
+        }
+        out
+    }
+}
+
+impl From<XmlElement> for XmlNode {
+    fn from(element: XmlElement) -> Self {
+        XmlNode::Element(element)
+    }
+}
+
+fn push_child(stack: &mut [XmlElement], node: XmlNode) {
+    if let Some(parent) = stack.last_mut() {
+        parent.children.push(node);
+    }
+}
+
+/// Buffered reader that tracks the line and column of the input consumed so
+/// far, so parse errors can point into the source document
+pub struct PositionReader<R> {
+    inner: R,
+    line: u64,
+    column: u64,
+    tag_start: (u64, u64),
+}
+
+impl<R: BufRead> PositionReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line: 1,
+            column: 1,
+            tag_start: (1, 1),
+        }
+    }
+
+    /// Line and column of the next unread character
+    pub fn position(&self) -> (u64, u64) {
+        (self.line, self.column)
+    }
+
+    /// Line and column of the last `<` consumed, i.e. the start of the most
+    /// recently read tag
+    pub fn tag_start(&self) -> (u64, u64) {
+        self.tag_start
+    }
+}
+
+impl<R: BufRead> Read for PositionReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for PositionReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The bytes being consumed are still at the front of the inner
+        // buffer, so this does not read
+        if let Ok(buf) = self.inner.fill_buf() {
+            for &byte in &buf[..amt.min(buf.len())] {
+                match byte {
+                    b'\n' => {
+                        self.line += 1;
+                        self.column = 1;
+                    }
+                    b'<' => {
+                        self.tag_start = (self.line, self.column);
+                        self.column += 1;
+                    }
+                    // UTF-8 continuation bytes do not start a character
+                    _ if byte & 0xC0 == 0x80 => {}
+                    _ => self.column += 1,
+                }
+            }
+        }
+        self.inner.consume(amt);
+    }
+}
+
+/// Strip the namespace prefix from a qualified name
+pub fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn write_node(node: &XmlNode, out: &mut String) {
+    match node {
+        XmlNode::Text(text) => out.push_str(&quick_xml::escape::partial_escape(text)),
+        XmlNode::Element(e) => write_element(e, out),
+        XmlNode::Comment(text) => {
+            out.push_str("<!--");
+            out.push_str(text);
+            out.push_str("-->");
+        }
+        XmlNode::ProcessingInstruction(text) => {
+            out.push_str("<?");
+            out.push_str(text);
+            out.push_str("?>");
+        }
+    }
+}
+
+fn write_element(e: &XmlElement, out: &mut String) {
+    out.push('<');
+    out.push_str(&e.name);
+    for (key, value) in &e.attributes {
+        out.push(' ');
+        out.push_str(key);
+        out.push_str("=\"");
+        out.push_str(&quick_xml::escape::escape(value));
+        out.push('"');
+    }
+    if e.children.is_empty() {
+        out.push_str("/>");
+    } else {
+        out.push('>');
+        for child in &e.children {
+            write_node(child, out);
+        }
+        out.push_str("</");
+        out.push_str(&e.name);
+        out.push('>');
+    }
+}
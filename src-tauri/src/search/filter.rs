@@ -0,0 +1,900 @@
+// Requirement filters - Structured queries over typed attribute values and
+// relations, e.g. `Priority > 5 AND Status in (Draft, Review)`
+
+use crate::reqif::model::*;
+use crate::reqif::store::DocumentStore;
+use crate::reqif::table::xhtml_text;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FilterError {
+    #[error("{message} at column {column}")]
+    Syntax { column: usize, message: String },
+    #[error("unknown attribute '{0}'")]
+    UnknownAttribute(String),
+    #[error("unknown relation type '{0}'")]
+    UnknownRelationType(String),
+    #[error("'{value}' is not a {expected} for '{attribute}'")]
+    InvalidValue {
+        attribute: String,
+        value: String,
+        expected: &'static str,
+    },
+    #[error("'{operator}' does not apply to '{attribute}'")]
+    Operator {
+        attribute: String,
+        operator: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+    /// Whether a value ordered `ordering` against the operand satisfies this
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering.is_eq(),
+            Operator::Ne => ordering.is_ne(),
+            Operator::Lt => ordering.is_lt(),
+            Operator::Le => ordering.is_le(),
+            Operator::Gt => ordering.is_gt(),
+            Operator::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// Which relations of an object `has_link` looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+    Any,
+}
+
+/// Condition on a SpecObject. Attributes are named by LONG-NAME or
+/// identifier within the object's type; `type` and `id` name the object's
+/// type and identifier. A condition on a value the object lacks is false.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare {
+        attribute: String,
+        operator: Operator,
+        value: String,
+    },
+    /// Equal to any of the values
+    In {
+        attribute: String,
+        values: Vec<String>,
+    },
+    /// Text containing the value, or enumeration holding it
+    Contains {
+        attribute: String,
+        value: String,
+    },
+    /// Linked by a relation of the type, or of any type
+    HasLink {
+        direction: Direction,
+        relation_type: Option<String>,
+    },
+}
+
+/// Parse a filter such as
+/// `type = "System Requirement" AND (Priority >= 5 OR NOT Status = Draft)`.
+///
+/// Conditions are `attribute op value` with `=`, `!=`, `<`, `<=`, `>`,
+/// `>=`; `attribute in (value, ...)`; `attribute contains value`; and
+/// `has_link(outgoing|incoming|any[, relation type])`. Keywords ignore case,
+/// and names or values with spaces are quoted. Dates are compared as
+/// instants, or as whole days when given as `YYYY-MM-DD`, a value falling
+/// on the day in its own time zone.
+pub fn parse_filter(input: &str) -> Result<Filter, FilterError> {
+    let mut parser = FilterParser {
+        tokens: lex(input)?,
+        position: 0,
+        end: input.chars().count(),
+    };
+    let filter = parser.or()?;
+    match parser.tokens.get(parser.position) {
+        Some((column, token)) => Err(syntax(*column, format!("unexpected {}", token))),
+        None => Ok(filter),
+    }
+}
+
+/// SpecObjects matching `filter`, in document order
+pub fn filter_objects<'a>(
+    store: &'a DocumentStore,
+    filter: &Filter,
+) -> Result<Vec<&'a SpecObject>, FilterError> {
+    filter.check(store)?;
+    let mut found = Vec::new();
+    for object in &store.content().spec_objects {
+        if filter.matches(store, object)? {
+            found.push(object);
+        }
+    }
+    Ok(found)
+}
+
+impl Filter {
+    /// Check that the attributes and relation types named exist in `store`
+    /// and that values fit the attributes' datatypes
+    pub fn check(&self, store: &DocumentStore) -> Result<(), FilterError> {
+        match self {
+            Filter::And(a, b) | Filter::Or(a, b) => {
+                a.check(store)?;
+                b.check(store)
+            }
+            Filter::Not(filter) => filter.check(store),
+            Filter::Compare {
+                attribute,
+                operator,
+                value,
+            } => check_attribute(store, attribute, &Test::Compare(*operator, value)),
+            Filter::In { attribute, values } => {
+                check_attribute(store, attribute, &Test::In(values))
+            }
+            Filter::Contains { attribute, value } => {
+                check_attribute(store, attribute, &Test::Contains(value))
+            }
+            Filter::HasLink {
+                relation_type: Some(name),
+                ..
+            } => store
+                .content()
+                .spec_types
+                .iter()
+                .any(|t| t.kind == SpecTypeKind::SpecRelationType && names(&t.ident, name))
+                .then_some(())
+                .ok_or_else(|| FilterError::UnknownRelationType(name.clone())),
+            Filter::HasLink { .. } => Ok(()),
+        }
+    }
+
+    /// Whether `object` satisfies the filter; fails on a value that does
+    /// not fit the attribute's type
+    pub fn matches(&self, store: &DocumentStore, object: &SpecObject) -> Result<bool, FilterError> {
+        match self {
+            Filter::And(a, b) => Ok(a.matches(store, object)? && b.matches(store, object)?),
+            Filter::Or(a, b) => Ok(a.matches(store, object)? || b.matches(store, object)?),
+            Filter::Not(filter) => Ok(!filter.matches(store, object)?),
+            Filter::Compare {
+                attribute,
+                operator,
+                value,
+            } => test(store, object, attribute, &Test::Compare(*operator, value)),
+            Filter::In { attribute, values } => test(store, object, attribute, &Test::In(values)),
+            Filter::Contains { attribute, value } => {
+                test(store, object, attribute, &Test::Contains(value))
+            }
+            Filter::HasLink {
+                direction,
+                relation_type,
+            } => {
+                let id = &object.ident.identifier;
+                let outgoing = matches!(direction, Direction::Outgoing | Direction::Any);
+                let incoming = matches!(direction, Direction::Incoming | Direction::Any);
+                let typed = |relation: &SpecRelation| match relation_type {
+                    Some(name) => store
+                        .spec_type(&relation.spec_type)
+                        .map_or(relation.spec_type == *name, |t| names(&t.ident, name)),
+                    None => true,
+                };
+                Ok(outgoing && store.outgoing_relations(id).any(typed)
+                    || incoming && store.incoming_relations(id).any(typed))
+            }
+        }
+    }
+}
+
+/// Test of one attribute's value
+enum Test<'a> {
+    Compare(Operator, &'a str),
+    In(&'a [String]),
+    Contains(&'a str),
+}
+
+impl Test<'_> {
+    fn operands(&self) -> Vec<&str> {
+        match self {
+            Test::Compare(_, operand) | Test::Contains(operand) => vec![operand],
+            Test::In(operands) => operands.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Whether the test holds given how a value compares to an operand
+    fn holds(
+        &self,
+        mut compare: impl FnMut(&str) -> Result<Ordering, FilterError>,
+    ) -> Result<bool, FilterError> {
+        match self {
+            Test::Compare(operator, operand) => Ok(operator.holds(compare(operand)?)),
+            Test::In(operands) => {
+                for operand in operands.iter() {
+                    if compare(operand)?.is_eq() {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Test::Contains(operand) => compare(operand).map(Ordering::is_eq),
+        }
+    }
+}
+
+fn is_builtin(attribute: &str) -> bool {
+    ["type", "id", "identifier"]
+        .iter()
+        .any(|b| b.eq_ignore_ascii_case(attribute))
+}
+
+/// Whether `name` is the element's identifier or, ignoring case, its
+/// LONG-NAME
+fn names(ident: &Identifiable, name: &str) -> bool {
+    ident.identifier == name
+        || ident
+            .long_name
+            .as_ref()
+            .is_some_and(|n| n.to_lowercase() == name.to_lowercase())
+}
+
+/// Check that `attribute` names an attribute of some SpecObject type and
+/// that the operands of `test` fit each such attribute's datatype, so a
+/// mistyped filter fails whatever values the objects hold
+fn check_attribute(store: &DocumentStore, attribute: &str, test: &Test) -> Result<(), FilterError> {
+    if is_builtin(attribute) {
+        return Ok(());
+    }
+    let mut known = false;
+    for definition in store
+        .content()
+        .spec_types
+        .iter()
+        .filter(|t| t.kind == SpecTypeKind::SpecObjectType)
+        .flat_map(|t| &t.spec_attributes)
+        .filter(|d| names(&d.ident, attribute))
+    {
+        known = true;
+        let Some(datatype) = store.definition_datatype(definition) else {
+            continue;
+        };
+        let expected = match datatype {
+            DatatypeDefinition::String { .. } | DatatypeDefinition::XHTML { .. } => continue,
+            DatatypeDefinition::Enumeration { .. } => "value of the enumeration",
+            DatatypeDefinition::Integer { .. } | DatatypeDefinition::Real { .. } => "number",
+            DatatypeDefinition::Boolean { .. } => "boolean",
+            DatatypeDefinition::Date { .. } => "date",
+        };
+        let enumeration = matches!(datatype, DatatypeDefinition::Enumeration { .. });
+        if matches!(test, Test::Contains(_)) && !enumeration {
+            return Err(FilterError::Operator {
+                attribute: attribute.to_string(),
+                operator: "contains",
+            });
+        }
+        for operand in test.operands() {
+            let fits = match datatype {
+                DatatypeDefinition::Enumeration { values, .. } => {
+                    values.iter().any(|v| names(&v.ident, operand))
+                }
+                DatatypeDefinition::Boolean { .. } => parse_bool(operand).is_some(),
+                DatatypeDefinition::Date { .. } => parse_date(operand).is_some(),
+                _ => parse_number(operand).is_some(),
+            };
+            if !fits {
+                return Err(FilterError::InvalidValue {
+                    attribute: attribute.to_string(),
+                    value: operand.to_string(),
+                    expected,
+                });
+            }
+        }
+    }
+    known
+        .then_some(())
+        .ok_or_else(|| FilterError::UnknownAttribute(attribute.to_string()))
+}
+
+fn test(
+    store: &DocumentStore,
+    object: &SpecObject,
+    attribute: &str,
+    test: &Test,
+) -> Result<bool, FilterError> {
+    if is_builtin(attribute) {
+        if !attribute.eq_ignore_ascii_case("type") {
+            return test_text(&object.ident.identifier, test);
+        }
+        let name = store
+            .spec_type(&object.spec_type)
+            .and_then(|t| t.ident.long_name.as_deref())
+            .unwrap_or(&object.spec_type)
+            .to_lowercase();
+        return match test {
+            Test::Contains(operand) => Ok(name.contains(&operand.to_lowercase())),
+            // The type also answers to its identifier
+            _ => test.holds(|operand| {
+                Ok(if operand == object.spec_type {
+                    Ordering::Equal
+                } else {
+                    name.cmp(&operand.to_lowercase())
+                })
+            }),
+        };
+    }
+
+    let Some(definition) = store.spec_type(&object.spec_type).and_then(|t| {
+        t.spec_attributes
+            .iter()
+            .find(|d| names(&d.ident, attribute))
+    }) else {
+        return Ok(false);
+    };
+    let Some(value) = store.value_or_default(object, &definition.ident.identifier) else {
+        return Ok(false);
+    };
+    let invalid = |value: &str, expected| FilterError::InvalidValue {
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+        expected,
+    };
+    let not_text = || match test {
+        Test::Contains(_) => Err(FilterError::Operator {
+            attribute: attribute.to_string(),
+            operator: "contains",
+        }),
+        _ => Ok(()),
+    };
+    match value {
+        AttributeValue::Integer { value, .. } => {
+            not_text()?;
+            test.holds(|operand| {
+                match parse_number(operand).ok_or_else(|| invalid(operand, "number"))? {
+                    Number::Integer(number) => Ok(value.cmp(&number)),
+                    Number::Real(number) => Ok(compare_real(*value as f64, number)),
+                }
+            })
+        }
+        AttributeValue::Real { value, .. } => {
+            not_text()?;
+            test.holds(|operand| {
+                match parse_number(operand).ok_or_else(|| invalid(operand, "number"))? {
+                    Number::Integer(number) => Ok(value.total_cmp(&(number as f64))),
+                    Number::Real(number) => Ok(value.total_cmp(&number)),
+                }
+            })
+        }
+        AttributeValue::Boolean { value, .. } => {
+            not_text()?;
+            test.holds(|operand| {
+                let flag = parse_bool(operand).ok_or_else(|| invalid(operand, "boolean"))?;
+                Ok(value.cmp(&flag))
+            })
+        }
+        AttributeValue::Date { value, .. } => {
+            not_text()?;
+            test.holds(|operand| {
+                let date = parse_date(operand).ok_or_else(|| invalid(operand, "date"))?;
+                Ok(date.compare(value.datetime()))
+            })
+        }
+        AttributeValue::String { value, .. } => test_text(value, test),
+        AttributeValue::XHTML { value, .. } => test_text(&xhtml_text(value), test),
+        AttributeValue::Enumeration { values, .. } => {
+            let specified = match store.definition_datatype(definition) {
+                Some(DatatypeDefinition::Enumeration { values, .. }) => values.as_slice(),
+                _ => &[],
+            };
+            let held: Vec<usize> = values
+                .iter()
+                .filter_map(|id| specified.iter().position(|v| v.ident.identifier == *id))
+                .collect();
+            let wanted = |operand: &str| {
+                specified
+                    .iter()
+                    .position(|v| names(&v.ident, operand))
+                    .ok_or_else(|| invalid(operand, "value of the enumeration"))
+            };
+            match test {
+                // Holding the value, whatever else is held
+                Test::Compare(Operator::Eq, operand) | Test::Contains(operand) => {
+                    Ok(held.contains(&wanted(operand)?))
+                }
+                Test::Compare(Operator::Ne, operand) => Ok(!held.contains(&wanted(operand)?)),
+                // Ordered by position among the specified values
+                Test::Compare(operator, operand) => {
+                    let wanted = wanted(operand)?;
+                    Ok(held.iter().any(|p| operator.holds(p.cmp(&wanted))))
+                }
+                Test::In(operands) => {
+                    for operand in operands.iter() {
+                        if held.contains(&wanted(operand)?) {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(false)
+                }
+            }
+        }
+    }
+}
+
+/// Case-insensitive test of text
+fn test_text(text: &str, test: &Test) -> Result<bool, FilterError> {
+    let text = text.to_lowercase();
+    match test {
+        Test::Contains(operand) => Ok(text.contains(&operand.to_lowercase())),
+        _ => test.holds(|operand| Ok(text.cmp(&operand.to_lowercase()))),
+    }
+}
+
+/// Number operand of a comparison; integers are kept exact
+enum Number {
+    Integer(i64),
+    Real(f64),
+}
+
+fn parse_number(text: &str) -> Option<Number> {
+    if let Ok(integer) = text.parse() {
+        return Some(Number::Integer(integer));
+    }
+    text.parse::<f64>()
+        .ok()
+        .filter(|n| !n.is_nan())
+        .map(Number::Real)
+}
+
+/// Order of two numbers neither of which is NaN
+fn compare_real(a: f64, b: f64) -> Ordering {
+    if a < b {
+        Ordering::Less
+    } else if a > b {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    text.to_ascii_lowercase().parse().ok()
+}
+
+/// Date operand of a comparison
+enum DateOperand {
+    Instant(DateTime<FixedOffset>),
+    /// A whole day, taken in the time zone of the value compared to it
+    Day(NaiveDate),
+}
+
+impl DateOperand {
+    /// Order of `value` against the operand; values within a day are equal
+    /// to it
+    fn compare(&self, value: DateTime<FixedOffset>) -> Ordering {
+        match self {
+            DateOperand::Instant(instant) => value.cmp(instant),
+            DateOperand::Day(day) => value.date_naive().cmp(day),
+        }
+    }
+}
+
+fn parse_date(text: &str) -> Option<DateOperand> {
+    if let Ok(date) = DateValue::parse(text) {
+        return Some(DateOperand::Instant(date.datetime()));
+    }
+    let day = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+    Some(DateOperand::Day(day))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    Operator(Operator),
+    Word(String),
+    Quoted(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+            Token::Operator(op) => write!(f, "'{:?}'", op),
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+        }
+    }
+}
+
+fn syntax(column: usize, message: impl Into<String>) -> FilterError {
+    FilterError::Syntax {
+        column: column + 1,
+        message: message.into(),
+    }
+}
+
+/// Tokens with their character offsets
+fn lex(input: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '=' => Token::Operator(Operator::Eq),
+            '!' if next == Some('=') => Token::Operator(Operator::Ne),
+            '<' if next == Some('=') => Token::Operator(Operator::Le),
+            '<' if next == Some('>') => Token::Operator(Operator::Ne),
+            '<' => Token::Operator(Operator::Lt),
+            '>' if next == Some('=') => Token::Operator(Operator::Ge),
+            '>' => Token::Operator(Operator::Gt),
+            '!' => return Err(syntax(start, "expected '!='")),
+            '"' => {
+                let length = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or_else(|| syntax(start, "missing closing quote"))?;
+                i += length + 1;
+                Token::Quoted(chars[start + 1..i].iter().collect())
+            }
+            _ => {
+                while i + 1 < chars.len() && !is_delimiter(chars[i + 1]) {
+                    i += 1;
+                }
+                Token::Word(chars[start..=i].iter().collect())
+            }
+        };
+        if matches!(
+            token,
+            Token::Operator(Operator::Ne | Operator::Le | Operator::Ge)
+        ) {
+            i += 1;
+        }
+        i += 1;
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "(),=!<>\"".contains(c)
+}
+
+/// Recursive descent over the tokens; NOT binds tighter than AND, and AND
+/// tighter than OR
+struct FilterParser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Column reported for errors at the end of the input
+    end: usize,
+}
+
+impl FilterParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), FilterError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| syntax(self.end, "unexpected end of filter"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Consume the keyword if it comes next
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), FilterError> {
+        match self.next()? {
+            (_, token) if token == expected => Ok(()),
+            (column, token) => Err(syntax(
+                column,
+                format!("expected {} but found {}", expected, token),
+            )),
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.and()?;
+        while self.keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.not()?;
+        while self.keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter, FilterError> {
+        if self.keyword("not") {
+            return Ok(Filter::Not(Box::new(self.not()?)));
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Filter, FilterError> {
+        let attribute = match self.next()? {
+            (_, Token::Open) => {
+                let filter = self.or()?;
+                self.expect(Token::Close)?;
+                return Ok(filter);
+            }
+            (_, Token::Word(word))
+                if word.eq_ignore_ascii_case("has_link") && self.peek() == Some(&Token::Open) =>
+            {
+                return self.has_link();
+            }
+            (_, Token::Word(name) | Token::Quoted(name)) => name,
+            (column, token) => {
+                return Err(syntax(
+                    column,
+                    format!("expected a condition but found {}", token),
+                ))
+            }
+        };
+        if self.keyword("in") {
+            self.expect(Token::Open)?;
+            let mut values = vec![self.value()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.position += 1;
+                values.push(self.value()?);
+            }
+            self.expect(Token::Close)?;
+            return Ok(Filter::In { attribute, values });
+        }
+        if self.keyword("contains") {
+            let value = self.value()?;
+            return Ok(Filter::Contains { attribute, value });
+        }
+        match self.next()? {
+            (_, Token::Operator(operator)) => Ok(Filter::Compare {
+                attribute,
+                operator,
+                value: self.value()?,
+            }),
+            (column, token) => Err(syntax(
+                column,
+                format!(
+                    "expected a comparison after '{}' but found {}",
+                    attribute, token
+                ),
+            )),
+        }
+    }
+
+    fn value(&mut self) -> Result<String, FilterError> {
+        match self.next()? {
+            (_, Token::Word(value) | Token::Quoted(value)) => Ok(value),
+            (column, token) => Err(syntax(
+                column,
+                format!("expected a value but found {}", token),
+            )),
+        }
+    }
+
+    fn has_link(&mut self) -> Result<Filter, FilterError> {
+        self.expect(Token::Open)?;
+        let direction = match self.next()? {
+            (_, Token::Word(word)) if word.eq_ignore_ascii_case("outgoing") => Direction::Outgoing,
+            (_, Token::Word(word)) if word.eq_ignore_ascii_case("incoming") => Direction::Incoming,
+            (_, Token::Word(word)) if word.eq_ignore_ascii_case("any") => Direction::Any,
+            (column, token) => {
+                return Err(syntax(
+                    column,
+                    format!("expected outgoing, incoming or any but found {}", token),
+                ))
+            }
+        };
+        let mut relation_type = None;
+        if self.peek() == Some(&Token::Comma) {
+            self.position += 1;
+            relation_type = Some(self.value()?);
+        }
+        self.expect(Token::Close)?;
+        Ok(Filter::HasLink {
+            direction,
+            relation_type,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+    use chrono::{Offset, Utc};
+
+    const TRACED: &str = include_str!("../../../tests/fixtures/traced.reqif");
+
+    fn ids(store: &DocumentStore, filter: &str) -> Vec<String> {
+        filter_objects(store, &parse_filter(filter).unwrap())
+            .unwrap()
+            .iter()
+            .map(|o| o.ident.identifier.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_precedence_and_syntax_errors() {
+        let filter = parse_filter("not a = 1 and b in (x, \"y z\") or c contains q").unwrap();
+        let compare = Filter::Compare {
+            attribute: "a".into(),
+            operator: Operator::Eq,
+            value: "1".into(),
+        };
+        let within = Filter::In {
+            attribute: "b".into(),
+            values: vec!["x".into(), "y z".into()],
+        };
+        let contains = Filter::Contains {
+            attribute: "c".into(),
+            value: "q".into(),
+        };
+        assert_eq!(
+            filter,
+            Filter::Or(
+                Box::new(Filter::And(
+                    Box::new(Filter::Not(Box::new(compare))),
+                    Box::new(within)
+                )),
+                Box::new(contains)
+            )
+        );
+        assert_eq!(
+            parse_filter("Priority >= 2025-01-01T00:00:00Z").unwrap(),
+            Filter::Compare {
+                attribute: "Priority".into(),
+                operator: Operator::Ge,
+                value: "2025-01-01T00:00:00Z".into(),
+            }
+        );
+
+        assert_eq!(
+            parse_filter("Priority > 5 AND").unwrap_err().to_string(),
+            "unexpected end of filter at column 17"
+        );
+        assert!(matches!(
+            parse_filter("(Priority > 5"),
+            Err(FilterError::Syntax { column: 14, .. })
+        ));
+        assert!(matches!(
+            parse_filter("has_link(sideways)"),
+            Err(FilterError::Syntax { column: 10, .. })
+        ));
+        assert!(matches!(
+            parse_filter("Title Priority"),
+            Err(FilterError::Syntax { column: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_typed_comparisons_and_relations() {
+        let store = DocumentStore::new(parser::parse_str(TRACED).unwrap());
+        assert_eq!(
+            ids(
+                &store,
+                r#"type = "System Requirement" AND Priority > 5 AND Status in (Draft, Review) AND has_link(incoming, "satisfies")"#
+            ),
+            ["sys-002"]
+        );
+        assert_eq!(
+            ids(
+                &store,
+                "type = sot-software AND has_link(outgoing, satisfies)"
+            ),
+            ["sw-001", "sw-002", "sw-003"]
+        );
+        assert_eq!(
+            ids(&store, "NOT has_link(any)"),
+            ["sys-003", "sys-004", "sw-004"]
+        );
+        assert_eq!(ids(&store, "Title contains \"PASSWORD LOGIN\""), ["sw-002"]);
+        assert_eq!(
+            ids(&store, "Status >= Review AND type != sot-software"),
+            ["sys-001", "sys-003"]
+        );
+        assert_eq!(
+            ids(&store, "Due >= 2025-03-01 AND Due < 2025-06-15"),
+            ["sys-001"]
+        );
+        assert_eq!(ids(&store, "Due = 2025-06-15"), ["sys-002"]);
+        assert_eq!(
+            ids(&store, "Due > 2025-03-01T00:00:00+01:00"),
+            ["sys-001", "sys-002"]
+        );
+        assert_eq!(ids(&store, "Priority > 7.5"), ["sys-001", "sys-004"]);
+
+        // Integers compare exactly, beyond the precision of a float
+        let large = TRACED.replace(r#"THE-VALUE="9""#, r#"THE-VALUE="9007199254740993""#);
+        let large = DocumentStore::new(parser::parse_str(&large).unwrap());
+        assert_eq!(ids(&large, "Priority = 9007199254740993"), ["sys-004"]);
+        assert!(ids(&large, "Priority = 9007199254740992").is_empty());
+
+        // A day is the one in the value's own time zone
+        let day = parse_date("2025-06-15").unwrap();
+        let late = DateTime::parse_from_rfc3339("2025-06-15T23:30:00-05:00").unwrap();
+        assert_eq!(day.compare(late), Ordering::Equal);
+        assert_eq!(
+            day.compare(late.with_timezone(&Utc.fix())),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_unknown_names_and_mistyped_values() {
+        let store = DocumentStore::new(parser::parse_str(TRACED).unwrap());
+        let run = |filter: &str| filter_objects(&store, &parse_filter(filter).unwrap());
+        assert_eq!(
+            run("Colour = red").unwrap_err(),
+            FilterError::UnknownAttribute("Colour".into())
+        );
+        assert_eq!(
+            run("has_link(any, traces)").unwrap_err(),
+            FilterError::UnknownRelationType("traces".into())
+        );
+        assert_eq!(
+            run("Priority > high").unwrap_err().to_string(),
+            "'high' is not a number for 'Priority'"
+        );
+        assert!(matches!(
+            run("Status = Done"),
+            Err(FilterError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            run("Due contains 2025"),
+            Err(FilterError::Operator { .. })
+        ));
+        assert!(matches!(
+            run("Priority > NaN"),
+            Err(FilterError::InvalidValue { .. })
+        ));
+
+        // Values are checked against the definitions, whatever objects hold
+        assert_eq!(
+            run("type = sot-software AND Priority > high")
+                .unwrap_err()
+                .to_string(),
+            "'high' is not a number for 'Priority'"
+        );
+        assert!(matches!(
+            run("id = none AND Status in (Draft, Done)"),
+            Err(FilterError::InvalidValue { .. })
+        ));
+    }
+}
@@ -0,0 +1,668 @@
+// Search index - Tantivy index over the text of SpecObjects, kept up to date
+// as objects are edited
+
+use super::query::{Clause, Query};
+use crate::reqif::model::*;
+use crate::reqif::store::DocumentStore;
+use crate::reqif::table::display_value;
+use crate::reqif::undo::Changed;
+use serde::Serialize;
+use std::path::Path;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{
+    BooleanQuery, BoostQuery, EmptyQuery, FuzzyTermQuery, Occur, PhrasePrefixQuery, PhraseQuery,
+    Query as TantivyQuery, TermQuery,
+};
+use tantivy::schema::{IndexRecordOption, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use thiserror::Error;
+
+/// Field holding a SpecObject's identifier
+pub const IDENTIFIER_FIELD: &str = "identifier";
+/// Field holding the name of a SpecObject's type
+pub const SPEC_TYPE_FIELD: &str = "spec-type";
+
+/// Characters kept on either side of the first match of a snippet
+const SNIPPET_CONTEXT: usize = 40;
+/// Memory the index writer may use before flushing a segment
+const WRITER_MEMORY: usize = 50_000_000;
+/// Objects indexed between progress reports
+const PROGRESS_STEP: usize = 500;
+/// Word of indexed or queried text. Offsets count characters.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub term: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Lowercased runs of letters and digits
+pub(crate) fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    for (i, c) in text.chars().enumerate() {
+        if c.is_alphanumeric() {
+            let token = current.get_or_insert_with(|| Token {
+                term: String::new(),
+                start: i,
+                end: i,
+            });
+            token.term.extend(c.to_lowercase());
+            token.end = i + 1;
+        } else if let Some(token) = current.take() {
+            tokens.push(token);
+        }
+    }
+    tokens.extend(current);
+    tokens
+}
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("search index error: {0}")]
+    Index(#[from] tantivy::TantivyError),
+    #[error("cannot open search index directory: {0}")]
+    Directory(#[from] tantivy::directory::error::OpenDirectoryError),
+    #[error("search index holds invalid field text: {0}")]
+    Fields(#[from] serde_json::Error),
+    #[error("cannot create search index directory: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Searchable text of one SpecObject
+#[derive(Debug)]
+struct Field {
+    /// Attribute definition identifier, or one of the `*_FIELD` names
+    name: String,
+    text: String,
+    tokens: Vec<Token>,
+}
+
+/// Object matching a query, with the best matches first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    pub object: String,
+    pub score: f32,
+    /// One per field with a match, in field order
+    pub snippets: Vec<Snippet>,
+}
+
+/// Excerpt of a field around its matches
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snippet {
+    pub field: String,
+    pub fragment: String,
+    pub highlights: Vec<Highlight>,
+}
+
+/// Matched text in a fragment, in characters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Tantivy fields of the index
+#[derive(Clone, Copy)]
+struct Fields {
+    /// Raw identifier, the key for updates
+    object: tantivy::schema::Field,
+    identifier: tantivy::schema::Field,
+    spec_type: tantivy::schema::Field,
+    /// One value per string, XHTML and enumeration attribute
+    values: tantivy::schema::Field,
+    /// Names and text of all fields as JSON, for snippets
+    stored: tantivy::schema::Field,
+}
+
+impl Fields {
+    fn schema() -> Schema {
+        let mut schema = Schema::builder();
+        schema.add_text_field("object", STRING | STORED);
+        schema.add_text_field(IDENTIFIER_FIELD, TEXT);
+        schema.add_text_field(SPEC_TYPE_FIELD, TEXT);
+        schema.add_text_field("values", TEXT);
+        schema.add_text_field("stored", STORED);
+        schema.build()
+    }
+
+    fn of(schema: &Schema) -> Result<Self, SearchError> {
+        Ok(Self {
+            object: schema.get_field("object")?,
+            identifier: schema.get_field(IDENTIFIER_FIELD)?,
+            spec_type: schema.get_field(SPEC_TYPE_FIELD)?,
+            values: schema.get_field("values")?,
+            stored: schema.get_field("stored")?,
+        })
+    }
+
+    /// Fields searched by every clause
+    fn searched(&self) -> [tantivy::schema::Field; 3] {
+        [self.identifier, self.spec_type, self.values]
+    }
+}
+
+/// Identifier, type name and every string, XHTML and enumeration value of
+/// each SpecObject, in a Tantivy index held in memory or on disk
+pub struct SearchIndex {
+    fields: Fields,
+    writer: IndexWriter,
+    reader: IndexReader,
+}
+
+impl SearchIndex {
+    /// Index `store` in memory
+    pub fn build(store: &DocumentStore) -> Result<Self, SearchError> {
+        Self::create(Index::create_in_ram(Fields::schema()), store, |_| true)
+    }
+
+    /// Index `store` in memory, telling `progress` every so often how many
+    /// objects are done; `None` once `progress` returns false
+    pub fn build_until(
+        store: &DocumentStore,
+        progress: impl FnMut(usize) -> bool,
+    ) -> Result<Option<Self>, SearchError> {
+        let mut stopped = false;
+        let mut progress = progress;
+        let index = Self::create(Index::create_in_ram(Fields::schema()), store, |done| {
+            stopped = !progress(done);
+            !stopped
+        })?;
+        Ok((!stopped).then_some(index))
+    }
+
+    /// Index `store` in directory `dir`, replacing any index already there,
+    /// so it can be reopened with [`SearchIndex::open`]
+    pub fn build_in(dir: &Path, store: &DocumentStore) -> Result<Self, SearchError> {
+        std::fs::create_dir_all(dir)?;
+        let directory = MmapDirectory::open(dir)?;
+        Self::create(
+            Index::open_or_create(directory, Fields::schema())?,
+            store,
+            |_| true,
+        )
+    }
+
+    /// Index previously built in `dir`. It reflects the document as of its
+    /// last update; the caller decides whether it is still current.
+    pub fn open(dir: &Path) -> Result<Self, SearchError> {
+        Self::from_index(Index::open(MmapDirectory::open(dir)?)?)
+    }
+
+    /// Index `store` into `index`; stops early, leaving the index
+    /// incomplete, once `progress` returns false
+    fn create(
+        index: Index,
+        store: &DocumentStore,
+        mut progress: impl FnMut(usize) -> bool,
+    ) -> Result<Self, SearchError> {
+        let mut search = Self::from_index(index)?;
+        search.writer.delete_all_documents()?;
+        for (done, object) in store.content().spec_objects.iter().enumerate() {
+            if done % PROGRESS_STEP == 0 && !progress(done) {
+                return Ok(search);
+            }
+            search.add(store, object)?;
+        }
+        search.commit()?;
+        progress(store.content().spec_objects.len());
+        Ok(search)
+    }
+
+    fn from_index(index: Index) -> Result<Self, SearchError> {
+        let fields = Fields::of(&index.schema())?;
+        let writer = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(Self {
+            fields,
+            writer,
+            reader,
+        })
+    }
+
+    /// Number of indexed objects
+    pub fn len(&self) -> usize {
+        self.reader.searcher().num_docs() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reindex SpecObject `object`, or drop it when it no longer exists
+    pub fn update(&mut self, store: &DocumentStore, object: &str) -> Result<(), SearchError> {
+        self.stage(store, object)?;
+        self.commit()
+    }
+
+    /// Reindex what `changed` touched, including every object of an edited
+    /// spec type
+    pub fn apply_changes(
+        &mut self,
+        store: &DocumentStore,
+        changed: &Changed,
+    ) -> Result<(), SearchError> {
+        if changed.is_empty() {
+            return Ok(());
+        }
+        for object in &changed.objects {
+            self.stage(store, object)?;
+        }
+        let typed: Vec<&str> = store
+            .content()
+            .spec_objects
+            .iter()
+            .filter(|o| changed.spec_types.contains(&o.spec_type))
+            .map(|o| o.ident.identifier.as_str())
+            .collect();
+        for object in typed {
+            self.stage(store, object)?;
+        }
+        self.commit()
+    }
+
+    /// Up to `limit` objects matching every clause of `query`, best first
+    pub fn search(&self, query: &Query, limit: usize) -> Result<Vec<SearchHit>, SearchError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let clauses: Vec<(Occur, Box<dyn TantivyQuery>)> = query
+            .clauses
+            .iter()
+            .map(|clause| (Occur::Must, self.clause_query(clause)))
+            .collect();
+        let searcher = self.reader.searcher();
+        let top = searcher.search(&BooleanQuery::new(clauses), &TopDocs::with_limit(limit))?;
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let document: TantivyDocument = searcher.doc(address)?;
+            let object = document
+                .get_first(self.fields.object)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let stored = document
+                .get_first(self.fields.stored)
+                .and_then(|v| v.as_str())
+                .unwrap_or("[]");
+            let fields: Vec<(String, String)> = serde_json::from_str(stored)?;
+            let fields: Vec<Field> = fields
+                .into_iter()
+                .map(|(name, text)| field(&name, text))
+                .collect();
+            hits.push(SearchHit {
+                object,
+                score,
+                snippets: snippets(&fields, query),
+            });
+        }
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.object.cmp(&b.object))
+        });
+        Ok(hits)
+    }
+
+    /// Replace the document of `object` in the pending commit
+    fn stage(&mut self, store: &DocumentStore, object: &str) -> Result<(), SearchError> {
+        self.writer
+            .delete_term(Term::from_field_text(self.fields.object, object));
+        if let Some(object) = store.spec_object(object) {
+            self.add(store, object)?;
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, store: &DocumentStore, object: &SpecObject) -> Result<(), SearchError> {
+        let fields = object_fields(store, object);
+        let mut document = TantivyDocument::new();
+        document.add_text(self.fields.object, &object.ident.identifier);
+        for field in &fields {
+            let target = match field.name.as_str() {
+                IDENTIFIER_FIELD => self.fields.identifier,
+                SPEC_TYPE_FIELD => self.fields.spec_type,
+                _ => self.fields.values,
+            };
+            document.add_text(target, &field.text);
+        }
+        let stored: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.text.as_str()))
+            .collect();
+        document.add_text(self.fields.stored, serde_json::to_string(&stored)?);
+        self.writer.add_document(document)?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), SearchError> {
+        self.writer.commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    /// Query matching `clause` in any searched field
+    fn clause_query(&self, clause: &Clause) -> Box<dyn TantivyQuery> {
+        let per_field = self
+            .fields
+            .searched()
+            .into_iter()
+            .map(|f| {
+                let term = |text: &str| Term::from_field_text(f, text);
+                let query: Box<dyn TantivyQuery> = match clause {
+                    Clause::Term(text) => {
+                        Box::new(TermQuery::new(term(text), IndexRecordOption::WithFreqs))
+                    }
+                    Clause::Prefix(prefix) => {
+                        Box::new(PhrasePrefixQuery::new_with_offset(vec![(0, term(prefix))]))
+                    }
+                    Clause::Fuzzy(text, distance) => {
+                        Box::new(FuzzyTermQuery::new(term(text), *distance, false))
+                    }
+                    Clause::Phrase(terms) => match terms.as_slice() {
+                        [] => Box::new(EmptyQuery),
+                        [single] => {
+                            Box::new(TermQuery::new(term(single), IndexRecordOption::WithFreqs))
+                        }
+                        _ => Box::new(PhraseQuery::new(terms.iter().map(|t| term(t)).collect())),
+                    },
+                };
+                (Occur::Should, query)
+            })
+            .collect();
+        Box::new(BoostQuery::new(
+            Box::new(BooleanQuery::new(per_field)),
+            boost(clause),
+        ))
+    }
+}
+
+/// One snippet per field with a match of any clause of `query`
+fn snippets(fields: &[Field], query: &Query) -> Vec<Snippet> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let spans: Vec<(usize, usize)> = query
+                .clauses
+                .iter()
+                .flat_map(|clause| find(clause, &field.tokens))
+                .collect();
+            (!spans.is_empty()).then(|| snippet(field, spans))
+        })
+        .collect()
+}
+
+/// Weight of a clause relative to an exact term
+fn boost(clause: &Clause) -> f32 {
+    match clause {
+        Clause::Term(_) => 1.0,
+        Clause::Phrase(_) => 1.5,
+        Clause::Prefix(_) => 0.8,
+        Clause::Fuzzy(..) => 0.6,
+    }
+}
+
+/// Fields of `object`: identifier, type name, then its string, XHTML and
+/// enumeration values (or defaults) with XHTML stripped to text
+fn object_fields(store: &DocumentStore, object: &SpecObject) -> Vec<Field> {
+    let spec_type = store.spec_type(&object.spec_type);
+    let type_name = spec_type
+        .and_then(|t| t.ident.long_name.clone())
+        .unwrap_or_else(|| object.spec_type.clone());
+    let mut fields = vec![
+        field(IDENTIFIER_FIELD, object.ident.identifier.clone()),
+        field(SPEC_TYPE_FIELD, type_name),
+    ];
+
+    let mut definitions: Vec<&str> = spec_type
+        .map(|t| {
+            t.spec_attributes
+                .iter()
+                .map(|d| d.ident.identifier.as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    for value in &object.values {
+        if !definitions.contains(&value.definition()) {
+            definitions.push(value.definition());
+        }
+    }
+    for definition in definitions {
+        if let Some(
+            value @ (AttributeValue::String { .. }
+            | AttributeValue::XHTML { .. }
+            | AttributeValue::Enumeration { .. }),
+        ) = store.value_or_default(object, definition)
+        {
+            fields.push(field(definition, display_value(store, value)));
+        }
+    }
+    fields
+}
+
+fn field(name: &str, text: String) -> Field {
+    Field {
+        name: name.to_string(),
+        tokens: tokenize(&text),
+        text,
+    }
+}
+
+/// Token ranges `(first, count)` of `tokens` matching `clause`
+fn find(clause: &Clause, tokens: &[Token]) -> Vec<(usize, usize)> {
+    let single = |matches: &dyn Fn(&str) -> bool| {
+        tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| matches(&t.term))
+            .map(|(i, _)| (i, 1))
+            .collect()
+    };
+    match clause {
+        Clause::Term(term) => single(&|t| t == term),
+        Clause::Prefix(prefix) => single(&|t| t.starts_with(prefix.as_str())),
+        Clause::Fuzzy(term, distance) => single(&|t| within_distance(t, term, *distance)),
+        Clause::Phrase(terms) if terms.is_empty() => Vec::new(),
+        Clause::Phrase(terms) => tokens
+            .windows(terms.len())
+            .enumerate()
+            .filter(|(_, window)| window.iter().zip(terms).all(|(t, term)| &t.term == term))
+            .map(|(i, _)| (i, terms.len()))
+            .collect(),
+    }
+}
+
+/// Whether `a` becomes `b` with at most `distance` single-character edits
+fn within_distance(a: &str, b: &str, distance: u8) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let distance = distance as usize;
+    if a.len().abs_diff(b.len()) > distance {
+        return false;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|&d| d > distance) {
+            return false;
+        }
+        previous = current;
+    }
+    previous[b.len()] <= distance
+}
+
+/// Excerpt of `field` around its first match, highlighting every match
+/// inside the excerpt
+fn snippet(field: &Field, mut spans: Vec<(usize, usize)>) -> Snippet {
+    spans.sort_unstable();
+    spans.dedup();
+    let ranges: Vec<(usize, usize)> = spans
+        .iter()
+        .map(|&(first, count)| {
+            (
+                field.tokens[first].start,
+                field.tokens[first + count - 1].end,
+            )
+        })
+        .collect();
+    let chars: Vec<char> = field.text.chars().collect();
+    let (first_start, first_end) = ranges[0];
+
+    let mut start = first_start.saturating_sub(SNIPPET_CONTEXT);
+    if start > 0 {
+        // Begin at a word boundary
+        if let Some(space) = chars[start..first_start]
+            .iter()
+            .position(|c| c.is_whitespace())
+        {
+            start += space + 1;
+        }
+    }
+    let mut end = (first_end + SNIPPET_CONTEXT).min(chars.len());
+    if end < chars.len() {
+        if let Some(space) = chars[first_end..end]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+        {
+            end = first_end + space;
+        }
+    }
+
+    let mut fragment = String::new();
+    let mut shift = start;
+    if start > 0 {
+        fragment.push('…');
+        shift -= 1;
+    }
+    fragment.extend(&chars[start..end]);
+    if end < chars.len() {
+        fragment.push('…');
+    }
+    let mut highlights: Vec<Highlight> = Vec::new();
+    for &(s, e) in ranges.iter().filter(|&&(s, e)| s >= start && e <= end) {
+        match highlights.last_mut() {
+            // Merge overlapping matches of different clauses
+            Some(last) if s - shift <= last.end => last.end = last.end.max(e - shift),
+            _ => highlights.push(Highlight {
+                start: s - shift,
+                end: e - shift,
+            }),
+        }
+    }
+    Snippet {
+        field: field.name.clone(),
+        fragment,
+        highlights,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqif::parser;
+    use crate::reqif::undo::{Edit, Journal};
+    use crate::search::query::parse_query;
+
+    const SMALL: &str = include_str!("../../../tests/fixtures/small.reqif");
+
+    fn search(index: &SearchIndex, query: &str) -> Vec<SearchHit> {
+        index.search(&parse_query(query).unwrap(), 10).unwrap()
+    }
+
+    fn objects(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.object.as_str()).collect()
+    }
+
+    #[test]
+    fn test_phrase_prefix_and_fuzzy_queries_with_snippets() {
+        let store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let mut reported = Vec::new();
+        let index = SearchIndex::build_until(&store, |done| {
+            reported.push(done);
+            true
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(reported, [0, 3]);
+        assert!(SearchIndex::build_until(&store, |_| false)
+            .unwrap()
+            .is_none());
+
+        let hits = search(&index, "\"user actions\"");
+        assert_eq!(objects(&hits), ["req-003"]);
+        let title = &hits[0].snippets[0];
+        assert_eq!(title.field, "ad-title");
+        assert_eq!(title.fragment, "System shall log all user actions");
+        assert_eq!(title.highlights, [Highlight { start: 21, end: 33 }]);
+        let description = &hits[0].snippets[1];
+        assert_eq!(description.field, "ad-description");
+        assert!(!description.fragment.contains('<'));
+
+        assert_eq!(objects(&search(&index, "authent*")), ["req-002"]);
+        assert_eq!(objects(&search(&index, "autentication~")), ["req-002"]);
+        assert!(search(&index, "autentcation~").is_empty());
+        assert_eq!(objects(&search(&index, "req-001")), ["req-001"]);
+        assert_eq!(search(&index, "requirement").len(), 3);
+        assert!(search(&index, "\"actions user\"").is_empty());
+    }
+
+    #[test]
+    fn test_index_follows_edits() {
+        let mut store = DocumentStore::new(parser::parse_str(SMALL).unwrap());
+        let mut index = SearchIndex::build(&store).unwrap();
+        let mut journal = Journal::default();
+        journal
+            .apply(
+                &mut store,
+                Edit::SetValue {
+                    object: "req-001".into(),
+                    value: AttributeValue::String {
+                        definition: "ad-title".into(),
+                        value: "Boot within a heartbeat".into(),
+                        extras: Extras::default(),
+                    },
+                },
+            )
+            .unwrap();
+        journal
+            .apply(
+                &mut store,
+                Edit::DeleteObject {
+                    object: "req-003".into(),
+                },
+            )
+            .unwrap();
+        index
+            .apply_changes(&store, &journal.take_changed())
+            .unwrap();
+        assert_eq!(objects(&search(&index, "heartbeat")), ["req-001"]);
+        assert!(search(&index, "logged").is_empty());
+        assert_eq!(index.len(), 2);
+
+        journal.undo(&mut store).unwrap();
+        index
+            .apply_changes(&store, &journal.take_changed())
+            .unwrap();
+        assert_eq!(objects(&search(&index, "logged")), ["req-003"]);
+
+        // An index built on disk reopens with its content
+        let dir =
+            std::env::temp_dir().join(format!("reqsmith-search-index-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        drop(SearchIndex::build_in(&dir, &store).unwrap());
+        let reopened = SearchIndex::open(&dir).unwrap();
+        assert_eq!(reopened.len(), 3);
+        assert_eq!(objects(&search(&reopened, "heartbeat")), ["req-001"]);
+        drop(reopened);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
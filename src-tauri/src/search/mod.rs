@@ -0,0 +1,5 @@
+// Search module - Full-text search over requirement attributes
+
+pub mod filter;
+pub mod index;
+pub mod query;
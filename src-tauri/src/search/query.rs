@@ -0,0 +1,133 @@
+// Search queries - Parses the query syntax of the search box
+
+use super::index::tokenize;
+use thiserror::Error;
+
+/// Longest edit distance a fuzzy term may ask for
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum QueryError {
+    #[error("the query has no search terms")]
+    Empty,
+    #[error("missing closing quote")]
+    UnterminatedPhrase,
+    #[error("'{0}' is not a fuzzy distance between 1 and 2")]
+    FuzzyDistance(String),
+}
+
+/// One part of a query that an object must match
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+    Term(String),
+    /// Any term starting with the text
+    Prefix(String),
+    /// Any term within the edit distance
+    Fuzzy(String, u8),
+    /// Consecutive terms
+    Phrase(Vec<String>),
+}
+
+/// Clauses that must all match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+/// Parse `input`. Words match whole terms; `"several words"` match as a
+/// phrase, `word*` as a prefix and `word~` or `word~2` within that many
+/// edits. Text is matched case-insensitively.
+pub fn parse_query(input: &str) -> Result<Query, QueryError> {
+    let mut clauses = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').ok_or(QueryError::UnterminatedPhrase)?;
+            clauses.extend(words(&quoted[..end]));
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            clauses.extend(word(&rest[..end])?);
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    if clauses.is_empty() {
+        return Err(QueryError::Empty);
+    }
+    Ok(Query { clauses })
+}
+
+/// Clause for a single word with its operator
+fn word(text: &str) -> Result<Option<Clause>, QueryError> {
+    if let Some(prefix) = text.strip_suffix('*') {
+        // A prefix spanning several terms matches them as a phrase
+        let mut terms = terms(prefix);
+        return Ok(match terms.len() {
+            0 => None,
+            1 => terms.pop().map(Clause::Prefix),
+            _ => Some(Clause::Phrase(terms)),
+        });
+    }
+    if let Some((term, distance)) = text.split_once('~') {
+        let distance = match distance {
+            "" => 1,
+            d => d
+                .parse::<u8>()
+                .ok()
+                .filter(|d| (1..=MAX_FUZZY_DISTANCE).contains(d))
+                .ok_or_else(|| QueryError::FuzzyDistance(d.to_string()))?,
+        };
+        let mut terms = terms(term);
+        return Ok(match terms.len() {
+            0 => None,
+            1 => terms.pop().map(|t| Clause::Fuzzy(t, distance)),
+            _ => Some(Clause::Phrase(terms)),
+        });
+    }
+    Ok(words(text))
+}
+
+/// Term, or phrase when `text` holds several terms, e.g. `req-001`
+fn words(text: &str) -> Option<Clause> {
+    let mut terms = terms(text);
+    match terms.len() {
+        0 => None,
+        1 => terms.pop().map(Clause::Term),
+        _ => Some(Clause::Phrase(terms)),
+    }
+}
+
+fn terms(text: &str) -> Vec<String> {
+    tokenize(text).into_iter().map(|t| t.term).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query_operators() {
+        let query = parse_query(r#"Start "user  Actions" auth* logg~ req-001 sistem~2"#).unwrap();
+        assert_eq!(
+            query.clauses,
+            [
+                Clause::Term("start".into()),
+                Clause::Phrase(vec!["user".into(), "actions".into()]),
+                Clause::Prefix("auth".into()),
+                Clause::Fuzzy("logg".into(), 1),
+                Clause::Phrase(vec!["req".into(), "001".into()]),
+                Clause::Fuzzy("sistem".into(), 2),
+            ]
+        );
+        assert_eq!(parse_query("  -- "), Err(QueryError::Empty));
+        assert_eq!(
+            parse_query("\"open phrase"),
+            Err(QueryError::UnterminatedPhrase)
+        );
+        assert_eq!(
+            parse_query("term~5"),
+            Err(QueryError::FuzzyDistance("5".into()))
+        );
+    }
+}